@@ -0,0 +1,222 @@
+//! Throughput of lookups, insertions and evictions of the caches.
+//! Run with `cargo bench`. The workloads are generated with a fixed
+//! seed so that runs of different revisions draw the same keys.
+
+#[macro_use]
+extern crate criterion;
+extern crate specie;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+use specie::arc::ARCCache;
+use specie::car::CARCache;
+use specie::clock::ClockCache;
+use specie::clockpro::ClockProCache;
+use specie::fifo::FIFOCache;
+use specie::gdsf::GDSFCache;
+use specie::hyperbolic::HyperbolicCache;
+use specie::lfu::LFUCache;
+use specie::lirs::LIRSCache;
+use specie::lru::{LRUCache, SampledLRUCache};
+use specie::lruk::LRUKCache;
+use specie::mq::MQCache;
+use specie::mru::MRUCache;
+use specie::random::RandomCache;
+use specie::s3fifo::S3FIFOCache;
+use specie::sieve::SieveCache;
+use specie::slru::SLRUCache;
+use specie::tinylfu::WTinyLFUCache;
+#[cfg(feature = "std")]
+use specie::tlru::TLRUCache;
+use specie::twoq::TwoQueueCache;
+use std::hash::Hash;
+use std::hint::black_box;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+// capacities of the caches, in entries
+const CAPACITIES: [usize; 3] = [64, 4096, 262_144];
+// number of operations in each iteration
+const OPS: usize = 4096;
+
+// A xorshift generator for the keys of the workloads.
+struct Keys(u64);
+
+impl Keys {
+    fn next(&mut self, range: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % range as u64) as usize
+    }
+
+    // Keys drawn uniformly from a space that is the capacity divided by
+    // the hit ratio, so that a full cache hits about that ratio of lookups.
+    fn workload(capacity: usize, hit_ratio: f64) -> Vec<usize> {
+        let space = (capacity as f64 / hit_ratio) as usize;
+        let mut keys = Keys(0x2545_f491_4f6c_dd1d);
+        (0..OPS).map(|_| keys.next(space)).collect()
+    }
+}
+
+// The operations that every cache design provides.
+trait Bench<K> {
+    fn with_capacity(capacity: usize) -> Self;
+    fn get(&mut self, key: &K) -> bool;
+    fn insert(&mut self, key: K);
+}
+
+macro_rules! bench_cache {
+    ($($cache:ident),*) => {
+        $(
+            impl<K: Eq + Hash> Bench<K> for $cache<K, u64> {
+                fn with_capacity(capacity: usize) -> Self {
+                    $cache::new(capacity)
+                }
+
+                fn get(&mut self, key: &K) -> bool {
+                    $cache::get(self, key).is_some()
+                }
+
+                fn insert(&mut self, key: K) {
+                    black_box($cache::insert(self, key, 0));
+                }
+            }
+        )*
+    }
+}
+
+bench_cache!(LRUCache, SampledLRUCache, ClockCache, LFUCache, FIFOCache, MRUCache, RandomCache, ARCCache,
+             TwoQueueCache, SLRUCache, LRUKCache, LIRSCache, CARCache,
+             WTinyLFUCache, GDSFCache, MQCache, S3FIFOCache, SieveCache, ClockProCache, HyperbolicCache);
+
+// The time-to-use outlasts the benchmarks, so that TLRU is measured
+// with the cost of tracking the deadlines and without expirations.
+#[cfg(feature = "std")]
+impl<K: Eq + Hash> Bench<K> for TLRUCache<K, u64> {
+    fn with_capacity(capacity: usize) -> Self {
+        TLRUCache::new(capacity, Duration::from_secs(3600))
+    }
+
+    fn get(&mut self, key: &K) -> bool {
+        TLRUCache::get(self, key).is_some()
+    }
+
+    fn insert(&mut self, key: K) {
+        black_box(TLRUCache::insert(self, key, 0));
+    }
+}
+
+// A cache that holds every key of the space up to its capacity.
+fn filled<C: Bench<K>, K>(capacity: usize, key: &dyn Fn(usize) -> K) -> C {
+    let mut cache = C::with_capacity(capacity);
+    for i in 0..capacity {
+        cache.insert(key(i));
+    }
+    cache
+}
+
+// Lookups that fill the cache on a miss, at several hit ratios.
+fn get_or_insert<C: Bench<K>, K: Clone>(c: &mut Criterion, name: &str, key: &dyn Fn(usize) -> K) {
+    let mut group = c.benchmark_group(format!("get_or_insert/{}", name));
+    group.throughput(Throughput::Elements(OPS as u64));
+    for &capacity in CAPACITIES.iter() {
+        for &ratio in [0.5, 0.9, 0.99].iter() {
+            let keys: Vec<K> = Keys::workload(capacity, ratio).into_iter().map(key).collect();
+            let mut cache: C = filled(capacity, key);
+            let id = BenchmarkId::new(format!("{}", capacity), ratio);
+            group.bench_function(id, |b| {
+                b.iter_batched(|| keys.clone(),
+                               |keys| {
+                                   for k in keys {
+                                       if !cache.get(&k) {
+                                           cache.insert(k);
+                                       }
+                                   }
+                               },
+                               BatchSize::SmallInput)
+            });
+        }
+    }
+    group.finish();
+}
+
+// Lookups of keys that are all cached.
+fn get_hit<C: Bench<K>, K>(c: &mut Criterion, name: &str, key: &dyn Fn(usize) -> K) {
+    let mut group = c.benchmark_group(format!("get_hit/{}", name));
+    group.throughput(Throughput::Elements(OPS as u64));
+    for &capacity in CAPACITIES.iter() {
+        let keys: Vec<K> = Keys::workload(capacity, 1.0).into_iter().map(key).collect();
+        let mut cache: C = filled(capacity, key);
+        group.bench_function(BenchmarkId::from_parameter(capacity), |b| {
+            b.iter(|| {
+                for k in &keys {
+                    black_box(cache.get(k));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// Insertions of new keys into a full cache, which evict on every insertion.
+fn insert_evict<C: Bench<K>, K>(c: &mut Criterion, name: &str, key: &dyn Fn(usize) -> K) {
+    let mut group = c.benchmark_group(format!("insert_evict/{}", name));
+    group.throughput(Throughput::Elements(OPS as u64));
+    for &capacity in CAPACITIES.iter() {
+        let mut cache: C = filled(capacity, key);
+        let mut next = capacity;
+        group.bench_function(BenchmarkId::from_parameter(capacity), |b| {
+            b.iter_batched(|| {
+                               next += OPS;
+                               (next - OPS..next).map(key).collect::<Vec<_>>()
+                           },
+                           |keys| {
+                               for k in keys {
+                                   cache.insert(k);
+                               }
+                           },
+                           BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+// Every workload for one design of the cache.
+fn design<C: Bench<K>, K: Clone>(c: &mut Criterion, name: &str, key: &dyn Fn(usize) -> K) {
+    get_hit::<C, K>(c, name, key);
+    get_or_insert::<C, K>(c, name, key);
+    insert_evict::<C, K>(c, name, key);
+}
+
+fn designs<K: Eq + Hash + Clone>(c: &mut Criterion, keys: &str, key: &dyn Fn(usize) -> K) {
+    design::<LRUCache<K, u64>, K>(c, &format!("lru/{}", keys), key);
+    design::<SampledLRUCache<K, u64>, K>(c, &format!("sampled/{}", keys), key);
+    design::<ClockCache<K, u64>, K>(c, &format!("clock/{}", keys), key);
+    design::<LFUCache<K, u64>, K>(c, &format!("lfu/{}", keys), key);
+    design::<FIFOCache<K, u64>, K>(c, &format!("fifo/{}", keys), key);
+    design::<MRUCache<K, u64>, K>(c, &format!("mru/{}", keys), key);
+    design::<RandomCache<K, u64>, K>(c, &format!("random/{}", keys), key);
+    design::<ARCCache<K, u64>, K>(c, &format!("arc/{}", keys), key);
+    design::<TwoQueueCache<K, u64>, K>(c, &format!("2q/{}", keys), key);
+    design::<SLRUCache<K, u64>, K>(c, &format!("slru/{}", keys), key);
+    design::<LRUKCache<K, u64>, K>(c, &format!("lruk/{}", keys), key);
+    design::<LIRSCache<K, u64>, K>(c, &format!("lirs/{}", keys), key);
+    design::<CARCache<K, u64>, K>(c, &format!("car/{}", keys), key);
+    design::<WTinyLFUCache<K, u64>, K>(c, &format!("tinylfu/{}", keys), key);
+    design::<GDSFCache<K, u64>, K>(c, &format!("gdsf/{}", keys), key);
+    design::<MQCache<K, u64>, K>(c, &format!("mq/{}", keys), key);
+    #[cfg(feature = "std")]
+    design::<TLRUCache<K, u64>, K>(c, &format!("tlru/{}", keys), key);
+    design::<S3FIFOCache<K, u64>, K>(c, &format!("s3fifo/{}", keys), key);
+    design::<SieveCache<K, u64>, K>(c, &format!("sieve/{}", keys), key);
+    design::<ClockProCache<K, u64>, K>(c, &format!("clockpro/{}", keys), key);
+    design::<HyperbolicCache<K, u64>, K>(c, &format!("hyperbolic/{}", keys), key);
+}
+
+fn benches(c: &mut Criterion) {
+    designs(c, "u64", &|i| i as u64);
+    designs(c, "string", &|i| format!("key-{:08}", i));
+}
+
+criterion_group!(cache, benches);
+criterion_main!(cache);
@@ -0,0 +1,382 @@
+//! A cache that chooses between two replacement policies at runtime, by
+//! simulating both of them on the recent accesses with ghost entries.
+
+use DefaultHashBuilder;
+use cache::Cache;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use core::time::Duration;
+use error::CapacityError;
+use lru::{EvictionReason, InsertResult};
+use policy::{LFUPolicy, LRUPolicy, Policy, PolicyCache};
+use stats::Stats;
+#[cfg(feature = "std")]
+use time::Clock;
+
+// number of windows that the other policy must win before it is used
+const DEFAULT_PATIENCE: usize = 3;
+
+// One of the two policies of an adaptive cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Choice {
+    First,
+    Second,
+}
+
+// Both policies order every entry of the cache, so that either of them
+// can choose the evicted entries as soon as it is chosen.
+struct Switch<A, B> {
+    first: A,
+    second: B,
+    choice: Choice,
+}
+
+impl<A: Policy, B: Policy> Policy for Switch<A, B> {
+    fn insert(&mut self, slot: usize) {
+        self.first.insert(slot);
+        self.second.insert(slot);
+    }
+
+    fn access(&mut self, slot: usize) {
+        self.first.access(slot);
+        self.second.access(slot);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.first.remove(slot);
+        self.second.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let slot = match self.choice {
+            Choice::First => self.first.evict()?,
+            Choice::Second => self.second.evict()?,
+        };
+        match self.choice {
+            Choice::First => self.second.remove(slot),
+            Choice::Second => self.first.remove(slot),
+        }
+        Some(slot)
+    }
+
+    fn clear(&mut self) {
+        self.first.clear();
+        self.second.clear();
+    }
+}
+
+// Workloads drift between favouring recency and favouring frequency.
+// Besides the entries, the cache keeps a shadow cache of ghost entries
+// for each policy, which stores the hashes of the keys that the policy
+// would cache at the same capacity. The lookups are divided into windows,
+// and the policy whose shadow hits more often in a window wins it. When
+// the policy that is not in use wins a number of consecutive windows,
+// the cache switches to it. The cache starts with the first policy, and
+// by default chooses between LRU and LFU.
+pub struct AdaptiveCache<K, V, A = LRUPolicy, B = LFUPolicy, S = DefaultHashBuilder> {
+    cache: PolicyCache<K, V, Switch<A, B>, S>,
+    // ghost entries of the keys that each policy would cache
+    shadows: (PolicyCache<u64, (), A>, PolicyCache<u64, (), B>),
+    // number of lookups in a window
+    window: usize,
+    // number of consecutive windows that the other policy must win
+    patience: usize,
+    // number of lookups in the current window
+    lookups: usize,
+    // hits of the shadows at the start of the current window
+    marks: (u64, u64),
+    // number of consecutive windows won by the policy that is not in use
+    streak: usize,
+    switches: u64,
+}
+
+impl<K, V, A, B> AdaptiveCache<K, V, A, B>
+    where K: Eq + Hash,
+          A: Policy + Default,
+          B: Policy + Default
+{
+    // The window is as long as the capacity.
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> AdaptiveCache<K, V, A, B> {
+        AdaptiveCache::with_window(capacity, capacity, DEFAULT_PATIENCE)
+    }
+
+    // Panics if the capacity is zero.
+    pub fn with_window(capacity: usize, window: usize, patience: usize) -> AdaptiveCache<K, V, A, B> {
+        AdaptiveCache::with_hasher(capacity, window, patience, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<AdaptiveCache<K, V, A, B>, CapacityError> {
+        AdaptiveCache::try_with_hasher(capacity, capacity, DEFAULT_PATIENCE, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, A, B, S> AdaptiveCache<K, V, A, B, S>
+    where K: Eq + Hash,
+          A: Policy + Default,
+          B: Policy + Default,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize,
+                       window: usize,
+                       patience: usize,
+                       hash_builder: S)
+                       -> AdaptiveCache<K, V, A, B, S> {
+        match AdaptiveCache::try_with_hasher(capacity, window, patience, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the window or the patience is zero.
+    pub fn try_with_hasher(capacity: usize,
+                           window: usize,
+                           patience: usize,
+                           hash_builder: S)
+                           -> Result<AdaptiveCache<K, V, A, B, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        assert!(window > 0, "a window must have at least one lookup");
+        assert!(patience > 0, "the patience must be at least one window");
+        let switch = Switch {
+            first: A::default(),
+            second: B::default(),
+            choice: Choice::First,
+        };
+        Ok(AdaptiveCache {
+            cache: PolicyCache::try_with_hasher(capacity, switch, hash_builder)?,
+            shadows: (PolicyCache::try_new(capacity)?, PolicyCache::try_new(capacity)?),
+            window,
+            patience,
+            lookups: 0,
+            marks: (0, 0),
+            streak: 0,
+            switches: 0,
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.observe(key);
+        self.cache.get(key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.observe(key);
+        self.cache.get_mut(key)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.cache.peek(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // A key that a shadow does not hold is added to it.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.cache.hash_builder().hash_one(&key);
+        if !self.shadows.0.contains_key(&hash) {
+            self.shadows.0.insert(hash, ());
+        }
+        if !self.shadows.1.contains_key(&hash) {
+            self.shadows.1.insert(hash, ());
+        }
+        self.cache.insert(key, val)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.cache.hash_builder().hash_one(key);
+        self.shadows.0.remove(&hash);
+        self.shadows.1.remove(&hash);
+        self.cache.remove(key)
+    }
+
+    // The shadows are cleared and a new window starts,
+    // but the cache keeps its policy.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.shadows.0.clear();
+        self.shadows.1.clear();
+        self.lookups = 0;
+        self.marks = self.shadow_hits();
+        self.streak = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        self.cache.stats()
+    }
+
+    // The policy that chooses the evicted entries.
+    pub fn choice(&self) -> Choice {
+        self.cache.policy().choice
+    }
+
+    // number of times that the cache changed its policy
+    pub fn switches(&self) -> u64 {
+        self.switches
+    }
+
+    // The number of hits of the shadows of the first
+    // and the second policy, since the cache was created.
+    pub fn shadow_hits(&self) -> (u64, u64) {
+        (self.shadows.0.stats().hits(), self.shadows.1.stats().hits())
+    }
+
+    pub fn set_on_evict<F>(&mut self, f: F)
+        where F: FnMut(K, V) + Send + Sync + 'static
+    {
+        self.cache.set_on_evict(f);
+    }
+
+    pub fn set_listener<F>(&mut self, f: F)
+        where F: FnMut(&K, &V, EvictionReason) + Send + Sync + 'static
+    {
+        self.cache.set_listener(f);
+    }
+
+    // The shadows do not expire, so they describe the
+    // policies rather than the time-to-live.
+    #[cfg(feature = "std")]
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.cache.set_ttl(ttl);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.cache.set_clock(clock);
+    }
+
+    // Look up the key in both shadows, and compare the
+    // policies at the end of each window.
+    fn observe<Q>(&mut self, key: &Q)
+        where Q: ?Sized + Hash
+    {
+        let hash = self.cache.hash_builder().hash_one(key);
+        self.shadows.0.get(&hash);
+        self.shadows.1.get(&hash);
+        self.lookups += 1;
+        if self.lookups < self.window {
+            return;
+        }
+        let hits = self.shadow_hits();
+        let gains = (hits.0 - self.marks.0, hits.1 - self.marks.1);
+        let choice = self.choice();
+        let won = match choice {
+            Choice::First => gains.1 > gains.0,
+            Choice::Second => gains.0 > gains.1,
+        };
+        self.streak = if won { self.streak + 1 } else { 0 };
+        if self.streak >= self.patience {
+            self.cache.policy_mut().choice = match choice {
+                Choice::First => Choice::Second,
+                Choice::Second => Choice::First,
+            };
+            self.switches += 1;
+            self.streak = 0;
+        }
+        self.lookups = 0;
+        self.marks = hits;
+    }
+}
+
+impl<K, V, A, B, S> Cache<K, V> for AdaptiveCache<K, V, A, B, S>
+    where K: Eq + Hash,
+          A: Policy + Default,
+          B: Policy + Default,
+          S: BuildHasher
+{
+    fn get(&mut self, key: &K) -> Option<&V> {
+        AdaptiveCache::get(self, key)
+    }
+
+    fn peek(&self, key: &K) -> Option<&V> {
+        AdaptiveCache::peek(self, key)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        AdaptiveCache::insert(self, key, val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        AdaptiveCache::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        AdaptiveCache::clear(self)
+    }
+
+    fn len(&self) -> usize {
+        AdaptiveCache::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(AdaptiveCache::capacity(self))
+    }
+
+    fn stats(&self) -> &Stats {
+        AdaptiveCache::stats(self)
+    }
+}
+
+#[test]
+fn adaptive_switching() {
+    fn access(cache: &mut AdaptiveCache<u32, u32>, key: u32) {
+        if cache.get(&key).is_none() {
+            cache.insert(key, key);
+        }
+    }
+
+    let mut cache = AdaptiveCache::with_window(4, 8, 2);
+    // Two popular keys between scans of new keys favour LFU.
+    for round in 0..6 {
+        for &key in &[0, 1, 0, 1] {
+            access(&mut cache, key);
+        }
+        for key in 0..4 {
+            access(&mut cache, 100 + 4 * round + key);
+        }
+    }
+    assert_eq!((Choice::Second, 1), (cache.choice(), cache.switches()));
+    // LFU keeps the popular keys once the workload moves to a
+    // loop that fits in the cache, which favours LRU.
+    for _ in 0..6 {
+        for key in 10..14 {
+            access(&mut cache, key);
+        }
+    }
+    assert_eq!((Choice::First, 2), (cache.choice(), cache.switches()));
+    let (lru, lfu) = cache.shadow_hits();
+    assert!(lru > lfu);
+}
@@ -0,0 +1,415 @@
+//! Adaptive Replacement Cache, as described by Megiddo and Modha in
+//! "ARC: A Self-Tuning, Low Overhead Replacement Cache" (FAST 2003).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// The pair of lists of a key: T1 and B1, or T2 and B2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    // keys that were accessed once since they entered the cache
+    Recent,
+    // keys that were accessed at least twice
+    Frequent,
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+}
+
+// The cached entries are split between T1, the entries that were
+// accessed once, and T2, the entries that were accessed again. The
+// keys evicted from T1 and T2 are remembered in the ghost lists B1 and
+// B2. An insertion of a key that is in B1 shows that T1 is too small,
+// and one in B2 that T2 is too small, and the target size of T1 is
+// adapted accordingly. Eviction takes the least-recently used entry of
+// T1 while T1 is larger than its target, and of T2 otherwise.
+//
+// The ghost lists record only the hashes of the keys, so that keys are
+// not cloned or kept alive after their eviction. A collision of hashes
+// can only make the adaptation less precise.
+pub struct ARCCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // target size of T1, the adaptation parameter p
+    target: usize,
+    hash_builder: S,
+    // locations of the cached entries in T1 and T2, hashed by key
+    table: HashTable<(Side, usize)>,
+    // T1 and T2, each from the most-recently used entry at the front
+    // to the least-recently used entry at the back
+    recent: List<Entry<K, V>>,
+    frequent: List<Entry<K, V>>,
+    // locations of the hashes in B1 and B2
+    ghosts: HashTable<(Side, usize)>,
+    // B1 and B2, each from the most recently evicted hash at the front
+    recent_ghosts: List<u64>,
+    frequent_ghosts: List<u64>,
+    stats: Stats,
+}
+
+impl<K, V> ARCCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> ARCCache<K, V> {
+        ARCCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<ARCCache<K, V>, CapacityError> {
+        ARCCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ARCCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> ARCCache<K, V, S> {
+        match ARCCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<ARCCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(ARCCache {
+            capacity,
+            target: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            recent: List::new(),
+            frequent: List::new(),
+            ghosts: HashTable::with_capacity(capacity),
+            recent_ghosts: List::new(),
+            frequent_ghosts: List::new(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without promoting the entry.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(side, index)| &self.list(side).get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(loc) = self.locate(hash, &key) {
+            let index = self.promote(loc);
+            self.stats.replacements += 1;
+            let prev = mem::replace(&mut self.frequent.get_mut(index).val, val);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        // The sizes of the ghost lists exclude the ghost of the key,
+        // which is removed before the target is adapted.
+        let full = self.len() >= self.capacity;
+        let (side, evicted) = match self.take_ghost(hash) {
+            Some(Side::Recent) => {
+                // T1 was too small to keep the key until its second access.
+                let delta = cmp::max(self.frequent_ghosts.len() / (self.recent_ghosts.len() + 1), 1);
+                self.target = cmp::min(self.target + delta, self.capacity);
+                (Side::Frequent, if full { self.replace(false) } else { None })
+            }
+            Some(Side::Frequent) => {
+                // T2 was too small to keep the key.
+                let delta = cmp::max(self.recent_ghosts.len() / (self.frequent_ghosts.len() + 1), 1);
+                self.target = self.target.saturating_sub(delta);
+                (Side::Frequent, if full { self.replace(true) } else { None })
+            }
+            None => (Side::Recent, self.make_room()),
+        };
+        if evicted.is_some() {
+            self.stats.evictions += 1;
+        }
+        let entry = Entry { key, val, hash };
+        let index = self.list_mut(side).push_front(entry);
+        let (recent, frequent) = (&self.recent, &self.frequent);
+        self.table.insert_unique(hash, (side, index), |&loc| entry_hash(recent, frequent, loc));
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // The ghost lists are cleared and the adaptation starts over.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.recent.clear();
+        self.frequent.clear();
+        self.ghosts.clear();
+        self.recent_ghosts.clear();
+        self.frequent_ghosts.clear();
+        self.target = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.frequent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The number of cached entries that the cache aims to keep in T1,
+    // among the entries that were accessed once. This is the adaptation
+    // parameter p, between zero and the capacity.
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    // The number of cached entries in T1 and in T2.
+    pub fn split(&self) -> (usize, usize) {
+        (self.recent.len(), self.frequent.len())
+    }
+
+    // The number of evicted keys that are remembered in B1 and in B2.
+    pub fn ghosts(&self) -> (usize, usize) {
+        (self.recent_ghosts.len(), self.frequent_ghosts.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        let index = self.promote(loc?);
+        Some(self.frequent.get_mut(index))
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(Side, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(side, i)| self.list(side).get(i).key.borrow() == key)
+            .copied()
+    }
+
+    fn list(&self, side: Side) -> &List<Entry<K, V>> {
+        match side {
+            Side::Recent => &self.recent,
+            Side::Frequent => &self.frequent,
+        }
+    }
+
+    fn list_mut(&mut self, side: Side) -> &mut List<Entry<K, V>> {
+        match side {
+            Side::Recent => &mut self.recent,
+            Side::Frequent => &mut self.frequent,
+        }
+    }
+
+    // Move an accessed entry to the most-recently used position of T2.
+    // Returns the index of the entry in T2.
+    fn promote(&mut self, loc: (Side, usize)) -> usize {
+        let index = match loc {
+            (Side::Frequent, index) => {
+                self.frequent.move_to_front(index);
+                return index;
+            }
+            (Side::Recent, index) => index,
+        };
+        let entry = self.recent.remove(index);
+        let hash = entry.hash;
+        let moved = self.frequent.push_front(entry);
+        *self.table
+            .find_mut(hash, |&l| l == loc)
+            .expect("table and lists are out of sync") = (Side::Frequent, moved);
+        moved
+    }
+
+    // Make room for a key that is in neither the cache nor the ghost lists.
+    // B1 and T1 together hold at most capacity keys, and the four lists
+    // together hold at most twice the capacity.
+    fn make_room(&mut self) -> Option<(K, V)> {
+        let recent = self.recent.len() + self.recent_ghosts.len();
+        let total = recent + self.frequent.len() + self.frequent_ghosts.len();
+        if recent >= self.capacity {
+            if self.recent.len() < self.capacity {
+                self.drop_ghost(Side::Recent);
+                if self.len() >= self.capacity { self.replace(false) } else { None }
+            } else {
+                // B1 is empty, so the entry is evicted without a ghost.
+                let index = self.recent.back().expect("T1 is full");
+                let e = self.take_entry((Side::Recent, index));
+                Some((e.key, e.val))
+            }
+        } else if total >= self.capacity {
+            if total >= 2 * self.capacity {
+                self.drop_ghost(Side::Frequent);
+            }
+            if self.len() >= self.capacity { self.replace(false) } else { None }
+        } else {
+            None
+        }
+    }
+
+    // Evict the least-recently used entry of T1 if T1 exceeds its target,
+    // or the least-recently used entry of T2 otherwise, and remember
+    // its key in the matching ghost list.
+    fn replace(&mut self, frequent_ghost: bool) -> Option<(K, V)> {
+        let t1 = self.recent.len();
+        let side = if t1 > 0 && (t1 > self.target || (frequent_ghost && t1 == self.target)) ||
+                      self.frequent.len() == 0 {
+            Side::Recent
+        } else {
+            Side::Frequent
+        };
+        let index = self.list(side).back()?;
+        let e = self.take_entry((side, index));
+        let ghosts = match side {
+            Side::Recent => &mut self.recent_ghosts,
+            Side::Frequent => &mut self.frequent_ghosts,
+        };
+        let ghost = ghosts.push_front(e.hash);
+        let (b1, b2) = (&self.recent_ghosts, &self.frequent_ghosts);
+        self.ghosts.insert_unique(e.hash, (side, ghost), |&loc| ghost_hash(b1, b2, loc));
+        Some((e.key, e.val))
+    }
+
+    // Forget the oldest key of a ghost list.
+    fn drop_ghost(&mut self, side: Side) {
+        let ghosts = match side {
+            Side::Recent => &mut self.recent_ghosts,
+            Side::Frequent => &mut self.frequent_ghosts,
+        };
+        if let Some(index) = ghosts.back() {
+            let hash = ghosts.remove(index);
+            self.ghosts
+                .find_entry(hash, |&loc| loc == (side, index))
+                .expect("ghost table and lists are out of sync")
+                .remove();
+        }
+    }
+
+    // Remove the ghost of a key and return the list that held it.
+    fn take_ghost(&mut self, hash: u64) -> Option<Side> {
+        let (b1, b2) = (&self.recent_ghosts, &self.frequent_ghosts);
+        let ((side, index), _) = self.ghosts
+            .find_entry(hash, |&loc| ghost_hash(b1, b2, loc) == hash)
+            .ok()?
+            .remove();
+        match side {
+            Side::Recent => self.recent_ghosts.remove(index),
+            Side::Frequent => self.frequent_ghosts.remove(index),
+        };
+        Some(side)
+    }
+
+    // Remove a cached entry from the table and its list.
+    fn take_entry(&mut self, loc: (Side, usize)) -> Entry<K, V> {
+        let hash = self.list(loc.0).get(loc.1).hash;
+        self.table
+            .find_entry(hash, |&l| l == loc)
+            .expect("table and lists are out of sync")
+            .remove();
+        self.list_mut(loc.0).remove(loc.1)
+    }
+}
+
+fn entry_hash<K, V>(recent: &List<Entry<K, V>>,
+                    frequent: &List<Entry<K, V>>,
+                    (side, index): (Side, usize))
+                    -> u64 {
+    match side {
+        Side::Recent => recent.get(index).hash,
+        Side::Frequent => frequent.get(index).hash,
+    }
+}
+
+fn ghost_hash(recent: &List<u64>, frequent: &List<u64>, (side, index): (Side, usize)) -> u64 {
+    match side {
+        Side::Recent => *recent.get(index),
+        Side::Frequent => *frequent.get(index),
+    }
+}
+
+#[test]
+fn arc_adaptation() {
+    let mut cache = ARCCache::new(4);
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&0);
+    cache.get(&1);
+    assert_eq!((2, 2), cache.split());
+    // T1 is larger than its target, so the entries accessed once are evicted.
+    assert_eq!(Some((2, 2)), cache.insert(4, 4).evicted);
+    assert_eq!(Some((3, 3)), cache.insert(5, 5).evicted);
+    assert_eq!((2, 0), cache.ghosts());
+    // A key that returns from B1 grows the target of T1.
+    assert_eq!(Some((4, 4)), cache.insert(2, 2).evicted);
+    assert_eq!(1, cache.target());
+    assert_eq!((1, 3), cache.split());
+    assert_eq!(Some(&0), cache.peek(&0));
+    // Once T1 is within its target, the entries of T2 are evicted.
+    assert_eq!(Some((0, 0)), cache.insert(6, 6).evicted);
+    assert_eq!((2, 1), cache.ghosts());
+    // A key that returns from B2 shrinks the target of T1.
+    assert_eq!(Some((5, 5)), cache.insert(0, 0).evicted);
+    assert_eq!(0, cache.target());
+    assert_eq!((1, 3), cache.split());
+    assert_eq!((3, 0), cache.ghosts());
+}
@@ -0,0 +1,286 @@
+//! The operations that are common to every cache, so that code can be
+//! written once for any replacement policy.
+
+use arc::ARCCache;
+use car::CARCache;
+use clock::ClockCache;
+use clockpro::ClockProCache;
+use core::hash::{BuildHasher, Hash};
+use fifo::FIFOCache;
+use gdsf::GDSFCache;
+use hyperbolic::HyperbolicCache;
+use lfu::LFUCache;
+use lirs::LIRSCache;
+use lru::{InsertResult, LRUCache, SampledLRUCache};
+use lruk::LRUKCache;
+use mq::MQCache;
+use mru::MRUCache;
+use random::RandomCache;
+use s3fifo::S3FIFOCache;
+use sieve::SieveCache;
+use slru::SLRUCache;
+use stats::Stats;
+use tinylfu::WTinyLFUCache;
+#[cfg(feature = "std")]
+use tlru::TLRUCache;
+use twoq::TwoQueueCache;
+
+// A cache with a replacement policy. The methods take the key type
+// itself rather than a borrowed form of it, so that the trait can be
+// used as a trait object. The inherent methods of the caches accept
+// borrowed forms of the keys. Each method behaves like the inherent
+// method of the same name: get() counts an access for the policy,
+// and peek() does not.
+pub trait Cache<K, V> {
+    fn get(&mut self, key: &K) -> Option<&V>;
+
+    fn peek(&self, key: &K) -> Option<&V>;
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.peek(key).is_some()
+    }
+
+    fn insert(&mut self, key: K, val: V) -> InsertResult<K, V>;
+
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    fn clear(&mut self);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The maximum number of entries, or the maximum total size for the
+    // caches that weigh their entries. None if the cache is unbounded.
+    fn capacity(&self) -> Option<usize>;
+
+    // True if the capacity is a total weight rather than a number of entries.
+    fn is_weighted(&self) -> bool {
+        false
+    }
+
+    fn stats(&self) -> &Stats;
+}
+
+macro_rules! impl_cache {
+    ($($(#[$attr:meta])* $cache:ident),*) => {
+        $(
+            $(#[$attr])*
+            impl<K, V, S> Cache<K, V> for $cache<K, V, S>
+                where K: Eq + Hash,
+                      S: BuildHasher
+            {
+                fn get(&mut self, key: &K) -> Option<&V> {
+                    $cache::get(self, key)
+                }
+
+                fn peek(&self, key: &K) -> Option<&V> {
+                    $cache::peek(self, key)
+                }
+
+                fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+                    $cache::insert(self, key, val)
+                }
+
+                fn remove(&mut self, key: &K) -> Option<V> {
+                    $cache::remove(self, key)
+                }
+
+                fn clear(&mut self) {
+                    $cache::clear(self)
+                }
+
+                fn len(&self) -> usize {
+                    $cache::len(self)
+                }
+
+                fn capacity(&self) -> Option<usize> {
+                    Some($cache::capacity(self))
+                }
+
+                fn stats(&self) -> &Stats {
+                    $cache::stats(self)
+                }
+            }
+        )*
+    }
+}
+
+impl_cache!(SampledLRUCache, ClockCache, LFUCache, FIFOCache, MRUCache, RandomCache, ARCCache,
+            TwoQueueCache, SLRUCache, LRUKCache, LIRSCache, CARCache, WTinyLFUCache, GDSFCache,
+            MQCache, #[cfg(feature = "std")] TLRUCache, S3FIFOCache, SieveCache, ClockProCache,
+            HyperbolicCache);
+
+// The capacity of an LRU cache is optional and counts the weights
+// of the entries if the cache has a weigher.
+impl<K, V, S> Cache<K, V> for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn get(&mut self, key: &K) -> Option<&V> {
+        LRUCache::get(self, key)
+    }
+
+    fn peek(&self, key: &K) -> Option<&V> {
+        LRUCache::peek(self, key)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        LRUCache::insert(self, key, val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        LRUCache::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        LRUCache::clear(self)
+    }
+
+    fn len(&self) -> usize {
+        LRUCache::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        LRUCache::capacity(self)
+    }
+
+    fn is_weighted(&self) -> bool {
+        LRUCache::is_weighted(self)
+    }
+
+    fn stats(&self) -> &Stats {
+        LRUCache::stats(self)
+    }
+}
+
+// One cache of each policy with the same capacity, in a fixed order.
+#[cfg(test)]
+fn all_caches(capacity: usize) -> Vec<Box<dyn Cache<u32, u32>>> {
+    use adaptive::AdaptiveCache;
+    use ghost::GhostCache;
+    use policy::{LRUPolicy, PolicyCache};
+
+    let all: Vec<Box<dyn Cache<u32, u32>>> =
+        vec![Box::new(LRUCache::new(capacity)),
+             Box::new(SampledLRUCache::new(capacity)),
+             Box::new(ClockCache::new(capacity)),
+             Box::new(LFUCache::new(capacity)),
+             Box::new(FIFOCache::new(capacity)),
+             Box::new(MRUCache::new(capacity)),
+             Box::new(RandomCache::new(capacity)),
+             Box::new(ARCCache::new(capacity)),
+             Box::new(TwoQueueCache::new(capacity)),
+             Box::new(SLRUCache::new(capacity)),
+             Box::new(LRUKCache::new(capacity)),
+             Box::new(LIRSCache::new(capacity)),
+             Box::new(CARCache::new(capacity)),
+             Box::new(WTinyLFUCache::new(capacity)),
+             Box::new(GDSFCache::new(capacity)),
+             Box::new(MQCache::new(capacity)),
+             Box::new(S3FIFOCache::new(capacity)),
+             Box::new(SieveCache::new(capacity)),
+             Box::new(ClockProCache::new(capacity)),
+             Box::new(HyperbolicCache::new(capacity)),
+             Box::new(PolicyCache::<u32, u32, LRUPolicy>::new(capacity)),
+             Box::new(AdaptiveCache::<u32, u32>::new(capacity)),
+             Box::new(GhostCache::<_, LRUPolicy>::new(LRUCache::new(capacity)))];
+    #[cfg(feature = "std")]
+    let all = {
+        let mut all = all;
+        all.push(Box::new(TLRUCache::new(capacity, std::time::Duration::from_secs(60))));
+        all
+    };
+    all
+}
+
+#[test]
+fn cache_conformance() {
+    // The operations that every policy provides. The behaviour
+    // that differs between the policies is tested by their modules.
+    for (i, cache) in all_caches(4).iter_mut().enumerate() {
+        assert!(cache.is_empty(), "cache {}", i);
+        assert_eq!(None, cache.insert(1, 2).replaced, "cache {}", i);
+        cache.insert(3, 4);
+        assert_eq!(Some(&2), cache.get(&1), "cache {}", i);
+        assert_eq!(None, cache.get(&5), "cache {}", i);
+        assert_eq!(Some(4), cache.insert(3, 5).replaced, "cache {}", i);
+        assert_eq!(Some(&5), cache.peek(&3), "cache {}", i);
+        assert!(cache.contains_key(&3), "cache {}", i);
+        assert_eq!(Some(5), cache.remove(&3), "cache {}", i);
+        assert_eq!(None, cache.remove(&3), "cache {}", i);
+        assert!(!cache.contains_key(&3), "cache {}", i);
+        assert_eq!((1, Some(4)), (cache.len(), cache.capacity()), "cache {}", i);
+        // A full cache makes room for a new key.
+        for key in 10..14 {
+            cache.insert(key, key);
+        }
+        assert_eq!(4, cache.len(), "cache {}", i);
+        let stats = cache.stats();
+        assert_eq!((1, 1, 1), (stats.hits(), stats.misses(), stats.replacements()), "cache {}", i);
+        assert_eq!((6, 1), (stats.insertions(), stats.evictions()), "cache {}", i);
+        cache.clear();
+        assert!(cache.is_empty(), "cache {}", i);
+        assert_eq!(None, cache.peek(&1), "cache {}", i);
+    }
+    assert_eq!(None, Cache::<i32, i32>::capacity(&LRUCache::unbounded()));
+}
+
+#[test]
+fn cache_zero_capacity() {
+    use adaptive::AdaptiveCache;
+    use policy::{FIFOPolicy, PolicyCache};
+
+    // No policy accepts a capacity of zero.
+    assert!(LRUCache::<i32, i32>::try_new(0).is_err());
+    assert!(SampledLRUCache::<i32, i32>::try_with_hasher(0, 5, Default::default()).is_err());
+    assert!(ClockCache::<i32, i32>::try_new(0).is_err());
+    assert!(LFUCache::<i32, i32>::try_new(0).is_err());
+    assert!(FIFOCache::<i32, i32>::try_new(0).is_err());
+    assert!(MRUCache::<i32, i32>::try_new(0).is_err());
+    assert!(RandomCache::<i32, i32>::try_new(0).is_err());
+    assert!(ARCCache::<i32, i32>::try_new(0).is_err());
+    assert!(TwoQueueCache::<i32, i32>::try_new(0).is_err());
+    assert!(SLRUCache::<i32, i32>::try_new(0).is_err());
+    assert!(LRUKCache::<i32, i32>::try_new(0).is_err());
+    assert!(LIRSCache::<i32, i32>::try_new(0).is_err());
+    assert!(CARCache::<i32, i32>::try_new(0).is_err());
+    assert!(WTinyLFUCache::<i32, i32>::try_new(0).is_err());
+    assert!(GDSFCache::<i32, i32>::try_new(0).is_err());
+    assert!(MQCache::<i32, i32>::try_new(0).is_err());
+    assert!(S3FIFOCache::<i32, i32>::try_new(0).is_err());
+    assert!(SieveCache::<i32, i32>::try_new(0).is_err());
+    assert!(ClockProCache::<i32, i32>::try_new(0).is_err());
+    assert!(HyperbolicCache::<i32, i32>::try_new(0).is_err());
+    assert!(PolicyCache::<i32, i32, FIFOPolicy>::try_new(0).is_err());
+    assert!(AdaptiveCache::<i32, i32>::try_new(0).is_err());
+    #[cfg(feature = "std")]
+    assert!(TLRUCache::<i32, i32>::try_new(0, std::time::Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn cache_generic() {
+    // A loop over more keys than the capacity, written once for every policy.
+    fn replay(cache: &mut dyn Cache<u32, u32>) -> Stats {
+        for _ in 0..4 {
+            for key in 0..12 {
+                if cache.get(&key).is_none() {
+                    cache.insert(key, key);
+                }
+                assert!(cache.len() <= 8);
+            }
+        }
+        cache.stats().clone()
+    }
+
+    let all: Vec<Stats> = all_caches(8).iter_mut().map(|cache| replay(&mut **cache)).collect();
+    for stats in &all {
+        assert_eq!(48, stats.hits() + stats.misses());
+        assert_eq!(stats.misses(), stats.insertions());
+    }
+    // A loop that is larger than the cache defeats LRU and favours MRU.
+    assert_eq!(0, all[0].hits());
+    assert!(all[5].hits() > 0);
+}
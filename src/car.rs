@@ -0,0 +1,406 @@
+//! Clock with Adaptive Replacement, as described by Bansal and Modha in
+//! "CAR: Clock with Adaptive Replacement" (FAST 2004).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// The pair of lists of a key: T1 and B1, or T2 and B2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    // keys that were not referenced again since they entered the cache
+    Recent,
+    // keys that were referenced at least twice
+    Frequent,
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // whether the entry was referenced since the hand last passed it
+    referenced: bool,
+}
+
+// The adaptation of ARCCache on top of two clocks. T1 and T2 are the
+// clocks of the entries that were referenced once and more than once,
+// and B1 and B2 remember the keys that were evicted from them. A hit
+// only sets the reference bit of its entry, so lookups do not reorder
+// the cache. When an entry must be evicted, the hand of T1 sweeps while
+// T1 is at least its target size p, and the hand of T2 otherwise. An
+// unreferenced entry under a hand is evicted into the matching ghost
+// list. A referenced entry of T1 moves to T2 and a referenced entry of
+// T2 stays in T2, with their bits cleared. An insertion of a key that
+// is in B1 grows the target of T1, and one in B2 shrinks it.
+//
+// As in ARCCache, the ghost lists record only the hashes of the keys.
+pub struct CARCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // target size of T1, the adaptation parameter p
+    target: usize,
+    hash_builder: S,
+    // locations of the cached entries in T1 and T2, hashed by key
+    table: HashTable<(Side, usize)>,
+    // T1 and T2, each from the entry just behind the hand at the front
+    // to the entry under the hand at the back
+    recent: List<Entry<K, V>>,
+    frequent: List<Entry<K, V>>,
+    // locations of the hashes in B1 and B2
+    ghosts: HashTable<(Side, usize)>,
+    // B1 and B2, each from the most recently evicted hash at the front
+    recent_ghosts: List<u64>,
+    frequent_ghosts: List<u64>,
+    stats: Stats,
+}
+
+impl<K, V> CARCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> CARCache<K, V> {
+        CARCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<CARCache<K, V>, CapacityError> {
+        CARCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> CARCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> CARCache<K, V, S> {
+        match CARCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<CARCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(CARCache {
+            capacity,
+            target: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            recent: List::new(),
+            frequent: List::new(),
+            ghosts: HashTable::with_capacity(capacity),
+            recent_ghosts: List::new(),
+            frequent_ghosts: List::new(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without setting the reference bit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(side, index)| &self.list(side).get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Replacing the value of a key counts as a reference.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some((side, index)) = self.locate(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.list_mut(side).get_mut(index);
+            e.referenced = true;
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        // The sizes of the ghost lists exclude the ghost of the key,
+        // which is removed before the target is adapted.
+        let ghost = self.take_ghost(hash);
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.replace()
+        } else {
+            None
+        };
+        let side = match ghost {
+            Some(Side::Recent) => {
+                // T1 was too small to keep the key until its second reference.
+                let delta = cmp::max(self.frequent_ghosts.len() / (self.recent_ghosts.len() + 1), 1);
+                self.target = cmp::min(self.target + delta, self.capacity);
+                Side::Frequent
+            }
+            Some(Side::Frequent) => {
+                // T2 was too small to keep the key.
+                let delta = cmp::max(self.recent_ghosts.len() / (self.frequent_ghosts.len() + 1), 1);
+                self.target = self.target.saturating_sub(delta);
+                Side::Frequent
+            }
+            None => {
+                // B1 and T1 together hold at most capacity keys, and the
+                // four lists together hold at most twice the capacity.
+                let recent = self.recent.len() + self.recent_ghosts.len();
+                let total = recent + self.frequent.len() + self.frequent_ghosts.len();
+                if recent >= self.capacity {
+                    self.drop_ghost(Side::Recent);
+                } else if total >= 2 * self.capacity {
+                    self.drop_ghost(Side::Frequent);
+                }
+                Side::Recent
+            }
+        };
+        let entry = Entry {
+            key,
+            val,
+            hash,
+            referenced: false,
+        };
+        let index = self.list_mut(side).push_front(entry);
+        let (recent, frequent) = (&self.recent, &self.frequent);
+        self.table.insert_unique(hash, (side, index), |&loc| entry_hash(recent, frequent, loc));
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // The ghost lists are cleared and the adaptation starts over.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.recent.clear();
+        self.frequent.clear();
+        self.ghosts.clear();
+        self.recent_ghosts.clear();
+        self.frequent_ghosts.clear();
+        self.target = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.frequent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The adaptation parameter p, the number of cached entries
+    // that the cache aims to keep in T1.
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    // The number of cached entries in T1 and in T2.
+    pub fn split(&self) -> (usize, usize) {
+        (self.recent.len(), self.frequent.len())
+    }
+
+    // The number of evicted keys that are remembered in B1 and in B2.
+    pub fn ghosts(&self) -> (usize, usize) {
+        (self.recent_ghosts.len(), self.frequent_ghosts.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        let (side, index) = loc?;
+        let e = self.list_mut(side).get_mut(index);
+        e.referenced = true;
+        Some(e)
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(Side, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(side, i)| self.list(side).get(i).key.borrow() == key)
+            .copied()
+    }
+
+    fn list(&self, side: Side) -> &List<Entry<K, V>> {
+        match side {
+            Side::Recent => &self.recent,
+            Side::Frequent => &self.frequent,
+        }
+    }
+
+    fn list_mut(&mut self, side: Side) -> &mut List<Entry<K, V>> {
+        match side {
+            Side::Recent => &mut self.recent,
+            Side::Frequent => &mut self.frequent,
+        }
+    }
+
+    // Sweep the hands until an unreferenced entry is found, evict it and
+    // remember its key in the matching ghost list. Every entry is passed at
+    // most once before its bit is cleared, so the sweep ends within two
+    // turns of the clocks.
+    fn replace(&mut self) -> Option<(K, V)> {
+        loop {
+            let side = if self.recent.len() >= cmp::max(1, self.target) || self.frequent.len() == 0 {
+                Side::Recent
+            } else {
+                Side::Frequent
+            };
+            let index = self.list(side).back()?;
+            if !self.list(side).get(index).referenced {
+                let e = self.take_entry((side, index));
+                let ghosts = match side {
+                    Side::Recent => &mut self.recent_ghosts,
+                    Side::Frequent => &mut self.frequent_ghosts,
+                };
+                let ghost = ghosts.push_front(e.hash);
+                let (b1, b2) = (&self.recent_ghosts, &self.frequent_ghosts);
+                self.ghosts.insert_unique(e.hash, (side, ghost), |&loc| ghost_hash(b1, b2, loc));
+                return Some((e.key, e.val));
+            }
+            self.list_mut(side).get_mut(index).referenced = false;
+            match side {
+                Side::Recent => {
+                    let entry = self.recent.remove(index);
+                    let hash = entry.hash;
+                    let moved = self.frequent.push_front(entry);
+                    *self.table
+                        .find_mut(hash, |&l| l == (Side::Recent, index))
+                        .expect("table and clocks are out of sync") = (Side::Frequent, moved);
+                }
+                Side::Frequent => self.frequent.move_to_front(index),
+            }
+        }
+    }
+
+    // Forget the oldest key of a ghost list.
+    fn drop_ghost(&mut self, side: Side) {
+        let ghosts = match side {
+            Side::Recent => &mut self.recent_ghosts,
+            Side::Frequent => &mut self.frequent_ghosts,
+        };
+        if let Some(index) = ghosts.back() {
+            let hash = ghosts.remove(index);
+            self.ghosts
+                .find_entry(hash, |&loc| loc == (side, index))
+                .expect("ghost table and lists are out of sync")
+                .remove();
+        }
+    }
+
+    // Remove the ghost of a key and return the list that held it.
+    fn take_ghost(&mut self, hash: u64) -> Option<Side> {
+        let (b1, b2) = (&self.recent_ghosts, &self.frequent_ghosts);
+        let ((side, index), _) = self.ghosts
+            .find_entry(hash, |&loc| ghost_hash(b1, b2, loc) == hash)
+            .ok()?
+            .remove();
+        match side {
+            Side::Recent => self.recent_ghosts.remove(index),
+            Side::Frequent => self.frequent_ghosts.remove(index),
+        };
+        Some(side)
+    }
+
+    // Remove a cached entry from the table and its clock.
+    fn take_entry(&mut self, loc: (Side, usize)) -> Entry<K, V> {
+        let hash = self.list(loc.0).get(loc.1).hash;
+        self.table
+            .find_entry(hash, |&l| l == loc)
+            .expect("table and clocks are out of sync")
+            .remove();
+        self.list_mut(loc.0).remove(loc.1)
+    }
+}
+
+fn entry_hash<K, V>(recent: &List<Entry<K, V>>,
+                    frequent: &List<Entry<K, V>>,
+                    (side, index): (Side, usize))
+                    -> u64 {
+    match side {
+        Side::Recent => recent.get(index).hash,
+        Side::Frequent => frequent.get(index).hash,
+    }
+}
+
+fn ghost_hash(recent: &List<u64>, frequent: &List<u64>, (side, index): (Side, usize)) -> u64 {
+    match side {
+        Side::Recent => *recent.get(index),
+        Side::Frequent => *frequent.get(index),
+    }
+}
+
+#[test]
+fn car_adaptation() {
+    let mut cache = CARCache::new(4);
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&0);
+    cache.get(&1);
+    // The hand of T1 moves the referenced entries to T2.
+    assert_eq!(Some((2, 2)), cache.insert(4, 4).evicted);
+    assert_eq!(((2, 2), (1, 0)), (cache.split(), cache.ghosts()));
+    // A key that returns from B1 grows the target of T1.
+    assert_eq!(Some((3, 3)), cache.insert(2, 2).evicted);
+    assert_eq!(Some((4, 4)), cache.insert(3, 3).evicted);
+    assert_eq!((2, (0, 4)), (cache.target(), cache.split()));
+    // Once T1 is below its target, the hand of T2 sweeps
+    // and skips over the referenced entries.
+    cache.get(&0);
+    assert_eq!(Some((1, 1)), cache.insert(5, 5).evicted);
+    assert_eq!(((1, 3), (1, 1)), (cache.split(), cache.ghosts()));
+    // A key that returns from B2 shrinks the target of T1.
+    assert_eq!(Some((2, 2)), cache.insert(1, 1).evicted);
+    assert_eq!((1, (1, 3)), (cache.target(), cache.split()));
+}
@@ -0,0 +1,255 @@
+//! A CLOCK cache, also known as second chance, that approximates LRU
+//! with a reference bit for each entry instead of a recency order.
+
+use DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use lru::InsertResult;
+use stats::Stats;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+    // set by each access and cleared when the hand passes the slot
+    referenced: bool,
+}
+
+// The slots form a circular buffer that is swept by a hand. A lookup
+// only sets the reference bit of its entry, so it costs no more than
+// a probe of the table. On eviction the hand clears the reference bits
+// that are set until it finds an entry that was not accessed since the
+// last sweep, and evicts that entry.
+pub struct ClockCache<K, V, S = DefaultHashBuilder> {
+    hash_builder: S,
+    // indices of the slots, hashed by key
+    table: HashTable<usize>,
+    // circular buffer of at most capacity slots
+    slots: Vec<Option<Slot<K, V>>>,
+    // indices of the slots that were emptied by removals
+    free: Vec<usize>,
+    // index of the next slot that is examined on eviction
+    hand: usize,
+    capacity: usize,
+    stats: Stats,
+}
+
+impl<K, V> ClockCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> ClockCache<K, V> {
+        ClockCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<ClockCache<K, V>, CapacityError> {
+        ClockCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ClockCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> ClockCache<K, V, S> {
+        match ClockCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<ClockCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(ClockCache {
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            hand: 0,
+            capacity,
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &slot.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &mut slot.val)
+    }
+
+    // Read the value without setting the reference bit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.slot(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // A new entry starts with its reference bit cleared,
+    // so an entry that is never read is evicted on the first sweep.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            let slot = self.slot_mut(index);
+            slot.referenced = true;
+            let prev = mem::replace(&mut slot.val, val);
+            self.stats.replacements += 1;
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        let mut evicted = None;
+        let index = if let Some(index) = self.free.pop() {
+            index
+        } else if self.slots.len() < self.capacity {
+            self.slots.push(None);
+            self.slots.len() - 1
+        } else {
+            let index = self.sweep();
+            self.stats.evictions += 1;
+            evicted = Some(self.take_slot(index));
+            index
+        };
+        self.stats.insertions += 1;
+        self.slots[index] = Some(Slot {
+            key,
+            val,
+            hash,
+            referenced: false,
+        });
+        let slots = &self.slots;
+        self.table.insert_unique(hash, index, |&i| {
+            slots[i].as_ref().expect("table and slots are out of sync").hash
+        });
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        self.free.push(index);
+        Some(self.take_slot(index).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.slots.clear();
+        self.free.clear();
+        self.hand = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Slot<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let slot = self.slot_mut(index?);
+        slot.referenced = true;
+        Some(slot)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.slot(i).key.borrow() == key).copied()
+    }
+
+    fn slot(&self, index: usize) -> &Slot<K, V> {
+        self.slots[index].as_ref().expect("table and slots are out of sync")
+    }
+
+    fn slot_mut(&mut self, index: usize) -> &mut Slot<K, V> {
+        self.slots[index].as_mut().expect("table and slots are out of sync")
+    }
+
+    // Advance the hand to the first slot whose reference bit is cleared,
+    // clearing the bits along the way. Only called when every slot is
+    // occupied, so the hand stops within one revolution.
+    fn sweep(&mut self) -> usize {
+        loop {
+            let index = self.hand;
+            self.hand = (index + 1) % self.slots.len();
+            let slot = self.slot_mut(index);
+            if !slot.referenced {
+                return index;
+            }
+            slot.referenced = false;
+        }
+    }
+
+    // Empty a slot and remove its index from the table.
+    fn take_slot(&mut self, index: usize) -> (K, V) {
+        let slot = self.slots[index].take().expect("table and slots are out of sync");
+        self.table
+            .find_entry(slot.hash, |&i| i == index)
+            .expect("table and slots are out of sync")
+            .remove();
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn clock_second_chance() {
+    let mut cache = ClockCache::new(3);
+    cache.insert(1, 1);
+    cache.insert(2, 2);
+    cache.insert(3, 3);
+    cache.get(&1);
+    cache.get(&3);
+    // 1 is given a second chance and 2 was never read.
+    assert_eq!(Some((2, 2)), cache.insert(4, 4).evicted);
+    // 3 is given a second chance, and the bit of 1 was cleared by the last sweep.
+    assert_eq!(Some((1, 1)), cache.insert(5, 5).evicted);
+    // The new entry 4 follows the hand and was never read.
+    assert_eq!(Some((4, 4)), cache.insert(6, 6).evicted);
+    assert_eq!(3, cache.stats().evictions());
+}
@@ -0,0 +1,511 @@
+//! CLOCK-Pro cache, as described by Jiang, Chen and Zhang in "CLOCK-Pro:
+//! An Effective Improvement of the CLOCK Replacement" (USENIX ATC 2005).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// The status of a page in the ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    // a resident page with a short reuse distance
+    Hot,
+    // a resident page with a long reuse distance, or a new page
+    Cold,
+    // a cold page that was evicted during its test period
+    Test,
+}
+
+struct Page<K, V> {
+    // hash of the key, computed once when the page was inserted
+    hash: u64,
+    status: Status,
+    // set while a cold page is in its test period, and always set
+    // for a non-resident test page
+    test: bool,
+    // set by each access and cleared when a hand passes the page
+    referenced: bool,
+    // the key and the value of a resident page
+    entry: Option<(K, V)>,
+}
+
+// The hands of the ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hand {
+    Hot,
+    Cold,
+    Test,
+}
+
+// An approximation of LIRS with the constant-time accesses of CLOCK.
+// Every page is in a single ring: the resident hot pages, the resident
+// cold pages, and the test pages, which only remember the hashes of
+// cold pages that were evicted recently. A new page is cold and starts
+// a test period. A cold page that is reused during its test period has
+// a reuse distance shorter than the hot pages, and becomes hot. An
+// access only sets the reference bit of its page.
+//
+// Three hands sweep the ring. The cold hand evicts the first cold page
+// that was not referenced, and keeps it as a test page if it is still
+// in its test period. The hot hand demotes the first hot page that was
+// not referenced when there are too many hot pages, and ends the test
+// periods that it passes. The test hand forgets the oldest test pages
+// when there are more than the capacity. The target number of cold
+// pages adapts: a reuse during a test period raises it, and a test
+// period that ends without a reuse lowers it.
+pub struct ClockProCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of resident pages
+    capacity: usize,
+    // target number of resident cold pages, between one and the capacity
+    cold_target: usize,
+    // number of resident hot pages
+    hot: usize,
+    // number of resident cold pages
+    cold: usize,
+    hash_builder: S,
+    // indices of the pages in the ring, hashed by key
+    table: HashTable<usize>,
+    // The ring is swept from the front towards the back and wraps to
+    // the front. Pages are inserted and moved just behind the hot hand.
+    ring: List<Page<K, V>>,
+    // the pages under the hands, or none to start from the front
+    hot_hand: Option<usize>,
+    cold_hand: Option<usize>,
+    test_hand: Option<usize>,
+    stats: Stats,
+}
+
+impl<K, V> ClockProCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> ClockProCache<K, V> {
+        ClockProCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<ClockProCache<K, V>, CapacityError> {
+        ClockProCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ClockProCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> ClockProCache<K, V, S> {
+        match ClockProCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<ClockProCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(ClockProCache {
+            capacity,
+            cold_target: 1,
+            hot: 0,
+            cold: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            ring: List::with_capacity(capacity),
+            hot_hand: None,
+            cold_hand: None,
+            test_hand: None,
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|(_, val)| &*val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|(_, val)| val)
+    }
+
+    // Read the value without setting the reference bit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.resident(index).1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // True if the key is cached in a hot page.
+    pub fn is_hot<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).is_some_and(|index| self.ring.get(index).status == Status::Hot)
+    }
+
+    // Replacing the value of a key counts as an access.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.stats.replacements += 1;
+            let page = self.ring.get_mut(index);
+            page.referenced = true;
+            let (_, prev) = page.entry.as_mut().expect("resident page without an entry");
+            return InsertResult {
+                replaced: Some(mem::replace(prev, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        // A reuse during the test period is known before the
+        // eviction, which may forget the test page.
+        let reused = self.take_test(hash);
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.run_cold()
+        } else {
+            None
+        };
+        let status = if reused {
+            self.cold_target = cmp::min(self.cold_target + 1, self.capacity);
+            self.hot += 1;
+            Status::Hot
+        } else {
+            self.cold += 1;
+            Status::Cold
+        };
+        let page = Page {
+            hash,
+            status,
+            test: !reused,
+            referenced: false,
+            entry: Some((key, val)),
+        };
+        let index = match self.hot_hand {
+            Some(hand) => self.ring.insert_before(hand, page),
+            None => self.ring.push_back(page),
+        };
+        let ring = &self.ring;
+        self.table.insert_unique(hash, index, |&i| ring.get(i).hash);
+        if self.hot > self.hot_target() {
+            self.run_hot();
+        }
+        while self.tests() > self.capacity {
+            self.run_test();
+        }
+        InsertResult { replaced: None, evicted }
+    }
+
+    // The test page of the key is forgotten as well.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        let page = self.take_page(index);
+        page.entry.map(|(_, val)| val)
+    }
+
+    // The test pages are cleared and the cold target is reset as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.ring.clear();
+        self.hot_hand = None;
+        self.cold_hand = None;
+        self.test_hand = None;
+        self.cold_target = 1;
+        self.hot = 0;
+        self.cold = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.hot + self.cold
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The target number of resident cold pages.
+    pub fn cold_target(&self) -> usize {
+        self.cold_target
+    }
+
+    // The number of resident hot pages, of resident cold pages,
+    // and of test pages that are not resident.
+    pub fn pages(&self) -> (usize, usize, usize) {
+        (self.hot, self.cold, self.tests())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut (K, V)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let page = self.ring.get_mut(index?);
+        page.referenced = true;
+        page.entry.as_mut()
+    }
+
+    // The index of the resident page of a key.
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&i| self.ring.get(i).entry.as_ref().is_some_and(|(k, _)| k.borrow() == key))
+            .copied()
+    }
+
+    fn resident(&self, index: usize) -> &(K, V) {
+        self.ring.get(index).entry.as_ref().expect("resident page without an entry")
+    }
+
+    fn hot_target(&self) -> usize {
+        self.capacity - self.cold_target
+    }
+
+    fn tests(&self) -> usize {
+        self.ring.len() - self.len()
+    }
+
+    // Forget the test page of a hash. Returns true if it was remembered.
+    fn take_test(&mut self, hash: u64) -> bool {
+        let ring = &self.ring;
+        let index = self.table.find(hash, |&i| {
+            let page = ring.get(i);
+            page.status == Status::Test && page.hash == hash
+        });
+        match index {
+            Some(&index) => {
+                self.take_page(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // A test period that ends without a reuse lowers the cold target.
+    fn end_test(&mut self, index: usize) {
+        self.ring.get_mut(index).test = false;
+        self.cold_target = cmp::max(self.cold_target - 1, 1);
+    }
+
+    // Move the cold hand to the first cold page that was not referenced
+    // and evict it. A referenced cold page becomes hot if it is in its
+    // test period, and starts a new test period otherwise.
+    fn run_cold(&mut self) -> Option<(K, V)> {
+        if self.cold == 0 {
+            self.run_hot();
+        }
+        loop {
+            let index = self.hand(Hand::Cold)?;
+            self.advance(Hand::Cold, index);
+            let page = self.ring.get_mut(index);
+            if page.status != Status::Cold {
+                continue;
+            }
+            if !page.referenced {
+                if !page.test {
+                    return self.take_page(index).entry;
+                }
+                page.status = Status::Test;
+                self.cold -= 1;
+                return page.entry.take();
+            }
+            page.referenced = false;
+            if page.test {
+                page.status = Status::Hot;
+                page.test = false;
+                self.cold -= 1;
+                self.hot += 1;
+                self.cold_target = cmp::min(self.cold_target + 1, self.capacity);
+            } else {
+                page.test = true;
+            }
+            self.move_behind_hot_hand(index);
+            if self.hot > self.hot_target() {
+                self.run_hot();
+            }
+        }
+    }
+
+    // Move the hot hand to the first hot page that was not referenced and
+    // demote it. The hand ends the test periods of the pages it passes.
+    fn run_hot(&mut self) {
+        while self.hot > 0 {
+            let index = match self.hand(Hand::Hot) {
+                Some(index) => index,
+                None => return,
+            };
+            self.advance(Hand::Hot, index);
+            let page = self.ring.get_mut(index);
+            match page.status {
+                Status::Hot if page.referenced => page.referenced = false,
+                Status::Hot => {
+                    page.status = Status::Cold;
+                    self.hot -= 1;
+                    self.cold += 1;
+                    return;
+                }
+                Status::Cold if page.test => self.end_test(index),
+                Status::Cold => {}
+                Status::Test => {
+                    self.take_page(index);
+                    self.cold_target = cmp::max(self.cold_target - 1, 1);
+                }
+            }
+        }
+    }
+
+    // Move the test hand to the first test page and forget it. The hand
+    // ends the test periods of the resident cold pages it passes.
+    fn run_test(&mut self) {
+        loop {
+            let index = match self.hand(Hand::Test) {
+                Some(index) => index,
+                None => return,
+            };
+            self.advance(Hand::Test, index);
+            let page = self.ring.get(index);
+            match page.status {
+                Status::Test => {
+                    self.take_page(index);
+                    self.cold_target = cmp::max(self.cold_target - 1, 1);
+                    return;
+                }
+                Status::Cold if page.test => self.end_test(index),
+                _ => {}
+            }
+        }
+    }
+
+    fn hand_mut(&mut self, hand: Hand) -> &mut Option<usize> {
+        match hand {
+            Hand::Hot => &mut self.hot_hand,
+            Hand::Cold => &mut self.cold_hand,
+            Hand::Test => &mut self.test_hand,
+        }
+    }
+
+    // The page under a hand. A hand that was reset starts from the front.
+    fn hand(&mut self, hand: Hand) -> Option<usize> {
+        let front = self.ring.front();
+        let hand = self.hand_mut(hand);
+        if hand.is_none() {
+            *hand = front;
+        }
+        *hand
+    }
+
+    // Move a hand past a page, wrapping from the back to the front.
+    fn advance(&mut self, hand: Hand, index: usize) {
+        let next = self.ring.next(index).or_else(|| self.ring.front());
+        *self.hand_mut(hand) = next;
+    }
+
+    // Move a page to the position of the newest pages, just behind
+    // the hot hand. A hand that is on the page moves past it first.
+    fn move_behind_hot_hand(&mut self, index: usize) {
+        for hand in [Hand::Hot, Hand::Cold, Hand::Test] {
+            if *self.hand_mut(hand) == Some(index) {
+                self.advance(hand, index);
+            }
+        }
+        match self.hot_hand {
+            Some(hand) if hand != index => self.ring.move_before(index, hand),
+            Some(_) => {}
+            None => self.ring.move_to_back(index),
+        }
+    }
+
+    // Remove a page from the table and the ring. The hands that
+    // are on the page move past it, and reset on an empty ring.
+    fn take_page(&mut self, index: usize) -> Page<K, V> {
+        for hand in [Hand::Hot, Hand::Cold, Hand::Test] {
+            if *self.hand_mut(hand) == Some(index) {
+                self.advance(hand, index);
+                if *self.hand_mut(hand) == Some(index) {
+                    *self.hand_mut(hand) = None;
+                }
+            }
+        }
+        let hash = self.ring.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and ring are out of sync")
+            .remove();
+        let page = self.ring.remove(index);
+        match (page.status, page.entry.is_some()) {
+            (Status::Hot, true) => self.hot -= 1,
+            (Status::Cold, true) => self.cold -= 1,
+            _ => {}
+        }
+        page
+    }
+}
+
+#[test]
+fn clockpro_pages() {
+    let mut cache = ClockProCache::new(3);
+    for i in 1..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&1);
+    // A cold page that is reused during its test period becomes hot,
+    // and the evicted cold page is kept as a test page.
+    assert_eq!(Some((2, 2)), cache.insert(4, 4).evicted);
+    assert!(cache.is_hot(&1));
+    assert_eq!(((1, 2, 1), 2), (cache.pages(), cache.cold_target()));
+    // A key that returns during its test period is inserted hot, and the
+    // hot hand demotes the page that was not referenced since.
+    assert_eq!(Some((3, 3)), cache.insert(2, 2).evicted);
+    assert!(cache.is_hot(&2) && !cache.is_hot(&1));
+    assert_eq!(((1, 2, 0), 2), (cache.pages(), cache.cold_target()));
+    // A loop that is larger than the cache still hits.
+    let mut cache = ClockProCache::new(4);
+    for _ in 0..10 {
+        for i in 0..5 {
+            if cache.get(&i).is_none() {
+                cache.insert(i, i);
+            }
+        }
+    }
+    assert!(cache.stats().hits() > 0);
+    assert!(cache.len() <= 4 && cache.pages().2 <= 4);
+}
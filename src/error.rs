@@ -0,0 +1,16 @@
+//! Errors reported by the cache constructors.
+
+use core::error::Error;
+use core::fmt;
+
+// A cache was configured with a capacity of zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError;
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("cache capacity must be greater than zero")
+    }
+}
+
+impl Error for CapacityError {}
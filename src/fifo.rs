@@ -0,0 +1,215 @@
+//! First-in, first-out cache that evicts entries in insertion order.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+}
+
+// Lookups do not reorder the entries, so the cache is read without
+// writing to the slab. Replacing the value of a key keeps the position
+// of the key in the insertion order.
+pub struct FIFOCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries, linked from the newest
+    // entry at the front to the oldest entry at the back
+    entries: List<Slot<K, V>>,
+    stats: Stats,
+}
+
+impl<K, V> FIFOCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> FIFOCache<K, V> {
+        FIFOCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<FIFOCache<K, V>, CapacityError> {
+        FIFOCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> FIFOCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> FIFOCache<K, V, S> {
+        match FIFOCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<FIFOCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(FIFOCache {
+            capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without counting the lookup.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.stats.replacements += 1;
+            let prev = mem::replace(&mut self.entries.get_mut(index).val, val);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.pop_oldest()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.entries.push_front(Slot { key, val, hash });
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_entry(index).1)
+    }
+
+    // The entry that was inserted first.
+    pub fn peek_oldest(&self) -> Option<(&K, &V)> {
+        self.entries.back().map(|index| {
+            let slot = self.entries.get(index);
+            (&slot.key, &slot.val)
+        })
+    }
+
+    pub fn pop_oldest(&mut self) -> Option<(K, V)> {
+        let index = self.entries.back()?;
+        Some(self.take_entry(index))
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        index
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Remove the entry with the given index from the table and the slab.
+    fn take_entry(&mut self, index: usize) -> (K, V) {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let slot = self.entries.remove(index);
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn fifo_eviction() {
+    let mut cache = FIFOCache::new(2);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    // Neither lookups nor replacements change the insertion order.
+    cache.get("a");
+    cache.insert("a", 3);
+    assert_eq!(Some((&"a", &3)), cache.peek_oldest());
+    assert_eq!(Some(("a", 3)), cache.insert("c", 4).evicted);
+    assert_eq!(Some(("b", 2)), cache.insert("d", 5).evicted);
+    assert_eq!(Some(("c", 4)), cache.pop_oldest());
+    assert_eq!(1, cache.len());
+}
@@ -0,0 +1,333 @@
+//! GreedyDual-Size-Frequency cache, as described by Cherkasova in
+//! "Improving WWW Proxies Performance with Greedy-Dual-Size-Frequency
+//! Caching Policy" (HP Labs 1998).
+
+use DefaultHashBuilder;
+use alloc::collections::BTreeMap;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    size: usize,
+    // cost of retrieving the value again after its eviction
+    cost: f64,
+    // number of accesses, counting the insertion
+    freq: u64,
+    // priority key of the entry in the order
+    rank: (u64, u64),
+}
+
+// Every entry has a priority of L + frequency * cost / size, where L is
+// the inflation, the priority of the last evicted entry. Eviction takes
+// the entry with the lowest priority, so small entries that are used
+// often and costly to retrieve are kept over large entries that are
+// cheap to retrieve. An access recomputes the priority of its entry
+// with the current inflation, so the entries that are not accessed age
+// relative to the others as the inflation grows.
+//
+// The capacity is the maximum total size of the entries. An entry that
+// is larger than the capacity is cached alone.
+pub struct GDSFCache<K, V, S = DefaultHashBuilder> {
+    // maximum total size of the entries
+    capacity: usize,
+    // total size of the entries
+    size: usize,
+    // the inflation L
+    inflation: f64,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries, in arbitrary order
+    entries: List<Entry<K, V>>,
+    // indices of the entries ordered by priority, then by the time of
+    // their last access
+    order: BTreeMap<(u64, u64), usize>,
+    // count of the accesses, which orders the entries with equal priorities
+    clock: u64,
+    stats: Stats,
+}
+
+impl<K, V> GDSFCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> GDSFCache<K, V> {
+        GDSFCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<GDSFCache<K, V>, CapacityError> {
+        GDSFCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> GDSFCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> GDSFCache<K, V, S> {
+        match GDSFCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize, hash_builder: S) -> Result<GDSFCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(GDSFCache {
+            capacity,
+            size: 0,
+            inflation: 0.0,
+            hash_builder,
+            table: HashTable::new(),
+            entries: List::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // The current priority of the entry of a key.
+    pub fn priority<Q>(&self, key: &Q) -> Option<f64>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| f64::from_bits(self.entries.get(index).rank.0))
+    }
+
+    // Insert an entry with a size of one and a cost of one.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        self.insert_with_cost(key, val, 1, 1.0)
+    }
+
+    // Replacing the value of a key counts as an access and replaces the
+    // size and the cost of the entry. When several entries must be
+    // evicted to make room, the pairs after the first are dropped.
+    // Panics if the cost is negative or not finite.
+    pub fn insert_with_cost(&mut self, key: K, val: V, size: usize, cost: f64) -> InsertResult<K, V> {
+        assert!(cost.is_finite() && cost >= 0.0, "cost must be finite and not negative");
+        let hash = self.hash_builder.hash_one(&key);
+        let (replaced, freq) = match self.index_of(hash, &key) {
+            Some(index) => {
+                self.stats.replacements += 1;
+                let e = self.take_entry(index);
+                (Some(e.val), e.freq + 1)
+            }
+            None => {
+                self.stats.insertions += 1;
+                (None, 1)
+            }
+        };
+        let evicted = self.evict_to_fit(size);
+        self.clock += 1;
+        let rank = (self.priority_of(freq, cost, size).to_bits(), self.clock);
+        let index = self.entries.push_front(Entry {
+            key,
+            val,
+            hash,
+            size,
+            cost,
+            freq,
+            rank,
+        });
+        self.order.insert(rank, index);
+        self.size += size;
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_entry(index).val)
+    }
+
+    // The entry that is evicted next.
+    pub fn peek_victim(&self) -> Option<(&K, &V)> {
+        self.order.values().next().map(|&index| {
+            let e = self.entries.get(index);
+            (&e.key, &e.val)
+        })
+    }
+
+    // The inflation is reset as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+        self.order.clear();
+        self.size = 0;
+        self.inflation = 0.0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The total size of the entries.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // The inflation L, the priority of the last evicted entry.
+    pub fn inflation(&self) -> f64 {
+        self.inflation
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.access(index);
+        Some(index)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Count an access and recompute the priority of the entry.
+    fn access(&mut self, index: usize) {
+        self.clock += 1;
+        let clock = self.clock;
+        let (freq, cost, size, rank) = {
+            let e = self.entries.get(index);
+            (e.freq + 1, e.cost, e.size, e.rank)
+        };
+        self.order.remove(&rank);
+        let rank = (self.priority_of(freq, cost, size).to_bits(), clock);
+        self.order.insert(rank, index);
+        let e = self.entries.get_mut(index);
+        e.freq = freq;
+        e.rank = rank;
+    }
+
+    // The priorities are finite and not negative,
+    // so their bits are ordered like the priorities.
+    fn priority_of(&self, freq: u64, cost: f64, size: usize) -> f64 {
+        self.inflation + freq as f64 * cost / cmp::max(size, 1) as f64
+    }
+
+    // Evict the entries with the lowest priorities until an entry
+    // of the given size fits, and raise the inflation to the priority
+    // of the last evicted entry. Returns the first evicted pair.
+    fn evict_to_fit(&mut self, size: usize) -> Option<(K, V)> {
+        let mut first = None;
+        while self.size + size > self.capacity {
+            let (&(priority, _), &index) = match self.order.iter().next() {
+                Some(victim) => victim,
+                None => break,
+            };
+            self.inflation = f64::from_bits(priority);
+            self.stats.evictions += 1;
+            let e = self.take_entry(index);
+            if first.is_none() {
+                first = Some((e.key, e.val));
+            }
+        }
+        first
+    }
+
+    // Remove the entry with the given index from the table, the order and the slab.
+    fn take_entry(&mut self, index: usize) -> Entry<K, V> {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let e = self.entries.remove(index);
+        self.order.remove(&e.rank);
+        self.size -= e.size;
+        e
+    }
+}
+
+#[test]
+fn gdsf_eviction() {
+    let mut cache = GDSFCache::new(10);
+    cache.insert_with_cost("small", 1, 1, 1.0);
+    cache.insert_with_cost("big", 2, 8, 1.0);
+    // The large entry has the lowest priority.
+    assert_eq!(Some(("big", 2)), cache.insert_with_cost("x", 3, 2, 1.0).evicted);
+    assert_eq!(0.125, cache.inflation());
+    assert_eq!(Some(0.625), cache.priority("x"));
+    // Accesses and costs raise the priorities.
+    cache.get("small");
+    cache.insert_with_cost("costly", 4, 2, 10.0);
+    assert_eq!(Some(("x", 3)), cache.insert_with_cost("filler", 5, 6, 1.0).evicted);
+    assert_eq!((0.625, 9), (cache.inflation(), cache.size()));
+    // Several entries are evicted to make room for a large one.
+    cache.insert_with_cost("large", 6, 9, 1.0);
+    assert_eq!((1, 9), (cache.len(), cache.size()));
+    // An entry larger than the capacity is cached alone.
+    cache.insert_with_cost("huge", 7, 20, 1.0);
+    assert_eq!((1, 20), (cache.len(), cache.size()));
+    assert_eq!(Some((&"huge", &7)), cache.peek_victim());
+}
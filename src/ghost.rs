@@ -0,0 +1,238 @@
+//! A wrapper that estimates the hit ratio of a cache at larger capacities,
+//! to tell from the live traffic whether a larger cache would pay off.
+
+use DefaultHashBuilder;
+use alloc::vec::Vec;
+use cache::Cache;
+use core::hash::{BuildHasher, Hash};
+use lru::InsertResult;
+use policy::{LRUPolicy, Policy, PolicyCache};
+use stats::{Stats, StatsSnapshot};
+
+// multiples of the capacity of the cache that are simulated by default
+const DEFAULT_MULTIPLES: [usize; 2] = [2, 4];
+
+// Besides the cache that it wraps, the wrapper keeps shadow caches of
+// ghost entries, one for each simulated capacity. A ghost entry stores
+// only the hash of a key, so a shadow that is several times larger than
+// the cache needs a fraction of its memory. The shadows follow the
+// lookups, insertions and removals of the cache, and count the hits that
+// a cache of their capacity would have had. Their policy is LRU unless
+// another policy is given, and should resemble the policy of the cache.
+pub struct GhostCache<C, P = LRUPolicy, S = DefaultHashBuilder> {
+    cache: C,
+    hash_builder: S,
+    // the simulated caches, from the smallest
+    shadows: Vec<PolicyCache<u64, (), P>>,
+}
+
+impl<C, P> GhostCache<C, P>
+    where P: Policy + Default
+{
+    // Simulate two and four times the capacity of the cache. The shadows
+    // count entries, so the capacities of a cache that weighs its entries
+    // must be given to with_capacities() instead.
+    // Panics if the cache is unbounded or weighs its entries.
+    pub fn new<K, V>(cache: C) -> GhostCache<C, P>
+        where C: Cache<K, V>
+    {
+        assert!(!cache.is_weighted(), "the capacity of a weighted cache is not a number of entries");
+        let capacity = cache.capacity().expect("an unbounded cache has no larger capacity");
+        let capacities: Vec<usize> = DEFAULT_MULTIPLES.iter().map(|m| m * capacity).collect();
+        GhostCache::with_capacities(cache, &capacities)
+    }
+
+    // Panics if a capacity is zero.
+    pub fn with_capacities(cache: C, capacities: &[usize]) -> GhostCache<C, P> {
+        GhostCache::with_hasher(cache, capacities, DefaultHashBuilder::default())
+    }
+}
+
+impl<C, P, S> GhostCache<C, P, S>
+    where P: Policy + Default,
+          S: BuildHasher
+{
+    // Panics if a capacity is zero.
+    pub fn with_hasher(cache: C, capacities: &[usize], hash_builder: S) -> GhostCache<C, P, S> {
+        let mut capacities = capacities.to_vec();
+        capacities.sort_unstable();
+        GhostCache {
+            cache,
+            hash_builder,
+            shadows: capacities.into_iter().map(PolicyCache::new).collect(),
+        }
+    }
+
+    pub fn get<K, V>(&mut self, key: &K) -> Option<&V>
+        where C: Cache<K, V>,
+              K: Hash
+    {
+        let hash = self.hash_builder.hash_one(key);
+        for shadow in &mut self.shadows {
+            shadow.get(&hash);
+        }
+        self.cache.get(key)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<K, V>(&self, key: &K) -> Option<&V>
+        where C: Cache<K, V>
+    {
+        self.cache.peek(key)
+    }
+
+    pub fn contains_key<K, V>(&self, key: &K) -> bool
+        where C: Cache<K, V>
+    {
+        self.cache.contains_key(key)
+    }
+
+    // A key that a shadow does not hold is added to it.
+    pub fn insert<K, V>(&mut self, key: K, val: V) -> InsertResult<K, V>
+        where C: Cache<K, V>,
+              K: Hash
+    {
+        let hash = self.hash_builder.hash_one(&key);
+        for shadow in &mut self.shadows {
+            if !shadow.contains_key(&hash) {
+                shadow.insert(hash, ());
+            }
+        }
+        self.cache.insert(key, val)
+    }
+
+    pub fn remove<K, V>(&mut self, key: &K) -> Option<V>
+        where C: Cache<K, V>,
+              K: Hash
+    {
+        let hash = self.hash_builder.hash_one(key);
+        for shadow in &mut self.shadows {
+            shadow.remove(&hash);
+        }
+        self.cache.remove(key)
+    }
+
+    // The shadows are cleared with the cache, and keep their counters.
+    pub fn clear<K, V>(&mut self)
+        where C: Cache<K, V>
+    {
+        for shadow in &mut self.shadows {
+            shadow.clear();
+        }
+        self.cache.clear();
+    }
+
+    pub fn len<K, V>(&self) -> usize
+        where C: Cache<K, V>
+    {
+        self.cache.len()
+    }
+
+    pub fn is_empty<K, V>(&self) -> bool
+        where C: Cache<K, V>
+    {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity<K, V>(&self) -> Option<usize>
+        where C: Cache<K, V>
+    {
+        self.cache.capacity()
+    }
+
+    pub fn stats<K, V>(&self) -> &Stats
+        where C: Cache<K, V>
+    {
+        self.cache.stats()
+    }
+
+    // The counters of each simulated capacity, from the smallest.
+    // The counters of the cache itself are in stats().
+    pub fn estimates(&self) -> Vec<(usize, StatsSnapshot)> {
+        self.shadows.iter().map(|shadow| (shadow.capacity(), shadow.stats().snapshot())).collect()
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.cache
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    pub fn into_inner(self) -> C {
+        self.cache
+    }
+}
+
+impl<K, V, C, P, S> Cache<K, V> for GhostCache<C, P, S>
+    where K: Hash,
+          C: Cache<K, V>,
+          P: Policy + Default,
+          S: BuildHasher
+{
+    fn get(&mut self, key: &K) -> Option<&V> {
+        GhostCache::get(self, key)
+    }
+
+    fn peek(&self, key: &K) -> Option<&V> {
+        GhostCache::peek(self, key)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        GhostCache::insert(self, key, val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        GhostCache::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        GhostCache::clear(self)
+    }
+
+    fn len(&self) -> usize {
+        GhostCache::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        GhostCache::capacity(self)
+    }
+
+    fn is_weighted(&self) -> bool {
+        self.cache.is_weighted()
+    }
+
+    fn stats(&self) -> &Stats {
+        GhostCache::stats(self)
+    }
+}
+
+#[test]
+fn ghost_estimates() {
+    use sim;
+    use lru::LRUCache;
+
+    // A loop that is larger than the cache would hit in a larger cache.
+    let mut cache: GhostCache<_> = GhostCache::with_capacities(LRUCache::new(4), &[16, 5, 8]);
+    let trace = (0..48).map(|i| i % 6);
+    let report = sim::replay::<_, (), _, _>(&mut cache, trace);
+    assert_eq!(0, report.total.hits);
+    let hits: Vec<(usize, u64)> = cache.estimates().iter().map(|&(capacity, s)| (capacity, s.hits)).collect();
+    assert_eq!(vec![(5, 0), (8, 42), (16, 42)], hits);
+    assert_eq!(48, cache.estimates()[0].1.lookups());
+    // The default capacities are multiples of the capacity of the cache.
+    let cache: GhostCache<_> = GhostCache::new(LRUCache::<u32, u32>::new(3));
+    let capacities: Vec<usize> = cache.estimates().iter().map(|&(capacity, _)| capacity).collect();
+    assert_eq!(vec![6, 12], capacities);
+}
+
+#[test]
+#[should_panic(expected = "weighted cache")]
+fn ghost_weighted() {
+    use lru::LRUCache;
+
+    let mut cache = LRUCache::new(100);
+    cache.set_weigher(|_: &u32, v: &u32| *v as usize);
+    let _: GhostCache<_> = GhostCache::new(cache);
+}
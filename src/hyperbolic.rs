@@ -0,0 +1,311 @@
+//! Hyperbolic cache, as described by Blankstein, Sen and Freedman in
+//! "Hyperbolic Caching: Flexible Caching for Web Applications" (USENIX
+//! ATC 2017).
+
+use DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use lru::InsertResult;
+use rng::Rng;
+use stats::Stats;
+
+// number of entries that are sampled for each eviction by default,
+// which is the number of samples used in the paper
+pub const DEFAULT_SAMPLES: usize = 64;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+    // clock instant when the entry was inserted
+    inserted: u64,
+    // number of accesses since the entry was inserted, including the insertion
+    uses: u64,
+    // cost of retrieving the value again after its eviction
+    cost: f64,
+}
+
+// Every entry has a priority of cost * uses / age, where the age is the
+// time since the entry was inserted, measured by a logical clock that
+// is incremented on each access. The priority of an entry that is no
+// longer accessed decays, so entries that were popular long ago leave
+// the cache without the aging that LFU needs. As the priorities change
+// with every access, there is no order of the entries: an eviction
+// samples a few entries at random, like SampledLRUCache, and evicts the
+// entry with the lowest priority.
+pub struct HyperbolicCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // number of entries sampled for each eviction
+    samples: usize,
+    // logical clock that is incremented on each access
+    clock: u64,
+    hash_builder: S,
+    // indices of the entries, hashed by key
+    table: HashTable<usize>,
+    // dense storage of the entries, in arbitrary order
+    slots: Vec<Slot<K, V>>,
+    // chooses the entries that are sampled
+    rng: Rng,
+    stats: Stats,
+}
+
+impl<K, V> HyperbolicCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> HyperbolicCache<K, V> {
+        HyperbolicCache::with_samples(capacity, DEFAULT_SAMPLES)
+    }
+
+    // Panics if the capacity or the number of samples is zero.
+    pub fn with_samples(capacity: usize, samples: usize) -> HyperbolicCache<K, V> {
+        HyperbolicCache::with_hasher(capacity, samples, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<HyperbolicCache<K, V>, CapacityError> {
+        HyperbolicCache::try_with_hasher(capacity, DEFAULT_SAMPLES, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> HyperbolicCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity or the number of samples is zero.
+    pub fn with_hasher(capacity: usize, samples: usize, hash_builder: S) -> HyperbolicCache<K, V, S> {
+        match HyperbolicCache::try_with_hasher(capacity, samples, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the number of samples is zero.
+    pub fn try_with_hasher(capacity: usize,
+                           samples: usize,
+                           hash_builder: S)
+                           -> Result<HyperbolicCache<K, V, S>, CapacityError> {
+        assert!(samples > 0, "number of samples must be nonzero");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(HyperbolicCache {
+            capacity,
+            samples,
+            clock: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            rng: Rng::default(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &slot.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &mut slot.val)
+    }
+
+    // Read the value without recording an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.slots[index].val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // The current priority of the entry of a key.
+    pub fn priority<Q>(&self, key: &Q) -> Option<f64>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| self.priority_of(&self.slots[index]))
+    }
+
+    // Insert an entry with a cost of one.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        self.insert_with_cost(key, val, 1.0)
+    }
+
+    // Replacing the value of a key counts as an access, keeps the age of
+    // the entry and sets its cost. An entry that is costly to retrieve is
+    // kept over an entry that is accessed as often but cheap to retrieve.
+    // Panics if the cost is negative or not finite.
+    pub fn insert_with_cost(&mut self, key: K, val: V, cost: f64) -> InsertResult<K, V> {
+        assert!(cost.is_finite() && cost >= 0.0, "cost must be finite and not negative");
+        let hash = self.hash_builder.hash_one(&key);
+        let now = self.tick();
+        if let Some(index) = self.index_of(hash, &key) {
+            let slot = &mut self.slots[index];
+            slot.uses += 1;
+            slot.cost = cost;
+            self.stats.replacements += 1;
+            return InsertResult {
+                replaced: Some(mem::replace(&mut slot.val, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.slots.len() >= self.capacity {
+            let index = self.sample();
+            self.stats.evictions += 1;
+            Some(self.take_slot(index))
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.slots.len();
+        self.slots.push(Slot {
+            key,
+            val,
+            hash,
+            inserted: now,
+            uses: 1,
+            cost,
+        });
+        let slots = &self.slots;
+        self.table.insert_unique(hash, index, |&i| slots[i].hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_slot(index).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.slots.clear();
+        self.clock = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Slot<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        self.tick();
+        let slot = &mut self.slots[index?];
+        slot.uses += 1;
+        Some(slot)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.slots[i].key.borrow() == key).copied()
+    }
+
+    fn tick(&mut self) -> u64 {
+        let now = self.clock;
+        self.clock = now.wrapping_add(1);
+        now
+    }
+
+    // The age of an entry is at least one, so that a new
+    // entry does not have an unbounded priority.
+    fn priority_of(&self, slot: &Slot<K, V>) -> f64 {
+        let age = self.clock.wrapping_sub(slot.inserted).max(1);
+        slot.cost * slot.uses as f64 / age as f64
+    }
+
+    // Choose the entry with the lowest priority among the sampled entries.
+    fn sample(&mut self) -> usize {
+        let n = self.slots.len();
+        let mut lowest = self.rng.below(n);
+        let mut priority = self.priority_of(&self.slots[lowest]);
+        for _ in 1..self.samples {
+            let index = self.rng.below(n);
+            let p = self.priority_of(&self.slots[index]);
+            if p < priority {
+                lowest = index;
+                priority = p;
+            }
+        }
+        lowest
+    }
+
+    // Remove a slot by moving the last slot into its place.
+    fn take_slot(&mut self, index: usize) -> (K, V) {
+        let hash = self.slots[index].hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slots are out of sync")
+            .remove();
+        let slot = self.slots.swap_remove(index);
+        if let Some(moved) = self.slots.get(index) {
+            let last = self.slots.len();
+            *self.table
+                .find_mut(moved.hash, |&i| i == last)
+                .expect("table and slots are out of sync") = index;
+        }
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn hyperbolic_eviction() {
+    let mut cache = HyperbolicCache::new(3);
+    cache.insert(0, 0);
+    cache.insert_with_cost(1, 1, 0.1);
+    cache.insert(2, 2);
+    // A cheap entry is evicted before the older entries.
+    assert_eq!(Some((1, 1)), cache.insert(3, 3).evicted);
+    cache.get(&0);
+    cache.get(&0);
+    assert_eq!(Some((2, 2)), cache.insert(4, 4).evicted);
+    // The priority of an entry decays while it is not accessed,
+    // until the popular entry is evicted.
+    let priority = cache.priority(&0).unwrap();
+    for _ in 0..8 {
+        cache.get(&3);
+        cache.get(&4);
+    }
+    assert!(cache.priority(&0).unwrap() < priority);
+    assert_eq!(Some((0, 0)), cache.insert(5, 5).evicted);
+}
@@ -0,0 +1,334 @@
+//! Least-frequently-used cache. Every operation takes constant time.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+    // node of the frequency bucket of the entry
+    bucket: usize,
+}
+
+// The entries that have been accessed the same number of times.
+struct Bucket {
+    freq: u64,
+    // node of the most-recently used entry of the bucket
+    head: usize,
+}
+
+// The entries are linked from the most frequently used entry at the
+// front to the least frequently used entry at the back, and the
+// entries with the same frequency are adjacent, from the most-recently
+// used to the least-recently used. A bucket marks the first entry of
+// each frequency, so an access moves its entry to the front of the next
+// bucket and eviction removes the entry at the back, which is the
+// least-recently used of the least frequently used entries.
+pub struct LFUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries in order of frequency
+    entries: List<Slot<K, V>>,
+    // buckets from the highest frequency at the front
+    // to the lowest frequency at the back
+    buckets: List<Bucket>,
+    stats: Stats,
+}
+
+impl<K, V> LFUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> LFUCache<K, V> {
+        LFUCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<LFUCache<K, V>, CapacityError> {
+        LFUCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> LFUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> LFUCache<K, V, S> {
+        match LFUCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<LFUCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(LFUCache {
+            capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            buckets: List::new(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &slot.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &mut slot.val)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Number of times the entry of a key has been accessed,
+    // counting its insertion.
+    pub fn frequency<Q>(&self, key: &Q) -> Option<u64>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| self.buckets.get(self.entries.get(index).bucket).freq)
+    }
+
+    // Replacing the value of a key counts as an access.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.increment(index);
+            self.stats.replacements += 1;
+            let prev = mem::replace(&mut self.entries.get_mut(index).val, val);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        // The entry is evicted before the new entry is inserted,
+        // because the new entry is the least frequently used.
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.pop_lfu()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let slot = Slot {
+            key,
+            val,
+            hash,
+            bucket: 0,
+        };
+        let lowest = self.buckets.back().filter(|&b| self.buckets.get(b).freq == 1);
+        let (index, bucket) = match lowest {
+            Some(bucket) => {
+                let head = self.buckets.get(bucket).head;
+                let index = self.entries.insert_before(head, slot);
+                self.buckets.get_mut(bucket).head = index;
+                (index, bucket)
+            }
+            None => {
+                let index = self.entries.push_back(slot);
+                (index, self.buckets.push_back(Bucket { freq: 1, head: index }))
+            }
+        };
+        self.entries.get_mut(index).bucket = bucket;
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_entry(index).1)
+    }
+
+    // The least frequently used entry. Among the entries with the
+    // lowest frequency, the least-recently used entry.
+    pub fn peek_lfu(&self) -> Option<(&K, &V)> {
+        self.entries.back().map(|index| {
+            let slot = self.entries.get(index);
+            (&slot.key, &slot.val)
+        })
+    }
+
+    // Remove the entry that is returned by peek_lfu().
+    pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+        let index = self.entries.back()?;
+        Some(self.take_entry(index))
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+        self.buckets.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Slot<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.increment(index);
+        Some(self.entries.get_mut(index))
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Move an entry to the front of the bucket of the next frequency,
+    // creating the bucket if there is no entry with that frequency.
+    fn increment(&mut self, index: usize) {
+        let bucket = self.entries.get(index).bucket;
+        let (freq, head) = {
+            let b = self.buckets.get(bucket);
+            (b.freq, b.head)
+        };
+        let higher = self.buckets.prev(bucket).filter(|&b| self.buckets.get(b).freq == freq + 1);
+        let next = self.next_in_bucket(index);
+        let target = match higher {
+            Some(higher) => {
+                let head = self.buckets.get(higher).head;
+                self.entries.move_before(index, head);
+                self.buckets.get_mut(higher).head = index;
+                higher
+            }
+            None => {
+                // The entries of the new bucket precede the
+                // entries that remain in the current bucket.
+                self.entries.move_before(index, head);
+                self.buckets.insert_before(bucket,
+                                           Bucket {
+                                               freq: freq + 1,
+                                               head: index,
+                                           })
+            }
+        };
+        self.entries.get_mut(index).bucket = target;
+        self.unlink_head(bucket, index, next);
+    }
+
+    // The entry after an entry in the same bucket.
+    fn next_in_bucket(&self, index: usize) -> Option<usize> {
+        let bucket = self.entries.get(index).bucket;
+        self.entries.next(index).filter(|&n| self.entries.get(n).bucket == bucket)
+    }
+
+    // Update a bucket when one of its entries leaves it. The bucket is
+    // removed if it has no other entry.
+    fn unlink_head(&mut self, bucket: usize, index: usize, next: Option<usize>) {
+        if self.buckets.get(bucket).head == index {
+            match next {
+                Some(next) => self.buckets.get_mut(bucket).head = next,
+                None => drop(self.buckets.remove(bucket)),
+            }
+        }
+    }
+
+    // Remove the entry with the given index from the table and the slab.
+    fn take_entry(&mut self, index: usize) -> (K, V) {
+        let (bucket, hash) = {
+            let slot = self.entries.get(index);
+            (slot.bucket, slot.hash)
+        };
+        let next = self.next_in_bucket(index);
+        self.unlink_head(bucket, index, next);
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let slot = self.entries.remove(index);
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn lfu_eviction() {
+    let mut cache = LFUCache::new(3);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    cache.insert("c", 3);
+    for _ in 0..3 {
+        cache.get("a");
+    }
+    cache.get("b");
+    cache.get("c");
+    // b and c have the same frequency and b is less recently used.
+    assert_eq!(Some((&"b", &2)), cache.peek_lfu());
+    assert_eq!(Some(("b", 2)), cache.insert("d", 4).evicted);
+    // The new entry is the least frequently used.
+    assert_eq!(Some(("d", 4)), cache.insert("e", 5).evicted);
+    cache.get("e");
+    cache.get("e");
+    assert_eq!(Some(("c", 3)), cache.insert("f", 6).evicted);
+    assert_eq!((Some(4), Some(3), Some(1)),
+               (cache.frequency("a"), cache.frequency("e"), cache.frequency("f")));
+    // Every remaining entry is found after the buckets were reordered.
+    assert_eq!(vec![Some(&1), Some(&5), Some(&6)],
+               vec![cache.peek("a"), cache.peek("e"), cache.peek("f")]);
+    assert_eq!(3, cache.buckets.len());
+    assert_eq!(Some(("f", 6)), cache.pop_lfu());
+}
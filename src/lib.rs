@@ -1 +1,55 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+extern crate hashbrown;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+pub mod adaptive;
+pub mod arc;
+pub mod cache;
+pub mod car;
+pub mod clock;
+pub mod clockpro;
+pub mod error;
+pub mod fifo;
+pub mod gdsf;
+pub mod ghost;
+pub mod hyperbolic;
+pub mod lfu;
+pub mod lirs;
+mod list;
 pub mod lru;
+pub mod lruk;
+pub mod memsize;
+pub mod mq;
+pub mod mru;
+pub mod opt;
+pub mod policy;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub mod random;
+mod rng;
+pub mod s3fifo;
+pub mod sieve;
+pub mod sim;
+mod sketch;
+pub mod slru;
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod time;
+pub mod tinylfu;
+#[cfg(feature = "std")]
+pub mod tlru;
+pub mod twoq;
+
+// The hasher used by the caches when none is specified.
+// Without the standard library the hashbrown default hasher is used.
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+#[cfg(not(feature = "std"))]
+pub type DefaultHashBuilder = hashbrown::DefaultHashBuilder;
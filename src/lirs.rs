@@ -0,0 +1,478 @@
+//! LIRS cache, as described by Jiang and Zhang in "LIRS: An Efficient Low
+//! Inter-reference Recency Set Replacement Policy to Improve Buffer Cache
+//! Performance" (SIGMETRICS 2002).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// default share of the capacity that is held by the resident HIR entries
+pub const DEFAULT_HIR_RATIO: f64 = 0.01;
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // whether the entry is in the LIR set
+    lir: bool,
+    // node of the entry in the stack
+    stack: Option<usize>,
+    // node of the entry in the queue, if it is a resident HIR entry
+    queue: Option<usize>,
+}
+
+// The referent of a node of the stack.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    // index of a cached entry
+    Resident(usize),
+    // node of an evicted HIR entry in the ghost queue
+    Ghost(usize),
+}
+
+struct Node {
+    hash: u64,
+    slot: Slot,
+}
+
+// The inter-reference recency of a key is the number of distinct keys
+// that were referenced between its last two references. The LIR set is
+// the entries with the lowest inter-reference recency and holds most of
+// the capacity. The other entries are HIR entries, and eviction takes
+// the oldest resident HIR entry from the queue.
+//
+// The stack orders the recently referenced keys by recency, and is
+// pruned so that its bottom is the least-recently used LIR entry. A HIR
+// entry that is referenced while it is in the stack has a lower
+// inter-reference recency than the bottom LIR entry, so the two are
+// exchanged. A key that is evicted while it is in the stack stays there
+// as a ghost, and enters the LIR set if it is inserted again while its
+// ghost is in the stack. A loop over more keys than the capacity keeps
+// its first keys in the LIR set, where an LRU cache would miss on every
+// reference.
+//
+// A ghost records only the hash of its key, and the number of ghosts is
+// bounded by the capacity: the oldest ghost is dropped from the stack
+// once there are more.
+pub struct LIRSCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // maximum number of entries in the LIR set
+    lir_capacity: usize,
+    // number of entries in the LIR set
+    lirs: usize,
+    hash_builder: S,
+    // indices of the cached entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the cached entries, in arbitrary order
+    entries: List<Entry<K, V>>,
+    // the stack from the most-recently referenced key at the front
+    // to the least-recently used LIR entry at the back
+    stack: List<Node>,
+    // the resident HIR entries from the newest at the front
+    // to the oldest at the back
+    queue: List<usize>,
+    // stack nodes of the ghosts, hashed by key
+    ghosts: HashTable<usize>,
+    // stack nodes of the ghosts from the newest at the front
+    // to the oldest at the back
+    ghost_queue: List<usize>,
+    stats: Stats,
+}
+
+impl<K, V> LIRSCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> LIRSCache<K, V> {
+        LIRSCache::with_ratio(capacity, DEFAULT_HIR_RATIO)
+    }
+
+    // The resident HIR entries hold hir_ratio of the capacity, rounded,
+    // and at least one entry.
+    // Panics if the capacity is zero or the ratio is not between 0 and 1.
+    pub fn with_ratio(capacity: usize, hir_ratio: f64) -> LIRSCache<K, V> {
+        LIRSCache::with_hasher(capacity, hir_ratio, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<LIRSCache<K, V>, CapacityError> {
+        LIRSCache::try_with_hasher(capacity, DEFAULT_HIR_RATIO, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> LIRSCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero or the ratio is not between 0 and 1.
+    pub fn with_hasher(capacity: usize, hir_ratio: f64, hash_builder: S) -> LIRSCache<K, V, S> {
+        match LIRSCache::try_with_hasher(capacity, hir_ratio, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the ratio is not between 0 and 1.
+    pub fn try_with_hasher(capacity: usize,
+                           hir_ratio: f64,
+                           hash_builder: S)
+                           -> Result<LIRSCache<K, V, S>, CapacityError> {
+        assert!((0.0..=1.0).contains(&hir_ratio), "HIR ratio must be between 0 and 1");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        let hir_capacity = cmp::max(1, (capacity as f64 * hir_ratio + 0.5) as usize);
+        Ok(LIRSCache {
+            capacity,
+            lir_capacity: capacity.saturating_sub(hir_capacity),
+            lirs: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            stack: List::new(),
+            queue: List::new(),
+            ghosts: HashTable::new(),
+            ghost_queue: List::new(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without counting a reference.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Whether the entry of a key is in the LIR set.
+    pub fn is_lir<Q>(&self, key: &Q) -> Option<bool>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| self.entries.get(index).lir)
+    }
+
+    // Replacing the value of a key counts as a reference.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.stats.replacements += 1;
+            self.reference(index);
+            let prev = mem::replace(&mut self.entries.get_mut(index).val, val);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.evict()
+        } else {
+            None
+        };
+        let ghost = self.take_ghost(hash);
+        let index = self.entries.push_front(Entry {
+            key,
+            val,
+            hash,
+            lir: false,
+            stack: None,
+            queue: None,
+        });
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        match ghost {
+            Some(node) => {
+                self.stack.get_mut(node).slot = Slot::Resident(index);
+                self.stack.move_to_front(node);
+                self.entries.get_mut(index).stack = Some(node);
+                self.promote(index);
+            }
+            None if self.lirs < self.lir_capacity => {
+                self.push_stack(index);
+                self.promote(index);
+            }
+            None => {
+                self.push_stack(index);
+                self.entries.get_mut(index).queue = Some(self.queue.push_front(index));
+                // The bottom of the stack is a HIR entry only if the LIR
+                // set is empty, which it is with a capacity of one entry.
+                self.prune();
+            }
+        }
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        let e = self.take_entry(index);
+        if let Some(node) = e.stack {
+            self.stack.remove(node);
+        }
+        if e.lir {
+            self.lirs -= 1;
+            self.prune();
+        }
+        Some(e.val)
+    }
+
+    // The ghosts are cleared as well.
+    pub fn clear(&mut self) {
+        self.lirs = 0;
+        self.table.clear();
+        self.entries.clear();
+        self.stack.clear();
+        self.queue.clear();
+        self.ghosts.clear();
+        self.ghost_queue.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn lir_capacity(&self) -> usize {
+        self.lir_capacity
+    }
+
+    // The number of entries in the LIR set, of resident HIR entries,
+    // and of ghosts.
+    pub fn sets(&self) -> (usize, usize, usize) {
+        (self.lirs, self.queue.len(), self.ghost_queue.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.reference(index);
+        Some(index)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // A reference moves the entry to the top of the stack. A HIR entry
+    // that was still in the stack enters the LIR set, and one that was
+    // not moves to the back of the queue.
+    fn reference(&mut self, index: usize) {
+        let (lir, stack, queue) = {
+            let e = self.entries.get(index);
+            (e.lir, e.stack, e.queue)
+        };
+        match stack {
+            Some(node) => self.stack.move_to_front(node),
+            None => self.push_stack(index),
+        }
+        if lir {
+            self.prune();
+        } else if stack.is_some() {
+            if let Some(node) = queue {
+                self.queue.remove(node);
+            }
+            self.entries.get_mut(index).queue = None;
+            self.promote(index);
+        } else if let Some(node) = queue {
+            self.queue.move_to_front(node);
+            self.prune();
+        }
+    }
+
+    fn push_stack(&mut self, index: usize) {
+        let hash = self.entries.get(index).hash;
+        let node = self.stack.push_front(Node {
+            hash,
+            slot: Slot::Resident(index),
+        });
+        self.entries.get_mut(index).stack = Some(node);
+    }
+
+    // Add an entry at the top of the stack to the LIR set. When the
+    // LIR set is full, its least-recently used entry becomes a HIR entry.
+    fn promote(&mut self, index: usize) {
+        self.entries.get_mut(index).lir = true;
+        self.lirs += 1;
+        if self.lirs > self.lir_capacity {
+            self.demote_bottom();
+        }
+    }
+
+    fn demote_bottom(&mut self) {
+        let node = match self.stack.back() {
+            Some(node) => node,
+            None => return,
+        };
+        if let Slot::Resident(index) = self.stack.remove(node).slot {
+            let queue = self.queue.push_front(index);
+            let e = self.entries.get_mut(index);
+            e.lir = false;
+            e.stack = None;
+            e.queue = Some(queue);
+            self.lirs -= 1;
+        }
+        self.prune();
+    }
+
+    // Remove the HIR entries and ghosts from the bottom of the stack,
+    // up to the least-recently used LIR entry.
+    fn prune(&mut self) {
+        while let Some(node) = self.stack.back() {
+            match self.stack.get(node).slot {
+                Slot::Resident(index) if self.entries.get(index).lir => break,
+                Slot::Resident(index) => self.entries.get_mut(index).stack = None,
+                Slot::Ghost(ghost) => {
+                    self.ghost_queue.remove(ghost);
+                    self.forget(node);
+                }
+            }
+            self.stack.remove(node);
+        }
+    }
+
+    // Evict the oldest resident HIR entry. Its node in the stack,
+    // if it has one, becomes a ghost.
+    fn evict(&mut self) -> Option<(K, V)> {
+        let index = *self.queue.get(self.queue.back()?);
+        let e = self.take_entry(index);
+        if let Some(node) = e.stack {
+            let ghost = self.ghost_queue.push_front(node);
+            self.stack.get_mut(node).slot = Slot::Ghost(ghost);
+            let stack = &self.stack;
+            self.ghosts.insert_unique(e.hash, node, |&n| stack.get(n).hash);
+            if self.ghost_queue.len() > self.capacity {
+                if let Some(oldest) = self.ghost_queue.back() {
+                    let node = self.ghost_queue.remove(oldest);
+                    self.forget(node);
+                    self.stack.remove(node);
+                }
+            }
+        }
+        Some((e.key, e.val))
+    }
+
+    // Remove a ghost from the ghost table.
+    fn forget(&mut self, node: usize) {
+        let hash = self.stack.get(node).hash;
+        self.ghosts
+            .find_entry(hash, |&n| n == node)
+            .expect("ghost table and stack are out of sync")
+            .remove();
+    }
+
+    // Remove the ghost of a hash from the ghost table and the ghost
+    // queue. Returns its node, which is left in the stack.
+    fn take_ghost(&mut self, hash: u64) -> Option<usize> {
+        let stack = &self.stack;
+        let (node, _) = self.ghosts.find_entry(hash, |&n| stack.get(n).hash == hash).ok()?.remove();
+        if let Slot::Ghost(ghost) = self.stack.get(node).slot {
+            self.ghost_queue.remove(ghost);
+        }
+        Some(node)
+    }
+
+    // Remove a cached entry from the table, the slab and the queue.
+    // Its node in the stack is left to the caller.
+    fn take_entry(&mut self, index: usize) -> Entry<K, V> {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let e = self.entries.remove(index);
+        if let Some(node) = e.queue {
+            self.queue.remove(node);
+        }
+        e
+    }
+}
+
+#[test]
+fn lirs_sets() {
+    let mut cache = LIRSCache::new(3);
+    assert_eq!(2, cache.lir_capacity());
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    cache.insert("c", 3);
+    // A HIR entry that is referenced while it is in the stack
+    // is exchanged with the bottom LIR entry.
+    cache.get("c");
+    assert_eq!((Some(true), Some(false)), (cache.is_lir("c"), cache.is_lir("a")));
+    assert_eq!(Some(("a", 1)), cache.insert("d", 4).evicted);
+    assert_eq!(Some(("d", 4)), cache.insert("e", 5).evicted);
+    assert_eq!((2, 1, 1), cache.sets());
+    // A key that is inserted while its ghost is in the stack
+    // enters the LIR set.
+    assert_eq!(Some(("e", 5)), cache.insert("d", 6).evicted);
+    assert_eq!((Some(true), Some(false)), (cache.is_lir("d"), cache.is_lir("b")));
+    assert_eq!((2, 1, 1), cache.sets());
+    // A loop over more keys than the capacity hits the LIR set.
+    let mut cache = LIRSCache::new(4);
+    for _ in 0..10 {
+        for i in 0..5 {
+            if cache.get(&i).is_none() {
+                cache.insert(i, i);
+            }
+        }
+    }
+    assert_eq!(27, cache.stats().hits());
+    assert_eq!(vec![Some(true); 3], (0..3).map(|i| cache.is_lir(&i)).collect::<Vec<_>>());
+}
@@ -0,0 +1,372 @@
+//! A doubly linked list whose nodes are stored in a slab and linked by index.
+//! The index of a node is stable until the node is removed, so a node can be
+//! moved or removed in constant time by anyone who holds its index.
+
+use alloc::vec::Vec;
+use core::iter::Enumerate;
+use core::mem;
+use core::slice;
+
+// index that marks the absence of a node
+const NIL: usize = usize::MAX;
+
+#[derive(Clone)]
+struct Node<T> {
+    // neighbour towards the front of the list
+    prev: usize,
+    // neighbour towards the back of the list, or the
+    // next vacant slot if the node is on the free list
+    next: usize,
+    // None if the slot is vacant
+    val: Option<T>,
+}
+
+#[derive(Clone)]
+pub struct List<T> {
+    // slab that stores the nodes of the list
+    nodes: Vec<Node<T>>,
+    // first node of the list
+    head: usize,
+    // last node of the list
+    tail: usize,
+    // first vacant slot of the slab
+    free: usize,
+    // number of nodes in the list
+    len: usize,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        List {
+            nodes: Vec::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+            free: NIL,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn front(&self) -> Option<usize> {
+        index(self.head)
+    }
+
+    pub fn back(&self) -> Option<usize> {
+        index(self.tail)
+    }
+
+    // The neighbour of a node towards the front of the list.
+    pub fn prev(&self, node: usize) -> Option<usize> {
+        index(self.nodes[node].prev)
+    }
+
+    // The neighbour of a node towards the back of the list.
+    pub fn next(&self, node: usize) -> Option<usize> {
+        index(self.nodes[node].next)
+    }
+
+    // True if the slot holds a node of the list.
+    #[cfg(feature = "std")]
+    pub fn contains(&self, node: usize) -> bool {
+        self.nodes.get(node).is_some_and(|n| n.val.is_some())
+    }
+
+    // Panics if the slot is vacant.
+    pub fn get(&self, node: usize) -> &T {
+        self.nodes[node].val.as_ref().expect("list node is vacant")
+    }
+
+    // Panics if the slot is vacant.
+    pub fn get_mut(&mut self, node: usize) -> &mut T {
+        self.nodes[node].val.as_mut().expect("list node is vacant")
+    }
+
+    // Returns the index of the new node.
+    pub fn push_front(&mut self, val: T) -> usize {
+        let node = self.alloc(val);
+        self.link_front(node);
+        node
+    }
+
+    // Returns the index of the new node.
+    pub fn push_back(&mut self, val: T) -> usize {
+        let node = self.alloc(val);
+        self.link_back(node);
+        node
+    }
+
+    // Insert a node in front of another node. Returns the index of the new node.
+    pub fn insert_before(&mut self, target: usize, val: T) -> usize {
+        let node = self.alloc(val);
+        self.link_before(node, target);
+        node
+    }
+
+    // The slot of the node is reused by a later push.
+    // Panics if the slot is vacant.
+    pub fn remove(&mut self, node: usize) -> T {
+        let val = self.nodes[node].val.take().expect("list node is vacant");
+        self.unlink(node);
+        self.nodes[node].next = self.free;
+        self.free = node;
+        self.len -= 1;
+        val
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.front()?;
+        Some(self.remove(node))
+    }
+
+    pub fn move_to_front(&mut self, node: usize) {
+        if self.head != node {
+            self.unlink(node);
+            self.link_front(node);
+        }
+    }
+
+    pub fn move_to_back(&mut self, node: usize) {
+        if self.tail != node {
+            self.unlink(node);
+            self.link_back(node);
+        }
+    }
+
+    // Move a node in front of another node.
+    pub fn move_before(&mut self, node: usize, target: usize) {
+        if node != target && self.nodes[target].prev != node {
+            self.unlink(node);
+            self.link_before(node, target);
+        }
+    }
+
+    // The allocated capacity of the slab is retained.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.head = NIL;
+        self.tail = NIL;
+        self.free = NIL;
+        self.len = 0;
+    }
+
+    // Reserve room for additional nodes beyond the vacant slots.
+    pub fn reserve(&mut self, additional: usize) {
+        let vacant = self.nodes.len() - self.len;
+        self.nodes.reserve(additional.saturating_sub(vacant));
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    // Bytes allocated for the slab, including the vacant slots.
+    // The heap memory owned by the values is not counted.
+    pub fn heap_size(&self) -> usize {
+        self.nodes.capacity() * mem::size_of::<Node<T>>()
+    }
+
+    // Iterates from the front of the list to the back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+        }
+    }
+
+    // Iterates in the order of the slots rather than the order of the list.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            nodes: self.nodes.iter_mut().enumerate(),
+            len: self.len,
+        }
+    }
+
+    fn alloc(&mut self, val: T) -> usize {
+        self.len += 1;
+        let node = Node {
+            prev: NIL,
+            next: NIL,
+            val: Some(val),
+        };
+        if self.free == NIL {
+            self.nodes.push(node);
+            return self.nodes.len() - 1;
+        }
+        let slot = self.free;
+        self.free = mem::replace(&mut self.nodes[slot], node).next;
+        slot
+    }
+
+    fn link_front(&mut self, node: usize) {
+        self.nodes[node].prev = NIL;
+        self.nodes[node].next = self.head;
+        match self.head {
+            NIL => self.tail = node,
+            head => self.nodes[head].prev = node,
+        }
+        self.head = node;
+    }
+
+    fn link_back(&mut self, node: usize) {
+        self.nodes[node].prev = self.tail;
+        self.nodes[node].next = NIL;
+        match self.tail {
+            NIL => self.head = node,
+            tail => self.nodes[tail].next = node,
+        }
+        self.tail = node;
+    }
+
+    fn link_before(&mut self, node: usize, target: usize) {
+        let prev = self.nodes[target].prev;
+        self.nodes[node].prev = prev;
+        self.nodes[node].next = target;
+        self.nodes[target].prev = node;
+        match prev {
+            NIL => self.head = node,
+            prev => self.nodes[prev].next = node,
+        }
+    }
+
+    fn unlink(&mut self, node: usize) {
+        let (prev, next) = (self.nodes[node].prev, self.nodes[node].next);
+        match prev {
+            NIL => self.head = next,
+            prev => self.nodes[prev].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.nodes[next].prev = prev,
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+fn index(node: usize) -> Option<usize> {
+    if node == NIL { None } else { Some(node) }
+}
+
+// Yields the index and the value of each node.
+pub struct Iter<'a, T: 'a> {
+    list: &'a List<T>,
+    front: usize,
+    back: usize,
+    // number of nodes that have not been yielded from either end
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.front;
+        self.front = self.list.nodes[node].next;
+        self.len -= 1;
+        Some((node, self.list.get(node)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.back;
+        self.back = self.list.nodes[node].prev;
+        self.len -= 1;
+        Some((node, self.list.get(node)))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+// Yields the index and the value of each node, skipping the vacant slots.
+pub struct IterMut<'a, T: 'a> {
+    nodes: Enumerate<slice::IterMut<'a, Node<T>>>,
+    // number of nodes that have not been yielded
+    len: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (usize, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.nodes.find_map(|(node, n)| n.val.as_mut().map(|val| (node, val)))?;
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+#[test]
+fn list_push_remove() {
+    let mut list = List::new();
+    let a = list.push_front('a');
+    let b = list.push_front('b');
+    let c = list.push_back('c');
+    assert_eq!(vec!['b', 'a', 'c'], list.iter().map(|(_, &v)| v).collect::<Vec<_>>());
+    assert_eq!((Some(b), Some(c)), (list.front(), list.back()));
+    assert_eq!('a', list.remove(a));
+    #[cfg(feature = "std")]
+    assert!(!list.contains(a) && list.contains(b));
+    assert_eq!(2, list.len());
+    // the vacant slot is reused
+    assert_eq!(a, list.push_back('d'));
+    assert_eq!(vec![(a, &'d'), (c, &'c')], list.iter().rev().take(2).collect::<Vec<_>>());
+    for (_, v) in list.iter_mut() {
+        *v = v.to_ascii_uppercase();
+    }
+    assert_eq!(Some('B'), list.pop_front());
+    assert_eq!('C', *list.get(c));
+    list.clear();
+    assert_eq!(0, list.len());
+    assert_eq!(None, list.iter().next());
+}
+
+#[test]
+fn list_move() {
+    let mut list = List::new();
+    let nodes: Vec<usize> = (0..4).map(|v| list.push_back(v)).collect();
+    list.move_to_front(nodes[2]);
+    list.move_to_back(nodes[0]);
+    list.move_to_front(nodes[2]);
+    assert_eq!(vec![2, 1, 3, 0], list.iter().map(|(_, &v)| v).collect::<Vec<_>>());
+    list.remove(nodes[2]);
+    list.remove(nodes[0]);
+    assert_eq!(vec![1, 3], list.iter().map(|(_, &v)| v).collect::<Vec<_>>());
+    assert_eq!(vec![3, 1], list.iter().rev().map(|(_, &v)| v).collect::<Vec<_>>());
+    let mut iter = list.iter();
+    assert_eq!(Some((nodes[1], &1)), iter.next());
+    assert_eq!(Some((nodes[3], &3)), iter.next_back());
+    assert_eq!(None, iter.next());
+    let node = list.insert_before(nodes[3], 2);
+    list.move_before(nodes[3], nodes[1]);
+    assert_eq!(vec![3, 1, 2], list.iter().map(|(_, &v)| v).collect::<Vec<_>>());
+    assert_eq!((Some(node), None), (list.next(nodes[1]), list.prev(nodes[3])));
+}
@@ -0,0 +1,212 @@
+//! A fixed-capacity LRU cache whose entries are stored inline.
+
+use core::borrow::Borrow;
+use core::mem;
+use core::slice;
+
+use super::InsertResult;
+
+// The entries are kept in an array in recency order, from the
+// most-recently used entry at the front to the least-recently used
+// entry at the back. Lookups scan the array and promotions rotate
+// its prefix, which is faster than hashing for small capacities.
+// The cache never allocates, so it is suited to caches of up to
+// 64 entries or so in hot paths and without the standard library.
+pub struct ArrayLRU<K, V, const N: usize> {
+    // The first len elements are occupied.
+    entries: [Option<(K, V)>; N],
+    len: usize,
+}
+
+impl<K, V, const N: usize> ArrayLRU<K, V, N>
+    where K: Eq
+{
+    const NONZERO: () = assert!(N > 0, "capacity must be nonzero");
+
+    // Fails to compile if the capacity is zero.
+    pub fn new() -> ArrayLRU<K, V, N> {
+        let () = Self::NONZERO;
+        ArrayLRU {
+            entries: core::array::from_fn(|_| None),
+            len: 0,
+        }
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.get_mut(key).map(|val| &*val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        let index = self.position(key)?;
+        self.entries[..=index].rotate_right(1);
+        self.entries[0].as_mut().map(|e| &mut e.1)
+    }
+
+    // Read the value without promoting the entry.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.position(key).and_then(|index| self.entries[index].as_ref()).map(|e| &e.1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.position(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        if let Some(index) = self.position(&key) {
+            self.entries[..=index].rotate_right(1);
+            let e = self.entries[0].as_mut().expect("entry is occupied");
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.1, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.len == N {
+            self.entries[N - 1].take()
+        } else {
+            self.len += 1;
+            None
+        };
+        self.entries[..self.len].rotate_right(1);
+        self.entries[0] = Some((key, val));
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        let index = self.position(key)?;
+        let e = self.entries[index].take();
+        self.entries[index..self.len].rotate_left(1);
+        self.len -= 1;
+        e.map(|e| e.1)
+    }
+
+    // Remove the least-recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.entries[self.len].take()
+    }
+
+    pub fn clear(&mut self) {
+        for e in &mut self.entries[..self.len] {
+            *e = None;
+        }
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    // Iterates from the most-recently used entry
+    // to the least-recently used entry.
+    pub fn iter(&self) -> ArrayIter<'_, K, V> {
+        ArrayIter { entries: self.entries[..self.len].iter() }
+    }
+
+    fn position<Q>(&self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.entries[..self.len]
+            .iter()
+            .position(|e| e.as_ref().is_some_and(|e| e.0.borrow() == key))
+    }
+}
+
+impl<K, V, const N: usize> Default for ArrayLRU<K, V, N>
+    where K: Eq
+{
+    fn default() -> Self {
+        ArrayLRU::new()
+    }
+}
+
+pub struct ArrayIter<'a, K: 'a, V: 'a> {
+    entries: slice::Iter<'a, Option<(K, V)>>,
+}
+
+impl<'a, K, V> Iterator for ArrayIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().and_then(|e| e.as_ref()).map(|e| (&e.0, &e.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ArrayIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().and_then(|e| e.as_ref()).map(|e| (&e.0, &e.1))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ArrayIter<'a, K, V> {}
+
+#[test]
+fn array_lru() {
+    let mut cache: ArrayLRU<&str, i32, 3> = ArrayLRU::new();
+    assert!(cache.is_empty());
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    cache.insert("c", 3);
+    assert!(cache.is_full());
+    assert_eq!(Some(&1), cache.get("a"));
+    assert_eq!(None, cache.get("d"));
+    assert_eq!(Some(&2), cache.peek("b"));
+    // b is the least-recently used entry because peek does not promote.
+    assert_eq!(Some(("b", 2)), cache.insert("d", 4).evicted);
+    assert_eq!(Some(3), cache.insert("c", 5).replaced);
+    assert_eq!(vec![("c", 5), ("d", 4), ("a", 1)],
+               cache.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>());
+    *cache.get_mut("a").unwrap() = 6;
+    assert_eq!(Some(4), cache.remove("d"));
+    assert!(!cache.contains_key("d"));
+    assert_eq!((2, 3), (cache.len(), cache.capacity()));
+    assert_eq!(Some(("c", 5)), cache.pop_lru());
+    assert_eq!(Some(&6), cache.peek("a"));
+    cache.clear();
+    assert_eq!(None, cache.pop_lru());
+}
+
+#[test]
+fn array_lru_inline() {
+    // The entries are stored in the cache itself.
+    assert_eq!(mem::size_of::<[Option<(u32, u32)>; 8]>() + mem::size_of::<usize>(),
+               mem::size_of::<ArrayLRU<u32, u32, 8>>());
+    let mut cache = ArrayLRU::<String, usize, 1>::default();
+    cache.insert("a".to_string(), 1);
+    assert_eq!(Some(("a".to_string(), 1)), cache.insert("b".to_string(), 2).evicted);
+    assert_eq!(Some(&2), cache.get("b"));
+}
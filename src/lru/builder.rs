@@ -0,0 +1,169 @@
+//! A builder that configures an LRUCache before it is created.
+
+use DefaultHashBuilder;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use core::time::Duration;
+use error::CapacityError;
+#[cfg(feature = "std")]
+use time::Clock;
+
+use super::{DEFAULT_CAPACITY, EvictionCallback, EvictionReason, LRUCache, Listener, Weigher};
+
+pub struct LRUCacheBuilder<K, V, S = DefaultHashBuilder> {
+    // None if the cache is unbounded
+    capacity: Option<usize>,
+    low_watermark: Option<usize>,
+    protected_capacity: Option<usize>,
+    hash_builder: S,
+    weigher: Option<Weigher<K, V>>,
+    on_evict: Option<EvictionCallback<K, V>>,
+    listener: Option<Listener<K, V>>,
+    #[cfg(feature = "std")]
+    ttl: Option<Duration>,
+    #[cfg(feature = "std")]
+    tti: Option<Duration>,
+    #[cfg(feature = "std")]
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl<K, V> LRUCacheBuilder<K, V> {
+    pub(super) fn new() -> LRUCacheBuilder<K, V> {
+        LRUCacheBuilder {
+            capacity: Some(DEFAULT_CAPACITY),
+            low_watermark: None,
+            protected_capacity: None,
+            hash_builder: DefaultHashBuilder::default(),
+            weigher: None,
+            on_evict: None,
+            listener: None,
+            #[cfg(feature = "std")]
+            ttl: None,
+            #[cfg(feature = "std")]
+            tti: None,
+            #[cfg(feature = "std")]
+            clock: None,
+        }
+    }
+}
+
+impl<K, V, S> LRUCacheBuilder<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // The maximum number of entries, or the maximum total
+    // weight of the entries if there is a weigher.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    // The capacity is the high watermark. See LRUCache::set_watermarks().
+    // Building panics if the low watermark exceeds the high watermark.
+    pub fn watermarks(mut self, high: usize, low: usize) -> Self {
+        self.capacity = Some(high);
+        self.low_watermark = Some(low);
+        self
+    }
+
+    // See LRUCache::set_probation().
+    pub fn probation(mut self, protected_capacity: usize) -> Self {
+        self.protected_capacity = Some(protected_capacity);
+        self
+    }
+
+    pub fn unbounded(mut self) -> Self {
+        self.capacity = None;
+        self
+    }
+
+    pub fn hasher<T: BuildHasher>(self, hash_builder: T) -> LRUCacheBuilder<K, V, T> {
+        LRUCacheBuilder {
+            capacity: self.capacity,
+            low_watermark: self.low_watermark,
+            protected_capacity: self.protected_capacity,
+            hash_builder,
+            weigher: self.weigher,
+            on_evict: self.on_evict,
+            listener: self.listener,
+            #[cfg(feature = "std")]
+            ttl: self.ttl,
+            #[cfg(feature = "std")]
+            tti: self.tti,
+            #[cfg(feature = "std")]
+            clock: self.clock,
+        }
+    }
+
+    pub fn weigher<F>(mut self, f: F) -> Self
+        where F: Fn(&K, &V) -> usize + Send + Sync + 'static
+    {
+        self.weigher = Some(Arc::new(f));
+        self
+    }
+
+    pub fn on_evict<F>(mut self, f: F) -> Self
+        where F: FnMut(K, V) + Send + Sync + 'static
+    {
+        self.on_evict = Some(Box::new(f));
+        self
+    }
+
+    pub fn listener<F>(mut self, f: F) -> Self
+        where F: FnMut(&K, &V, EvictionReason) + Send + Sync + 'static
+    {
+        self.listener = Some(Box::new(f));
+        self
+    }
+
+    #[cfg(feature = "std")]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    #[cfg(feature = "std")]
+    pub fn tti(mut self, tti: Duration) -> Self {
+        self.tti = Some(tti);
+        self
+    }
+
+    #[cfg(feature = "std")]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    // Panics if the capacity is zero.
+    pub fn build(self) -> LRUCache<K, V, S> {
+        match self.try_build() {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_build(self) -> Result<LRUCache<K, V, S>, CapacityError> {
+        let mut cache = match self.capacity {
+            Some(capacity) => LRUCache::try_with_hasher(capacity, self.hash_builder)?,
+            None => LRUCache::unbounded_with_hasher(self.hash_builder),
+        };
+        if let (Some(high), Some(low)) = (cache.capacity, self.low_watermark) {
+            cache.set_watermarks(high, low);
+        }
+        cache.protected_capacity = self.protected_capacity;
+        cache.weigher = self.weigher;
+        cache.on_evict = self.on_evict;
+        cache.listener = self.listener;
+        #[cfg(feature = "std")]
+        {
+            if let Some(clock) = self.clock {
+                cache.expiry.set_clock(clock);
+            }
+            cache.expiry.ttl = self.ttl;
+            cache.expiry.tti = self.tti;
+        }
+        Ok(cache)
+    }
+}
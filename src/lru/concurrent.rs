@@ -0,0 +1,388 @@
+//! A concurrent LRU cache whose lookups do not wait for the recency order.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use core::ptr;
+use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use stats::StatsSnapshot;
+
+use super::InsertResult;
+use super::sharded::{default_shards, lock, shard_index};
+
+// number of accesses that a read buffer holds before it is drained
+const BUFFER_CAPACITY: usize = 64;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+    // index of the node of the key in the recency order
+    node: usize,
+}
+
+// Counters that are updated without a lock, so that
+// lookups do not serialize on the statistics.
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+    replacements: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            replacements: self.replacements.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            ..StatsSnapshot::default()
+        }
+    }
+
+    fn reset(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            hits: self.hits.swap(0, Ordering::Relaxed),
+            misses: self.misses.swap(0, Ordering::Relaxed),
+            insertions: self.insertions.swap(0, Ordering::Relaxed),
+            replacements: self.replacements.swap(0, Ordering::Relaxed),
+            evictions: self.evictions.swap(0, Ordering::Relaxed),
+            ..StatsSnapshot::default()
+        }
+    }
+}
+
+fn bump(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+// The values of the keys that belong to a shard, and the
+// counters of the operations on those keys.
+struct Shard<K, V> {
+    table: RwLock<HashTable<Slot<K, V>>>,
+    stats: Counters,
+}
+
+// The values are divided into shards by the hashes of their keys, and
+// each shard has its own read-write lock. Lookups share the read lock of
+// the shard of their key and record the entry that they accessed in one
+// of several read buffers, without locking the recency order. The
+// recorded accesses are applied to the recency order when a buffer fills
+// up and before each write. An access is dropped if its buffer is busy
+// or full, so the recency order is approximate under heavy contention.
+//
+// A write excludes only the lookups of the shard of its key, and of the
+// shard of the entry that it evicts. The recency order is one list
+// behind a mutex, so writes still wait for each other, and a lookup
+// that fills its read buffer drains it only if the recency order is
+// not locked. The counters are kept per shard and are not locked.
+pub struct ConcurrentLRUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // values of the cache, read under the shared lock of their shard
+    shards: Box<[Shard<K, V>]>,
+    // hashes of the keys from the most-recently used entry at the
+    // front to the least-recently used entry at the back.
+    // Always locked before the shards.
+    order: Mutex<List<u64>>,
+    // nodes of the recency order that were accessed by lookups
+    buffers: Box<[Mutex<Vec<usize>>]>,
+}
+
+impl<K, V> ConcurrentLRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> ConcurrentLRUCache<K, V> {
+        ConcurrentLRUCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<ConcurrentLRUCache<K, V>, CapacityError> {
+        ConcurrentLRUCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ConcurrentLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> ConcurrentLRUCache<K, V, S> {
+        match ConcurrentLRUCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<ConcurrentLRUCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        let shards = default_shards();
+        let per_shard = capacity.div_ceil(shards);
+        Ok(ConcurrentLRUCache {
+            capacity,
+            hash_builder,
+            shards: (0..shards)
+                .map(|_| {
+                    Shard {
+                        table: RwLock::new(HashTable::with_capacity(per_shard)),
+                        stats: Counters::default(),
+                    }
+                })
+                .collect(),
+            order: Mutex::new(List::with_capacity(capacity)),
+            buffers: (0..default_shards()).map(|_| Mutex::new(Vec::new())).collect(),
+        })
+    }
+
+    // The value is cloned so that the shared lock is not held by the caller.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let shard = self.shard(hash);
+        let found = read(&shard.table)
+            .find(hash, |slot| slot.key.borrow() == key)
+            .map(|slot| (slot.val.clone(), slot.node));
+        let (val, node) = match found {
+            Some(found) => found,
+            None => {
+                bump(&shard.stats.misses);
+                return None;
+            }
+        };
+        bump(&shard.stats.hits);
+        self.record(hash, node);
+        Some(val)
+    }
+
+    // Read the value without recording an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        let hash = self.hash_builder.hash_one(key);
+        read(&self.shard(hash).table)
+            .find(hash, |slot| slot.key.borrow() == key)
+            .map(|slot| slot.val.clone())
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        read(&self.shard(hash).table).find(hash, |slot| slot.key.borrow() == key).is_some()
+    }
+
+    // The accesses that were recorded before the insertion are applied
+    // first, so that the least-recently used entry is evicted. Each node
+    // of the recency order is an entry, so the order counts the entries.
+    pub fn insert(&self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        let mut order = lock(&self.order);
+        self.drain(&mut order);
+        let shard = self.shard(hash);
+        let mut data = write(&shard.table);
+        if let Some(slot) = data.find_mut(hash, |slot| slot.key == key) {
+            order.move_to_front(slot.node);
+            let prev = mem::replace(&mut slot.val, val);
+            bump(&shard.stats.replacements);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        let mut evicted = None;
+        if order.len() >= self.capacity {
+            let node = order.back().expect("recency order and values are out of sync");
+            let victim = order.remove(node);
+            let victim_shard = self.shard(victim);
+            // The shard of the key is already locked.
+            let slot = if ptr::eq(victim_shard, shard) {
+                take_slot(&mut data, victim, node)
+            } else {
+                take_slot(&mut write(&victim_shard.table), victim, node)
+            };
+            bump(&victim_shard.stats.evictions);
+            evicted = Some((slot.key, slot.val));
+        }
+        let node = order.push_front(hash);
+        let slot = Slot { key, val, hash, node };
+        data.insert_unique(hash, slot, |slot| slot.hash);
+        bump(&shard.stats.insertions);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let mut order = lock(&self.order);
+        let mut data = write(&self.shard(hash).table);
+        let (slot, _) = data.find_entry(hash, |slot| slot.key.borrow() == key).ok()?.remove();
+        order.remove(slot.node);
+        Some(slot.val)
+    }
+
+    // The recency order is locked while the shards are cleared
+    // one at a time, so no entry is inserted in the meantime.
+    pub fn clear(&self) {
+        let mut order = lock(&self.order);
+        for buffer in self.buffers.iter() {
+            lock(buffer).clear();
+        }
+        for shard in self.shards.iter() {
+            write(&shard.table).clear();
+        }
+        order.clear();
+    }
+
+    // The shards are read one at a time, so the result may not
+    // reflect concurrent operations.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read(&shard.table).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Sum of the counters of the shards.
+    pub fn stats(&self) -> StatsSnapshot {
+        self.shards.iter().map(|shard| shard.stats.snapshot()).sum()
+    }
+
+    // Set the counters of the shards to zero and return the sum
+    // of their previous values.
+    pub fn reset_stats(&self) -> StatsSnapshot {
+        self.shards.iter().map(|shard| shard.stats.reset()).sum()
+    }
+
+    fn shard(&self, hash: u64) -> &Shard<K, V> {
+        &self.shards[shard_index(hash, self.shards.len())]
+    }
+
+    // Record an access in a read buffer without waiting for a lock.
+    // A full buffer is drained if the recency order is not locked.
+    fn record(&self, hash: u64, node: usize) {
+        let mut buffer = match self.buffers[hash as usize % self.buffers.len()].try_lock() {
+            Ok(buffer) => buffer,
+            Err(_) => return,
+        };
+        if buffer.len() < BUFFER_CAPACITY {
+            buffer.push(node);
+        }
+        if buffer.len() == BUFFER_CAPACITY {
+            if let Ok(mut order) = self.order.try_lock() {
+                promote(&mut order, buffer.drain(..));
+            }
+        }
+    }
+
+    // Apply the accesses of every read buffer to the recency order.
+    fn drain(&self, order: &mut List<u64>) {
+        for buffer in self.buffers.iter() {
+            promote(order, lock(buffer).drain(..));
+        }
+    }
+}
+
+// The node of an access may have been removed, or reused by another
+// entry, after the access was recorded. Such an access promotes the
+// wrong entry, which only makes the recency order less precise.
+fn promote<I: Iterator<Item = usize>>(order: &mut List<u64>, nodes: I) {
+    for node in nodes {
+        if order.contains(node) {
+            order.move_to_front(node);
+        }
+    }
+}
+
+// Remove the slot of a node of the recency order.
+fn take_slot<K, V>(data: &mut HashTable<Slot<K, V>>, hash: u64, node: usize) -> Slot<K, V> {
+    data.find_entry(hash, |slot| slot.node == node)
+        .ok()
+        .expect("recency order and values are out of sync")
+        .remove()
+        .0
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn concurrent_lru_cache() {
+    let cache = ConcurrentLRUCache::new(3);
+    assert!(cache.is_empty());
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    assert_eq!(Some(2), cache.get(&1));
+    assert_eq!(Some(4), cache.peek(&3));
+    // The recorded access to 1 is applied before 3 is evicted.
+    assert_eq!(Some((3, 4)), cache.insert(7, 8).evicted);
+    assert_eq!(Some(2), cache.insert(1, 3).replaced);
+    assert_eq!(Some((5, 6)), cache.insert(9, 10).evicted);
+    assert_eq!(Some(8), cache.remove(&7));
+    assert!(!cache.contains_key(&7));
+    assert_eq!((2, 3), (cache.len(), cache.capacity()));
+    let stats = cache.reset_stats();
+    assert_eq!((1, 5, 1, 2), (stats.hits, stats.insertions, stats.replacements, stats.evictions));
+    assert_eq!(StatsSnapshot::default(), cache.stats());
+    cache.clear();
+    assert_eq!(None, cache.get(&1));
+    assert!(ConcurrentLRUCache::<i32, i32>::try_new(0).is_err());
+}
+
+#[test]
+fn concurrent_lru_threads() {
+    let cache = ConcurrentLRUCache::new(100);
+    for i in 0..100 {
+        cache.insert(i, i);
+    }
+    std::thread::scope(|s| {
+        for t in 0..4 {
+            let cache = &cache;
+            s.spawn(move || {
+                for i in 0..1000 {
+                    cache.get(&(i % 50));
+                    if i % 10 == 0 {
+                        cache.insert(100 + t * 100 + i / 10, 0);
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(100, cache.len());
+    assert_eq!(4000, cache.stats().hits + cache.stats().misses);
+    // The keys that were read are more recent than the keys that were not.
+    assert!((0..50).filter(|i| cache.contains_key(i)).count() > 25);
+}
@@ -0,0 +1,132 @@
+//! A view into a single entry of an LRUCache, which may either be vacant or occupied.
+
+use DefaultHashBuilder;
+use core::hash::{BuildHasher, Hash};
+
+use super::{EvictionReason, LRUCache};
+
+pub enum Entry<'a, K: 'a + Eq + Hash, V: 'a, S: 'a = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+pub struct OccupiedEntry<'a, K: 'a + Eq + Hash, V: 'a, S: 'a = DefaultHashBuilder> {
+    cache: &'a mut LRUCache<K, V, S>,
+    // index of the entry in the slab
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K: 'a + Eq + Hash, V: 'a, S: 'a = DefaultHashBuilder> {
+    cache: &'a mut LRUCache<K, V, S>,
+    // hash of the key, computed once when the entry was created
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref e) => e.key(),
+            Entry::Vacant(ref e) => e.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+        where F: FnOnce() -> V
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    pub fn and_modify<F>(self, f: F) -> Self
+        where F: FnOnce(&mut V)
+    {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+    where K: Eq + Hash,
+          V: Default,
+          S: BuildHasher
+{
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    pub(super) fn new(cache: &'a mut LRUCache<K, V, S>, index: usize) -> Self {
+        OccupiedEntry { cache, index }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.cache.entries.get(self.index).key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.cache.entries.get(self.index).val
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.cache.entries.get_mut(self.index).val
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.cache.entries.get_mut(self.index).val
+    }
+
+    pub fn insert(&mut self, val: V) -> V {
+        self.cache.replace_value(self.index, val, None)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.cache.take_entry(self.index, EvictionReason::ExplicitlyRemoved)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    pub(super) fn new(cache: &'a mut LRUCache<K, V, S>, hash: u64, key: K) -> Self {
+        VacantEntry { cache, hash, key }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, val: V) -> &'a mut V {
+        &mut self.cache.insert_vacant(self.hash, self.key, val, None).0.val
+    }
+}
@@ -0,0 +1,244 @@
+//! Time-based expiration of cache entries.
+//! Without the standard library there is no time source
+//! and entries never expire.
+
+#[cfg(feature = "std")]
+use super::wheel::TimerWheel;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+#[cfg(feature = "std")]
+use time::{Clock, SystemClock};
+
+// The expiration settings of a cache.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "std"), derive(Default))]
+pub(super) struct Expiry {
+    // entries expire this long after their value is written
+    #[cfg(feature = "std")]
+    pub(super) ttl: Option<Duration>,
+    // entries expire this long after they are last accessed
+    #[cfg(feature = "std")]
+    pub(super) tti: Option<Duration>,
+    // set once any entry has been written with its own time-to-live
+    #[cfg(feature = "std")]
+    pub(super) overrides: bool,
+    // fraction of the time-to-live, at the end of the time-to-live,
+    // during which an access refreshes the entry
+    #[cfg(feature = "std")]
+    pub(super) refresh_window: Option<f64>,
+    // source of the current time
+    #[cfg(feature = "std")]
+    pub(super) clock: Arc<dyn Clock>,
+    // time from which the ticks of the timer wheel are counted
+    #[cfg(feature = "std")]
+    origin: Instant,
+    // deadlines of the entries, identified by the hash of their key
+    #[cfg(feature = "std")]
+    wheel: TimerWheel<u64>,
+}
+
+// The times after which an entry is treated as absent.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Deadline {
+    // time-to-live of the entry itself, which overrides the cache's
+    #[cfg(feature = "std")]
+    ttl: Option<Duration>,
+    #[cfg(feature = "std")]
+    write: Option<Instant>,
+    #[cfg(feature = "std")]
+    idle: Option<Instant>,
+    // time after which an access refreshes the value
+    #[cfg(feature = "std")]
+    refresh: Option<Instant>,
+    // tick of the timer wheel at which the entry is checked
+    #[cfg(feature = "std")]
+    scheduled: Option<u64>,
+}
+
+// A tick of the timer wheel is 2^20 nanoseconds, about one millisecond.
+#[cfg(feature = "std")]
+const TICK_BITS: u32 = 20;
+
+#[cfg(feature = "std")]
+impl Default for Expiry {
+    fn default() -> Expiry {
+        Expiry {
+            ttl: None,
+            tti: None,
+            overrides: false,
+            refresh_window: None,
+            clock: Arc::new(SystemClock),
+            origin: Instant::now(),
+            wheel: TimerWheel::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Expiry {
+    pub(super) fn is_enabled(&self) -> bool {
+        self.ttl.is_some() || self.tti.is_some() || self.overrides
+    }
+
+    // Set the deadlines of a value that is written now.
+    // The time-to-live of the entry overrides the time-to-live of the cache.
+    // A deadline that is too far in the future to be represented is never reached.
+    pub(super) fn on_write(&mut self, hash: u64, ttl: Option<Duration>, deadline: &mut Deadline) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = self.clock.now();
+        deadline.ttl = ttl;
+        let ttl = ttl.or(self.ttl);
+        deadline.write = ttl.and_then(|ttl| now.checked_add(ttl));
+        deadline.idle = self.tti.and_then(|tti| now.checked_add(tti));
+        deadline.refresh = match (ttl, self.refresh_window) {
+            (Some(ttl), Some(window)) => now.checked_add(ttl.mul_f64(1.0 - window)),
+            _ => None,
+        };
+        self.schedule(hash, deadline);
+    }
+
+    // An access postpones the idle deadline of an entry.
+    pub(super) fn on_access(&mut self, hash: u64, deadline: &mut Deadline) {
+        if let Some(tti) = self.tti {
+            deadline.idle = self.clock.now().checked_add(tti);
+            self.schedule(hash, deadline);
+        }
+    }
+
+    // Schedule an entry that was due but has not expired
+    // because its deadlines were postponed.
+    pub(super) fn reschedule(&mut self, hash: u64, deadline: &mut Deadline) {
+        deadline.scheduled = None;
+        self.schedule(hash, deadline);
+    }
+
+    // An entry is scheduled once at its earliest deadline. Later
+    // deadlines are picked up when the scheduled tick is due.
+    fn schedule(&mut self, hash: u64, deadline: &mut Deadline) {
+        if let Some(t) = deadline.earliest() {
+            let tick = self.tick(t);
+            if deadline.scheduled.is_none_or(|scheduled| tick < scheduled) {
+                self.wheel.insert(tick, hash);
+                deadline.scheduled = Some(tick);
+            }
+        }
+    }
+
+    // Remove the scheduled (tick, hash) pairs that are due.
+    pub(super) fn advance(&mut self) -> Vec<(u64, u64)> {
+        let now = self.tick(self.clock.now());
+        self.wheel.advance(now)
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.wheel.clear();
+    }
+
+    pub(super) fn heap_size(&self) -> usize {
+        self.wheel.heap_size()
+    }
+
+    // The ticks of the timer wheel are counted from the time of the new
+    // clock. The caller must schedule the deadlines of the entries again.
+    pub(super) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.origin = clock.now();
+        self.clock = clock;
+        self.wheel.clear();
+    }
+
+    pub(super) fn is_expired(&self, deadline: &Deadline) -> bool {
+        match deadline.earliest() {
+            Some(t) => t <= self.clock.now(),
+            None => false,
+        }
+    }
+
+    // An entry is refreshed at most once for each write. Returns
+    // true if the entry is within its refresh window.
+    pub(super) fn take_refresh(&self, deadline: &mut Deadline) -> bool {
+        match deadline.refresh {
+            Some(t) if t <= self.clock.now() => {
+                deadline.refresh = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Ticks are rounded down so that an entry is never checked
+    // later than its deadline. An early check reschedules the entry.
+    fn tick(&self, t: Instant) -> u64 {
+        (t.saturating_duration_since(self.origin).as_nanos() >> TICK_BITS) as u64
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deadline {
+    pub(super) fn is_scheduled_at(&self, tick: u64) -> bool {
+        self.scheduled == Some(tick)
+    }
+
+    // The time-to-live with which the entry was written, if it has its own.
+    pub(super) fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    fn earliest(&self) -> Option<Instant> {
+        match (self.write, self.idle) {
+            (Some(write), Some(idle)) => Some(write.min(idle)),
+            (write, idle) => write.or(idle),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Expiry {
+    pub(super) fn is_enabled(&self) -> bool {
+        false
+    }
+
+    pub(super) fn on_write(&mut self,
+                           _hash: u64,
+                           _ttl: Option<Duration>,
+                           _deadline: &mut Deadline) {
+    }
+
+    pub(super) fn on_access(&mut self, _hash: u64, _deadline: &mut Deadline) {}
+
+    pub(super) fn reschedule(&mut self, _hash: u64, _deadline: &mut Deadline) {}
+
+    pub(super) fn advance(&mut self) -> Vec<(u64, u64)> {
+        Vec::new()
+    }
+
+    pub(super) fn clear(&mut self) {}
+
+    pub(super) fn heap_size(&self) -> usize {
+        0
+    }
+
+    pub(super) fn is_expired(&self, _deadline: &Deadline) -> bool {
+        false
+    }
+
+    pub(super) fn take_refresh(&self, _deadline: &mut Deadline) -> bool {
+        false
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Deadline {
+    pub(super) fn is_scheduled_at(&self, _tick: u64) -> bool {
+        false
+    }
+
+    pub(super) fn ttl(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,294 @@
+//! Iterators over the entries of an LRUCache.
+
+use DefaultHashBuilder;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use list;
+
+use super::{CacheEntry, LRUCache};
+
+// Iterates from the most-recently used entry to the least-recently used entry.
+pub struct Iter<'a, K: 'a + Eq + Hash, V: 'a, S: 'a = DefaultHashBuilder> {
+    entries: list::Iter<'a, CacheEntry<K, V>>,
+    marker: PhantomData<&'a S>,
+}
+
+impl<'a, K, V, S> Iter<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    pub(super) fn new(cache: &'a LRUCache<K, V, S>) -> Self {
+        Iter {
+            entries: cache.entries.iter(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, S> Iterator for Iter<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|(_, e)| (&e.key, &e.val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+// Iterating from the back visits the least-recently used entry first.
+impl<'a, K, V, S> DoubleEndedIterator for Iter<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|(_, e)| (&e.key, &e.val))
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Iter<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+}
+
+impl<'a, K, V, S> IntoIterator for &'a LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// Iterates over the entries in arbitrary order.
+// Recency is not updated by the iteration.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    entries: list::IterMut<'a, CacheEntry<K, V>>,
+}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    pub(super) fn new<S>(cache: &'a mut LRUCache<K, V, S>) -> Self
+        where K: Eq + Hash
+    {
+        IterMut { entries: cache.entries.iter_mut() }
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|(_, e)| (&e.key, &mut e.val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a mut LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// Iterates over the keys from the most-recently used entry
+// to the least-recently used entry.
+pub struct Keys<'a, K: 'a + Eq + Hash, V: 'a, S: 'a = DefaultHashBuilder> {
+    pub(super) inner: Iter<'a, K, V, S>,
+}
+
+impl<'a, K, V, S> Iterator for Keys<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, S> DoubleEndedIterator for Keys<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Keys<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+}
+
+// Iterates over the values from the most-recently used entry
+// to the least-recently used entry.
+pub struct Values<'a, K: 'a + Eq + Hash, V: 'a, S: 'a = DefaultHashBuilder> {
+    pub(super) inner: Iter<'a, K, V, S>,
+}
+
+impl<'a, K, V, S> Iterator for Values<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, S> DoubleEndedIterator for Values<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Values<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+}
+
+// Iterates over the values in arbitrary order.
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    pub(super) inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+// Consumes the cache from the least-recently used entry
+// to the most-recently used entry.
+pub struct IntoIter<K: Eq + Hash, V, S = DefaultHashBuilder> {
+    cache: LRUCache<K, V, S>,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cache.pop_lru()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cache.len(), Some(self.cache.len()))
+    }
+}
+
+impl<K, V, S> DoubleEndedIterator for IntoIter<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cache.pop_mru()
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for IntoIter<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+}
+
+impl<K, V, S> IntoIterator for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { cache: self }
+    }
+}
+
+// Removes the entries from the least-recently used entry
+// to the most-recently used entry. Any entries that are not
+// yielded are removed when the iterator is dropped.
+pub struct Drain<'a, K: 'a + Eq + Hash, V: 'a, S: 'a + BuildHasher = DefaultHashBuilder> {
+    pub(super) cache: &'a mut LRUCache<K, V, S>,
+}
+
+impl<'a, K, V, S> Iterator for Drain<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cache.pop_lru()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cache.len(), Some(self.cache.len()))
+    }
+}
+
+impl<'a, K, V, S> DoubleEndedIterator for Drain<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cache.pop_mru()
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Drain<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+}
+
+impl<'a, K, V, S> Drop for Drain<'a, K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn drop(&mut self) {
+        self.cache.clear();
+    }
+}
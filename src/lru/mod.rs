@@ -1,159 +1,2675 @@
 //! The lru module implements a [least-recently used](
 //! https://en.wikipedia.org/wiki/Cache_replacement_policies#Least_Recently_Used_.28LRU.29) cache.
 
-use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
-use std::hash::Hash;
-use std::rc::Rc;
+use DefaultHashBuilder;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter::{FromIterator, Rev};
+use core::mem;
+use core::num::NonZeroUsize;
+use core::ops::{Index, IndexMut};
+use core::time::Duration;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use self::expiry::{Deadline, Expiry};
+use self::segments::Segments;
+use memsize::MemSize;
+use stats::{Stats, StatsSnapshot};
+#[cfg(feature = "std")]
+use time::Clock;
 
-struct CacheEntry<V> {
+mod array;
+mod builder;
+#[cfg(feature = "std")]
+mod concurrent;
+mod entry;
+mod expiry;
+mod iter;
+mod sampled;
+mod segments;
+#[cfg(feature = "std")]
+mod sharded;
+#[cfg(feature = "std")]
+mod sync;
+#[cfg(feature = "std")]
+mod wheel;
+#[cfg(feature = "serde")]
+mod serde;
+
+pub use self::array::{ArrayIter, ArrayLRU};
+pub use self::builder::LRUCacheBuilder;
+#[cfg(feature = "std")]
+pub use self::concurrent::ConcurrentLRUCache;
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use self::iter::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+pub use self::sampled::{DEFAULT_SAMPLES, SampledLRUCache};
+#[cfg(feature = "std")]
+pub use self::sharded::ShardedLRUCache;
+#[cfg(feature = "std")]
+pub use self::sync::SyncLRUCache;
+
+#[derive(Clone)]
+struct CacheEntry<K, V> {
+    // cache key, stored only in the entry
+    key: K,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
     // cache value
     val: V,
     // clock instant when entry was most recently accessed
-    instant: u64,
+    last_access: u64,
+    // pinned entries are never chosen for eviction
+    pinned: bool,
+    // weight of the entry when the value was stored
+    weight: usize,
+    // times after which the entry is treated as absent
+    deadline: Deadline,
+    // clock instant when the entry was inserted
+    inserted: u64,
+    // number of times the entry has been promoted
+    accesses: u64,
+    // set once the entry leaves the probationary segment
+    protected: bool,
+}
+
+impl<K, V> CacheEntry<K, V> {
+
+    fn matches<Q: ?Sized + Eq>(&self, key: &Q) -> bool
+        where K: Borrow<Q>
+    {
+        <K as Borrow<Q>>::borrow(&self.key) == key
+    }
+}
+
+// capacity of a cache that is created by LRUCache::default()
+pub const DEFAULT_CAPACITY: usize = 128;
+
+// The values displaced from the cache by an insertion.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InsertResult<K, V> {
+    // previous value of the key, if the key was already cached
+    pub replaced: Option<V>,
+    // least-recently used (key, value) pair, if the cache was full.
+    // None if the pair was handed to the eviction callback.
+    // When a weigher or a low watermark makes several evictions necessary,
+    // the pairs after the first are only seen by the callback and the listener.
+    pub evicted: Option<(K, V)>,
+}
+
+// A value that is read by get_stale_ok().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachedValue<T> {
+    // the entry has not expired
+    Fresh(T),
+    // the entry has expired and should be refreshed
+    Stale(T),
+}
+
+impl<T> CachedValue<T> {
+    pub fn is_stale(&self) -> bool {
+        match *self {
+            CachedValue::Fresh(_) => false,
+            CachedValue::Stale(_) => true,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        match self {
+            CachedValue::Fresh(val) | CachedValue::Stale(val) => val,
+        }
+    }
+}
+
+// The outcome of a lookup by get_result().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheResult<T> {
+    // the key is cached with a value
+    Hit(T),
+    // the key is cached as known to be absent
+    NegativeHit,
+    // the key is not cached
+    Miss,
+}
+
+// Information about a cached entry. The times are instants
+// of the logical clock of the cache, which advances on each
+// insertion and each promotion of an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryMeta {
+    // instant when the entry was inserted
+    pub inserted: u64,
+    // instant when the entry was most recently accessed
+    pub last_access: u64,
+    // number of times the entry has been promoted since it was inserted
+    pub accesses: u64,
+    // number of entries that are more recently used than the entry
+    pub recency_rank: usize,
+    pub pinned: bool,
+    pub weight: usize,
+    // false while the entry is in the probationary segment
+    pub protected: bool,
+}
+
+// called with the (key, value) pairs that are evicted by capacity
+type EvictionCallback<K, V> = Box<dyn FnMut(K, V) + Send + Sync>;
+
+// The cause of an entry leaving the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvictionReason {
+    // evicted to make room for a new entry or a smaller capacity
+    CapacityEvicted,
+    // the value was overwritten by a new value for the same key
+    Replaced,
+    // the entry outlived its expiration time
+    Expired,
+    // removed by a call such as remove(), pop_lru() or clear()
+    ExplicitlyRemoved,
+}
+
+// called with every (key, value) pair before it leaves the cache
+type Listener<K, V> = Box<dyn FnMut(&K, &V, EvictionReason) + Send + Sync>;
+
+// reloads the value of a key that is within its refresh window
+type Loader<K, V> = Box<dyn FnMut(&K) -> Option<V> + Send + Sync>;
+
+// computes the weight of a (key, value) pair. Shared with clones of the cache.
+type Weigher<K, V> = Arc<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+// Every entry has a weight of one unless there is a weigher.
+fn weigh<K, V>(weigher: &Option<Weigher<K, V>>, key: &K, val: &V) -> usize {
+    match *weigher {
+        Some(ref f) => f(key, val),
+        None => 1,
+    }
+}
+
+// Move an entry that was located to the most-recently used position
+// of its segment and record the access at the current instant of the
+// clock. The access also postpones the idle deadline of the entry.
+fn reorder<'a, K, V>(clock: &mut u64,
+                     entries: &'a mut List<CacheEntry<K, V>>,
+                     segments: &mut Segments,
+                     expiry: &mut Expiry,
+                     index: usize)
+                     -> &'a mut CacheEntry<K, V> {
+    segments.move_to_front(entries, index);
+    let e = entries.get_mut(index);
+    e.last_access = *clock;
+    *clock = clock.wrapping_add(1);
+    e.accesses += 1;
+    expiry.on_access(e.hash, &mut e.deadline);
+    e
+}
+
+// Approximate number of bytes used by a cached (key, value) pair.
+// Includes the node of the entry in the slab with its two links
+// and the index of the entry in the table.
+fn entry_mem_size<K: MemSize, V: MemSize>(key: &K, val: &V) -> usize {
+    mem::size_of::<CacheEntry<K, V>>() + 3 * mem::size_of::<usize>() + key.heap_size() +
+    val.heap_size()
+}
+
+fn notify<K, V>(listener: &mut Option<Listener<K, V>>,
+                key: &K,
+                val: &V,
+                reason: EvictionReason) {
+    if let Some(ref mut f) = *listener {
+        f(key, val, reason);
+    }
 }
 
-pub struct LRUCache<K: Eq + Hash, V> {
-    // maximum number of elements stored in the cache
-    capacity: usize,
-    // logical clock that is incremented on each operation
+pub struct LRUCache<K: Eq + Hash, V, S = DefaultHashBuilder> {
+    // maximum total weight of the elements stored in the cache.
+    // An unbounded cache never evicts entries on insertion.
+    capacity: Option<usize>,
+    // total weight that eviction reduces the cache to once the
+    // capacity is exceeded. None to evict only what is necessary.
+    low_watermark: Option<usize>,
+    // total weight of the elements stored in the cache
+    weight: usize,
+    // maximum total weight of the protected entries. None if new
+    // entries are not admitted to a probationary segment.
+    protected_capacity: Option<usize>,
+    // total weight of the protected entries
+    protected_weight: usize,
+    // logical clock that is incremented on each insertion and promotion.
+    // Only used to describe the entries, not to order them.
     clock: u64,
-    // unordered map that stores (key, value) pairs
-    data: HashMap<Rc<K>, CacheEntry<V>>,
-    // ordered map sorted by clock instants. Used by eviction algorithm
-    order: BTreeMap<u64, Rc<K>>,
+    // hashes keys for lookups into the table
+    hash_builder: S,
+    // unordered table of the indices of the entries in the slab
+    table: HashTable<usize>,
+    // slab that stores the entries, linked from the most-recently used
+    // entry at the front to the least-recently used entry at the back.
+    // Used by eviction algorithm
+    entries: List<CacheEntry<K, V>>,
+    // the protected and probationary segments of the list
+    segments: Segments,
+    // optional callback that takes ownership of evicted entries
+    on_evict: Option<EvictionCallback<K, V>>,
+    // optional listener that observes every entry leaving the cache
+    listener: Option<Listener<K, V>>,
+    // counters for lookups, insertions and evictions
+    stats: Stats,
+    // optional function that computes the weight of each entry
+    weigher: Option<Weigher<K, V>>,
+    // time-to-live and time-to-idle of the entries
+    expiry: Expiry,
+    // optional loader that refreshes entries before they expire
+    loader: Option<Loader<K, V>>,
+    // optional cache of keys that are known to be absent
+    negative: Option<Box<LRUCache<K, (), S>>>,
 }
 
 impl<K, V> LRUCache<K, V>
     where K: Eq + Hash
 {
+    // Panics if the capacity is zero.
     pub fn new(capacity: usize) -> LRUCache<K, V> {
+        LRUCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<LRUCache<K, V>, CapacityError> {
+        LRUCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn with_nonzero_capacity(capacity: NonZeroUsize) -> LRUCache<K, V> {
+        LRUCache::new(capacity.get())
+    }
+
+    pub fn unbounded() -> LRUCache<K, V> {
+        LRUCache::unbounded_with_hasher(DefaultHashBuilder::default())
+    }
+
+    pub fn builder() -> LRUCacheBuilder<K, V> {
+        LRUCacheBuilder::new()
+    }
+
+    // The capacity is the approximate number of bytes used by the entries.
+    // Panics if the capacity is zero.
+    pub fn with_memory_limit(bytes: usize) -> LRUCache<K, V>
+        where K: MemSize + 'static,
+              V: MemSize + 'static
+    {
+        let mut cache = LRUCache::new(bytes);
+        cache.set_weigher(entry_mem_size);
+        cache
+    }
+}
+
+impl<K, V, S> LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> LRUCache<K, V, S> {
+        match LRUCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<LRUCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(LRUCache {
+            capacity: Some(capacity),
+            low_watermark: None,
+            weight: 0,
+            protected_capacity: None,
+            protected_weight: 0,
+            clock: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            segments: Segments::default(),
+            on_evict: None,
+            listener: None,
+            stats: Stats::default(),
+            weigher: None,
+            expiry: Expiry::default(),
+            loader: None,
+            negative: None,
+        })
+    }
+
+    pub fn unbounded_with_hasher(hash_builder: S) -> LRUCache<K, V, S> {
         LRUCache {
-            capacity: capacity,
+            capacity: None,
+            low_watermark: None,
+            weight: 0,
+            protected_capacity: None,
+            protected_weight: 0,
             clock: 0,
-            data: HashMap::with_capacity(capacity),
-            order: BTreeMap::new(),
+            hash_builder,
+            table: HashTable::new(),
+            entries: List::new(),
+            segments: Segments::default(),
+            on_evict: None,
+            listener: None,
+            stats: Stats::default(),
+            weigher: None,
+            expiry: Expiry::default(),
+            loader: None,
+            negative: None,
         }
     }
 
-    pub fn get(&mut self, key: K) -> Option<&V> {
-        let now = self.clock;
-        let key = Rc::new(key);
-        let prev = match self.data.entry(key.clone()) {
-            // If the (key, value) pair is located,
-            // then find the logical time instant associated
-            // with that key. The instant must be set to now.
-            Entry::Occupied(mut e) => {
-                self.clock += 1;
-                let e = e.get_mut();
-                let prev = e.instant;
-                e.instant = now;
-                Some(prev)
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    pub fn get_cloned<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        self.get(key).cloned()
+    }
+
+    // Keys that are known to be absent are reported separately
+    // from keys that are not cached.
+    pub fn get_result<Q>(&mut self, key: &Q) -> CacheResult<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        if let Some(ref mut negative) = self.negative {
+            if negative.touch(key) {
+                self.stats.negative_hits += 1;
+                return CacheResult::NegativeHit;
+            }
+        }
+        match self.lookup(key) {
+            Some(e) => CacheResult::Hit(&e.val),
+            None => CacheResult::Miss,
+        }
+    }
+
+    // Record that a key is known to be absent, removing any value of the key.
+    // A later insert() of the key replaces the negative entry.
+    // Has no effect unless negative caching is enabled.
+    pub fn insert_negative(&mut self, key: K) {
+        if self.negative.is_none() {
+            return;
+        }
+        self.remove(&key);
+        if let Some(ref mut negative) = self.negative {
+            negative.insert(key, ());
+        }
+    }
+
+    // An expired entry that has not yet been reclaimed is returned
+    // as stale instead of being treated as absent. A stale entry is not
+    // promoted so that it remains expired until it is written again.
+    // Stale lookups are counted apart from hits and misses.
+    pub fn get_stale_ok<Q>(&mut self, key: &Q) -> Option<CachedValue<&V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        if let Some(index) = self.index_of(hash, key) {
+            if self.expiry.is_expired(&self.entries.get(index).deadline) {
+                self.stats.stale_hits += 1;
+                return Some(CachedValue::Stale(&self.entries.get(index).val));
             }
-            _ => None,
+        }
+        self.lookup(key).map(|e| CachedValue::Fresh(&e.val))
+    }
+
+    pub fn get_key_value<Q>(&mut self, key: &Q) -> Option<(&K, &V)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| (&e.key, &e.val))
+    }
+
+    pub fn touch<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        // Promote the entry without borrowing the value.
+        let hash = self.hash_builder.hash_one(key);
+        self.promote(hash, key).is_some()
+    }
+
+    pub fn demote<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        // Move the entry to the least-recently used position. A protected
+        // entry returns to the probationary segment, so that it is the
+        // next entry to be evicted.
+        let hash = self.hash_builder.hash_one(key);
+        let index = match self.index_of(hash, key) {
+            Some(index) => index,
+            None => return false,
         };
-        match prev {
-            Some(t) => {
-                // If the (key, value) pair is located,
-                // then delete the association with the old instant
-                // and create an association to now.
-                let k = self.order.remove(&t);
-                self.order.insert(now, k.unwrap());
-                self.data.get(key.as_ref()).as_ref().map(|x| &x.val)
+        self.segments.unlink(&self.entries, index);
+        let e = self.entries.get_mut(index);
+        if e.protected {
+            e.protected = false;
+            self.protected_weight -= e.weight;
+        }
+        self.segments.link_back(&mut self.entries, index);
+        true
+    }
+
+    pub fn pin<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.set_pinned(key, true)
+    }
+
+    pub fn unpin<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.set_pinned(key, false)
+    }
+
+    pub fn is_pinned<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).is_some_and(|index| self.entries.get(index).pinned)
+    }
+
+    // A pinned entry keeps its place in the recency order
+    // and is only passed over by eviction.
+    fn set_pinned<Q>(&mut self, key: &Q, pinned: bool) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        match self.index_of(hash, key) {
+            Some(index) => {
+                self.entries.get_mut(index).pinned = pinned;
+                true
             }
-            None => None,
+            None => false,
         }
     }
 
-    pub fn insert(&mut self, key: K, val: V) {
-        let now = self.clock;
-        self.clock += 1;
-        let size = self.data.len();
-        let key = Rc::new(key);
-        let evict = match self.data.entry(key.clone()) {
-            // If the (key, value) pair is located,
-            // then replace the previous value,
-            // and update the logical time association
-            // with the pair.
-            Entry::Occupied(mut e) => {
-                let e = e.get_mut();
-                let prev = e.instant;
-                e.instant = now;
-                e.val = val;
-                self.order.remove(&prev);
-                self.order.insert(now, key.clone());
-                None
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        // Read the value without advancing the clock
+        // or updating the recency of the entry.
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key)
+            .map(|index| self.entries.get(index))
+            .filter(|e| !self.expiry.is_expired(&e.deadline))
+            .map(|e| &e.val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        self.insert_with_expiry(key, val, None)
+    }
+
+    // The time-to-live of the entry overrides the time-to-live of the cache.
+    // A later insert() of the key restores the time-to-live of the cache.
+    #[cfg(feature = "std")]
+    pub fn insert_with_ttl(&mut self, key: K, val: V, ttl: Duration) -> InsertResult<K, V> {
+        self.expiry.overrides = true;
+        self.insert_with_expiry(key, val, Some(ttl))
+    }
+
+    fn insert_with_expiry(&mut self, key: K, val: V, ttl: Option<Duration>) -> InsertResult<K, V> {
+        if let Some(ref mut negative) = self.negative {
+            negative.remove(&key);
+        }
+        let hash = self.hash_builder.hash_one(&key);
+        // If the (key, value) pair is located,
+        // then replace the previous value,
+        // and move the pair to the front of the slab.
+        // An expired entry is reclaimed rather than replaced.
+        if let Some(index) = self.live_index(hash, &key) {
+            self.access(index);
+            let prev = self.replace_value(index, val, ttl);
+            // A heavier value may no longer fit in the cache.
+            let evicted = self.evict_to_fit(0);
+            return InsertResult { replaced: Some(prev), evicted };
+        }
+        // If the (key, value) pair is not located,
+        // then insert the new association.
+        let (_, evicted) = self.insert_vacant(hash, key, val, ttl);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn insert_many<I>(&mut self, pairs: I)
+        where I: IntoIterator<Item = (K, V)>
+    {
+        // Grow the table and the slab once for the whole batch
+        // instead of once per insertion.
+        let pairs = pairs.into_iter();
+        let additional = match self.capacity {
+            Some(capacity) => cmp::min(pairs.size_hint().0, capacity.saturating_sub(self.len())),
+            None => pairs.size_hint().0,
+        };
+        self.reserve(additional);
+        for (k, v) in pairs {
+            self.insert(k, v);
+        }
+    }
+
+    pub fn get_many<Q>(&mut self, keys: &[Q]) -> Vec<Option<&V>>
+        where K: Borrow<Q>,
+              Q: Hash + Eq
+    {
+        // Each key is hashed once. The entries are promoted
+        // in the order of the keys and then collected.
+        let hashes: Vec<u64> = keys.iter().map(|k| self.hash_builder.hash_one(k)).collect();
+        for (key, &hash) in keys.iter().zip(&hashes) {
+            let hit = self.promote(hash, key).is_some();
+            self.stats.record_lookup(hit);
+        }
+        let cache = &*self;
+        keys.iter()
+            .zip(hashes)
+            .map(|(key, hash)| cache.index_of(hash, key).map(|index| &cache.entries.get(index).val))
+            .collect()
+    }
+
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &V
+        where F: FnOnce() -> V
+    {
+        self.entry(key).or_insert_with(f)
+    }
+
+    pub fn try_get_or_insert_with<F, E>(&mut self, key: K, f: F) -> Result<&V, E>
+        where F: FnOnce() -> Result<V, E>
+    {
+        // Nothing is inserted if the loader fails.
+        match self.entry(key) {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => Ok(e.insert(f()?)),
+        }
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash_builder.hash_one(&key);
+        // An occupied entry is promoted when it is located
+        // so that the entry counts as an access.
+        let index = self.promote(hash, &key);
+        self.stats.record_lookup(index.is_some());
+        match index {
+            Some(index) => Entry::Occupied(OccupiedEntry::new(self, index)),
+            None => Entry::Vacant(VacantEntry::new(self, hash, key)),
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        // Delete the index of the (key, value) pair from the table
+        // and the pair from the slab.
+        if let Some(ref mut negative) = self.negative {
+            negative.remove(key);
+        }
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.live_index(hash, key)?;
+        Some(self.take_entry(index, EvictionReason::ExplicitlyRemoved).1)
+    }
+
+    // The owned value of a removed entry, so that it can be reused.
+    // Same as remove().
+    pub fn take<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.remove(key)
+    }
+
+    pub fn swap<Q>(&mut self, key: &Q, val: V) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        // Replace the value in place, without updating the recency
+        // or the deadlines of the entry. The new value is dropped
+        // if the key is not located. A heavier value may evict the
+        // oldest entries, which can include the swapped entry itself.
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.live_index(hash, key)?;
+        let prev = self.swap_value(index, val);
+        drop(self.evict_to_fit(0));
+        Some(prev)
+    }
+
+    // Replace the value of the entry with the given index and renew its
+    // deadlines. The recency of the entry is not updated.
+    fn replace_value(&mut self, index: usize, val: V, ttl: Option<Duration>) -> V {
+        let e = self.entries.get_mut(index);
+        self.expiry.on_write(e.hash, ttl, &mut e.deadline);
+        self.swap_value(index, val)
+    }
+
+    // Replace the value of the entry with the given index
+    // and keep its recency and its deadlines.
+    fn swap_value(&mut self, index: usize, val: V) -> V {
+        let e = self.entries.get_mut(index);
+        let prev = mem::replace(&mut e.val, val);
+        let weight = weigh(&self.weigher, &e.key, &e.val);
+        let prev_weight = mem::replace(&mut e.weight, weight);
+        self.weight = self.weight - prev_weight + weight;
+        if e.protected {
+            self.protected_weight = self.protected_weight - prev_weight + weight;
+        }
+        notify(&mut self.listener, &e.key, &prev, EvictionReason::Replaced);
+        self.stats.replacements += 1;
+        prev
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+        where F: FnMut(&K, &mut V) -> bool
+    {
+        // Entries that are rejected by the predicate
+        // are removed from both the table and the slab.
+        let entries = &mut self.entries;
+        let segments = &mut self.segments;
+        let listener = &mut self.listener;
+        let weight = &mut self.weight;
+        let protected_weight = &mut self.protected_weight;
+        self.table.retain(|&mut index| {
+            let e = entries.get_mut(index);
+            let keep = f(&e.key, &mut e.val);
+            if !keep {
+                let e = segments.remove(entries, index);
+                *weight -= e.weight;
+                if e.protected {
+                    *protected_weight -= e.weight;
+                }
+                notify(listener, &e.key, &e.val, EvictionReason::ExplicitlyRemoved);
             }
-            // If the (key, value) pair is not located,
-            // then insert the new association.
-            Entry::Vacant(e) => {
-                let evict = {
-                    if size == self.capacity {
-                        // Evict the oldest entry from the clock instant map
-                        let oldest = self.order.keys().cloned().next().unwrap();
-                        Some(self.order.remove(&oldest).unwrap())
-                    } else {
-                        None
-                    }
-                };
-                let entry = CacheEntry {
-                    val: val,
-                    instant: now,
-                };
-                e.insert(entry);
-                self.order.insert(now, key.clone());
-                evict
+            keep
+        });
+    }
+
+    // Expired entries are reclaimed lazily when their key is accessed.
+    // The sweep reclaims every expired entry and returns the number
+    // of entries that were removed.
+    pub fn purge_expired(&mut self) -> usize {
+        if !self.expiry.is_enabled() {
+            return 0;
+        }
+        // The timer wheel yields the entries whose earliest deadline
+        // has passed. Entries that were removed in the meantime are
+        // skipped, and entries whose deadlines were postponed are
+        // scheduled again.
+        let mut purged = 0;
+        for (tick, hash) in self.expiry.advance() {
+            let entries = &mut self.entries;
+            let index = match self.table.find(hash, |&i| entries.get(i).deadline.is_scheduled_at(tick)) {
+                Some(&index) => index,
+                None => continue,
+            };
+            let e = entries.get_mut(index);
+            if !self.expiry.is_expired(&e.deadline) {
+                self.expiry.reschedule(hash, &mut e.deadline);
+                continue;
+            }
+            drop(self.take_entry(index, EvictionReason::Expired));
+            purged += 1;
+        }
+        purged
+    }
+
+    // Panics if the capacity is zero.
+    pub fn resize(&mut self, capacity: usize) {
+        if capacity == 0 {
+            panic!("{}", CapacityError);
+        }
+        // If the cache is shrinking then evict the
+        // oldest entries until the remaining entries fit.
+        self.capacity = Some(capacity);
+        self.low_watermark = self.low_watermark.map(|low| cmp::min(low, capacity));
+        drop(self.evict_to_fit(0));
+    }
+
+    // The high watermark becomes the capacity. Once an insertion would
+    // exceed the high watermark, the oldest entries are evicted in one
+    // pass until the entries and the new entry fit within the low
+    // watermark, so that the following insertions do not evict.
+    // Panics if the high watermark is zero or below the low watermark.
+    pub fn set_watermarks(&mut self, high: usize, low: usize) {
+        assert!(low <= high, "low watermark must not exceed the high watermark");
+        self.resize(high);
+        self.low_watermark = Some(low);
+    }
+
+    pub fn low_watermark(&self) -> Option<usize> {
+        self.low_watermark
+    }
+
+    // New entries start in a probationary segment and are protected
+    // once they are accessed again. Eviction chooses the least-recently
+    // used probationary entry before any protected entry, so a scan of
+    // keys that are read once does not displace the entries that are
+    // read repeatedly. The protected entries weigh at most the given
+    // capacity, typically most of the capacity of the cache, and the
+    // least-recently used protected entries return to the probationary
+    // segment to make room. The protected entries precede the
+    // probationary entries in the recency order, so that eviction
+    // and demotion take the back of a segment in constant time.
+    pub fn set_probation(&mut self, protected_capacity: usize) {
+        self.protected_capacity = Some(protected_capacity);
+        self.demote_protected(usize::MAX);
+    }
+
+    pub fn protected_capacity(&self) -> Option<usize> {
+        self.protected_capacity
+    }
+
+    // The capacity becomes the maximum total weight of the entries.
+    // The weights of the cached entries are recomputed and the
+    // oldest entries are evicted until the remaining entries fit.
+    // Values that are modified in place are not weighed again.
+    pub fn set_weigher<F>(&mut self, f: F)
+        where F: Fn(&K, &V) -> usize + Send + Sync + 'static
+    {
+        let weigher: Weigher<K, V> = Arc::new(f);
+        let mut total = 0;
+        let mut protected = 0;
+        for (_, e) in self.entries.iter_mut() {
+            e.weight = weigher(&e.key, &e.val);
+            total += e.weight;
+            if e.protected {
+                protected += e.weight;
+            }
+        }
+        self.weight = total;
+        self.protected_weight = protected;
+        self.weigher = Some(weigher);
+        drop(self.evict_to_fit(0));
+    }
+
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    // True if the capacity counts the weights of the entries.
+    pub fn is_weighted(&self) -> bool {
+        self.weigher.is_some()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let entries = &self.entries;
+        self.table.reserve(additional, |&i| entries.get(i).hash);
+        self.entries.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        // The vacant slots of the slab are released by moving the
+        // entries to a new slab, which changes their indices.
+        let mut entries = mem::take(&mut self.entries);
+        self.entries = List::with_capacity(entries.len());
+        self.table = HashTable::with_capacity(entries.len());
+        while let Some(e) = entries.pop_front() {
+            let hash = e.hash;
+            let index = self.entries.push_back(e);
+            let entries = &self.entries;
+            self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        }
+        self.entries.shrink_to_fit();
+        self.segments = Segments::rebuild(&self.entries);
+    }
+
+    pub fn clear(&mut self) {
+        // The allocated capacity of the data table is retained.
+        // No entries survive the clear so the clock can start over.
+        if self.listener.is_some() {
+            for (_, e) in self.entries.iter() {
+                notify(&mut self.listener,
+                       &e.key,
+                       &e.val,
+                       EvictionReason::ExplicitlyRemoved);
             }
+        }
+        if let Some(ref mut negative) = self.negative {
+            negative.clear();
+        }
+        self.table.clear();
+        self.entries.clear();
+        self.segments = Segments::default();
+        self.weight = 0;
+        self.protected_weight = 0;
+        self.clock = 0;
+        self.expiry.clear();
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        Drain { cache: self }
+    }
+
+    // Reload the value of an entry if it is within its refresh window.
+    // The value is kept if the loader returns None. A reloaded value
+    // keeps the time-to-live with which the entry was written.
+    fn refresh(&mut self, index: usize) {
+        let loader = match self.loader {
+            Some(ref mut loader) => loader,
+            None => return,
         };
-        match evict {
-            // Evict the oldest entry from the data map
-            // Moved to end of function because of borrow checker
-            Some(k) => {
-                self.data.remove(k.as_ref());
+        let e = self.entries.get_mut(index);
+        if !self.expiry.take_refresh(&mut e.deadline) {
+            return;
+        }
+        if let Some(val) = loader(&e.key) {
+            let ttl = e.deadline.ttl();
+            drop(self.replace_value(index, val, ttl));
+        }
+    }
+
+    // Find the index of the entry of a key in the slab.
+    // An entry that has expired is reclaimed and not returned.
+    fn live_index<Q>(&mut self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        let index = self.index_of(hash, key)?;
+        if self.expiry.is_expired(&self.entries.get(index).deadline) {
+            drop(self.take_entry(index, EvictionReason::Expired));
+            return None;
+        }
+        Some(index)
+    }
+
+
+    // Entries that are written after this call expire once the
+    // time-to-live has elapsed. Expired entries are treated as absent
+    // by lookups and are reclaimed when their key is accessed.
+    #[cfg(feature = "std")]
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.expiry.ttl = Some(ttl);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn ttl(&self) -> Option<Duration> {
+        self.expiry.ttl
+    }
+
+    // Entries that are written or accessed after this call expire once
+    // they have not been accessed for the time-to-idle. Each lookup that
+    // promotes an entry postpones its expiration; peek() does not.
+    #[cfg(feature = "std")]
+    pub fn set_tti(&mut self, tti: Duration) {
+        self.expiry.tti = Some(tti);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn tti(&self) -> Option<Duration> {
+        self.expiry.tti
+    }
+
+    // An access to an entry during the final fraction of its time-to-live
+    // reloads the entry with the loader, so that entries which are
+    // accessed frequently do not expire. Entries that are written
+    // before this call are not refreshed.
+    // Panics if the window is not between zero and one.
+    #[cfg(feature = "std")]
+    pub fn set_refresh_ahead<F>(&mut self, window: f64, loader: F)
+        where F: FnMut(&K) -> Option<V> + Send + Sync + 'static
+    {
+        assert!((0.0..=1.0).contains(&window), "refresh window must be between zero and one");
+        self.expiry.refresh_window = Some(window);
+        self.loader = Some(Box::new(loader));
+    }
+
+    // Keys that are known to be absent are cached separately from the
+    // values, with their own capacity and time-to-live.
+    // Panics if the capacity is zero.
+    #[cfg(feature = "std")]
+    pub fn set_negative_caching(&mut self, capacity: usize, ttl: Duration)
+        where S: Clone
+    {
+        let mut negative = LRUCache::with_hasher(capacity, self.hash_builder.clone());
+        negative.expiry.set_clock(self.expiry.clock.clone());
+        negative.set_ttl(ttl);
+        self.negative = Some(Box::new(negative));
+    }
+
+    // Expiration is measured with the clock, which is the system clock
+    // by default. The clock should be set before entries are written:
+    // existing deadlines are kept as they are.
+    #[cfg(feature = "std")]
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.expiry.set_clock(Arc::new(clock));
+        for (_, e) in self.entries.iter_mut() {
+            self.expiry.reschedule(e.hash, &mut e.deadline);
+        }
+    }
+
+    // Promote the entry of a key and count the lookup as a hit or a miss.
+    // The borrowed key is hashed and compared in place, so a miss
+    // does not allocate. The key is hashed once and the table is
+    // probed once for each lookup.
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut CacheEntry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.live_index(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.refresh(index);
+        Some(self.access(index))
+    }
+
+    // Promote the entry of a key and return its index in the slab.
+    fn promote<Q>(&mut self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        let index = self.live_index(hash, key)?;
+        self.access(index);
+        Some(index)
+    }
+
+    // Promote an entry that was located. An entry in the probationary
+    // segment becomes protected, and the least-recently used protected
+    // entries return to the probationary segment while the protected
+    // segment is too heavy.
+    fn access(&mut self, index: usize) -> &mut CacheEntry<K, V> {
+        if self.protected_capacity.is_some() {
+            let e = self.entries.get(index);
+            if !e.protected {
+                self.segments.unlink(&self.entries, index);
+                let e = self.entries.get_mut(index);
+                e.protected = true;
+                self.protected_weight += e.weight;
+                self.segments.link_front(&mut self.entries, index);
+                self.demote_protected(index);
             }
-            None => {}
+        }
+        reorder(&mut self.clock,
+                &mut self.entries,
+                &mut self.segments,
+                &mut self.expiry,
+                index)
+    }
+
+    // Move the least-recently used protected entries, other than the
+    // entry with the given index, to the most-recently used position of
+    // the probationary segment until the protected entries fit.
+    fn demote_protected(&mut self, keep: usize) {
+        let capacity = match self.protected_capacity {
+            Some(capacity) => capacity,
+            None => return,
         };
+        while self.protected_weight > capacity {
+            let index = match self.segments.protected_back(&self.entries) {
+                Some(index) if index != keep => index,
+                _ => break,
+            };
+            // The entry is already in front of the probationary entries.
+            self.segments.unlink(&self.entries, index);
+            let e = self.entries.get_mut(index);
+            e.protected = false;
+            self.protected_weight -= e.weight;
+            self.segments.link_front(&mut self.entries, index);
+        }
     }
 
-    pub fn len(&self) -> usize {
-        debug_assert!(self.data.len() == self.order.len());
-        self.data.len()
+    // Find the index of the entry of a key in the slab.
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).matches(key)).copied()
     }
-}
 
-#[test]
-fn lru_cache() {
-    let mut cache = LRUCache::new(3);
-    assert_eq!(0, cache.clock);
-    assert_eq!(0, cache.len());
+    // Insert a key that is not located in the table.
+    // Evict the oldest entries until the new entry fits.
+    fn insert_vacant(&mut self,
+                     hash: u64,
+                     key: K,
+                     val: V,
+                     ttl: Option<Duration>)
+                     -> (&mut CacheEntry<K, V>, Option<(K, V)>) {
+        let now = self.clock;
+        self.clock = now.wrapping_add(1);
+        self.stats.insertions += 1;
+        let weight = weigh(&self.weigher, &key, &val);
+        let evicted = self.evict_to_fit(weight);
+        self.weight += weight;
+        let entry = CacheEntry {
+            key,
+            hash,
+            val,
+            last_access: now,
+            pinned: false,
+            weight,
+            deadline: Deadline::default(),
+            inserted: now,
+            accesses: 0,
+            protected: false,
+        };
+        let index = self.segments.push_front(&mut self.entries, entry);
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        let e = self.entries.get_mut(index);
+        self.expiry.on_write(hash, ttl, &mut e.deadline);
+        (e, evicted)
+    }
 
-    cache.insert(1, 2);
-    cache.insert(3, 4);
-    cache.insert(5, 6);
-    assert_eq!(3, cache.clock);
-    assert_eq!(3, cache.len());
-    assert_eq!(Some(&2), cache.get(1));
-    assert_eq!(Some(&4), cache.get(3));
-    assert_eq!(Some(&6), cache.get(5));
-    assert_eq!(None, cache.get(7));
-    assert_eq!(6, cache.clock);
+    // Evict the oldest entries until an additional weight fits in the cache,
+    // or within the low watermark if there is one.
+    // Returns the first entry that was not handed to the eviction callback.
+    // An entry that is heavier than the capacity evicts every unpinned entry.
+    fn evict_to_fit(&mut self, additional: usize) -> Option<(K, V)> {
+        let capacity = self.capacity?;
+        if self.weight + additional <= capacity {
+            return None;
+        }
+        let target = self.low_watermark.unwrap_or(capacity);
+        let mut first = None;
+        while self.weight + additional > target {
+            // Evict the oldest entry from the table
+            // and then from the slab
+            match self.evict() {
+                Some(pair) => {
+                    let pair = self.on_evicted(pair);
+                    if first.is_none() {
+                        first = pair;
+                    }
+                }
+                None => break,
+            }
+        }
+        first
+    }
 
-    cache.insert(1, 1);
-    cache.insert(3, 3);
-    cache.insert(5, 6);
-    assert_eq!(9, cache.clock);
-    assert_eq!(3, cache.len());
-    assert_eq!(Some(&1), cache.get(1));
-    assert_eq!(Some(&3), cache.get(3));
-    assert_eq!(Some(&6), cache.get(5));
-    assert_eq!(None, cache.get(7));
+    // Evict the least-recently used entry that is not pinned, from
+    // the probationary segment if it has an entry that is not pinned.
+    // The probationary entries are at the back of the list, so the
+    // search passes over the pinned entries only. If every entry is
+    // pinned then nothing is evicted and the cache is allowed to grow
+    // beyond its capacity.
+    fn evict(&mut self) -> Option<(K, V)> {
+        let mut index = self.entries.back()?;
+        while self.entries.get(index).pinned {
+            index = self.entries.prev(index)?;
+        }
+        self.stats.evictions += 1;
+        Some(self.take_entry(index, EvictionReason::CapacityEvicted))
+    }
 
-    cache.insert(7, 8);
-    assert_eq!(3, cache.len());
-    assert_eq!(None, cache.get(1));
-    assert_eq!(Some(&3), cache.get(3));
-    assert_eq!(Some(&6), cache.get(5));
-    assert_eq!(Some(&8), cache.get(7));
-    assert_eq!(16, cache.clock);
+    // Hand an entry that was evicted by capacity to the eviction callback.
+    // The entry is returned if there is no eviction callback.
+    fn on_evicted(&mut self, pair: (K, V)) -> Option<(K, V)> {
+        match self.on_evict {
+            Some(ref mut f) => {
+                f(pair.0, pair.1);
+                None
+            }
+            None => Some(pair),
+        }
+    }
+
+    pub fn set_on_evict<F>(&mut self, f: F)
+        where F: FnMut(K, V) + Send + Sync + 'static
+    {
+        self.on_evict = Some(Box::new(f));
+    }
+
+    // The listener is invoked before any entry leaves the cache,
+    // and before a value is overwritten, together with the reason.
+    // It replaces any previous listener or subscription.
+    pub fn set_listener<F>(&mut self, f: F)
+        where F: FnMut(&K, &V, EvictionReason) + Send + Sync + 'static
+    {
+        self.listener = Some(Box::new(f));
+    }
+
+    // Deliver a copy of every entry that leaves the cache over a channel.
+    // Events are discarded once the receiver is dropped.
+    #[cfg(feature = "std")]
+    pub fn subscribe(&mut self) -> ::std::sync::mpsc::Receiver<(K, V, EvictionReason)>
+        where K: Clone + Send + 'static,
+              V: Clone + Send + 'static
+    {
+        let (sender, receiver) = ::std::sync::mpsc::channel();
+        self.set_listener(move |k: &K, v: &V, reason| {
+            let _ = sender.send((k.clone(), v.clone(), reason));
+        });
+        receiver
+    }
+
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let index = self.entries.back()?;
+        Some(self.take_entry(index, EvictionReason::ExplicitlyRemoved))
+    }
+
+    pub fn pop_mru(&mut self) -> Option<(K, V)> {
+        let index = self.entries.front()?;
+        Some(self.take_entry(index, EvictionReason::ExplicitlyRemoved))
+    }
+
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        let e = self.entries.get(self.entries.back()?);
+        Some((&e.key, &e.val))
+    }
+
+    pub fn peek_mru(&self) -> Option<(&K, &V)> {
+        let e = self.entries.get(self.entries.front()?);
+        Some((&e.key, &e.val))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, S> {
+        Iter::new(self)
+    }
+
+    // Iterates from the least-recently used entry to the
+    // most-recently used entry without promoting the entries.
+    pub fn iter_lru_first(&self) -> Rev<Iter<'_, K, V, S>> {
+        self.iter().rev()
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut::new(self)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V, S> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V, S> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    // Inspecting the metadata of an entry does not promote it.
+    pub fn metadata<Q>(&self, key: &Q) -> Option<EntryMeta>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        let e = self.entries.get(index);
+        Some(EntryMeta {
+            inserted: e.inserted,
+            last_access: e.last_access,
+            accesses: e.accesses,
+            recency_rank: self.entries.iter().take_while(|&(i, _)| i != index).count(),
+            pinned: e.pinned,
+            weight: e.weight,
+            protected: e.protected,
+        })
+    }
+
+    pub fn most_recent_key(&self) -> Option<&K> {
+        self.peek_mru().map(|(k, _)| k)
+    }
+
+    pub fn least_recent_key(&self) -> Option<&K> {
+        self.peek_lru().map(|(k, _)| k)
+    }
+
+    // Remove the entry with the given index from the table and the slab.
+    // The stored hash of the entry locates its index without hashing the key.
+    fn take_entry(&mut self, index: usize, reason: EvictionReason) -> (K, V) {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let e = self.segments.remove(&mut self.entries, index);
+        self.weight -= e.weight;
+        if e.protected {
+            self.protected_weight -= e.weight;
+        }
+        notify(&mut self.listener, &e.key, &e.val, reason);
+        (e.key, e.val)
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    // Mean number of clock ticks since each entry was last accessed.
+    // Zero if the cache is empty.
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn average_age(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.entries.iter().map(|(_, e)| self.clock.wrapping_sub(e.last_access)).sum();
+        total as f64 / self.len() as f64
+    }
+
+    // Set the counters to zero and return their previous values.
+    pub fn reset_stats(&mut self) -> StatsSnapshot {
+        mem::take(&mut self.stats).snapshot()
+    }
+
+    pub fn len(&self) -> usize {
+        debug_assert!(self.table.len() == self.entries.len());
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|capacity| self.weight >= capacity)
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    // Approximate number of bytes used by the cache and its internal
+    // structures, including the allocated capacity of the table and the
+    // slab. The heap memory owned by the keys and the values is not
+    // counted, because it cannot be measured without knowing their types.
+    // Use mem_size() when they implement MemSize.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.table.allocation_size() + self.entries.heap_size() +
+        self.expiry.heap_size() + self.negative.as_ref().map_or(0, |n| n.memory_usage())
+    }
+
+    // bytes allocated on the heap by the keys and the values
+    fn contents_heap_size(&self) -> usize
+        where K: MemSize,
+              V: MemSize
+    {
+        self.entries.iter().map(|(_, e)| e.key.heap_size() + e.val.heap_size()).sum()
+    }
+}
+
+impl<K, V, S> fmt::Debug for LRUCache<K, V, S>
+    where K: Eq + Hash + fmt::Debug,
+          V: fmt::Debug,
+          S: BuildHasher
+{
+    // The entries are formatted from the most-recently used
+    // entry to the least-recently used entry.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> Clone for LRUCache<K, V, S>
+    where K: Eq + Hash + Clone,
+          V: Clone,
+          S: BuildHasher + Clone
+{
+    // The clone preserves the recency order and the metadata
+    // of the entries so that it evicts entries in the same order.
+    // The eviction callback is not cloned.
+    fn clone(&self) -> Self {
+        LRUCache {
+            capacity: self.capacity,
+            low_watermark: self.low_watermark,
+            weight: self.weight,
+            protected_capacity: self.protected_capacity,
+            protected_weight: self.protected_weight,
+            clock: self.clock,
+            hash_builder: self.hash_builder.clone(),
+            table: self.table.clone(),
+            entries: self.entries.clone(),
+            segments: self.segments,
+            on_evict: None,
+            listener: None,
+            stats: self.stats.clone(),
+            weigher: self.weigher.clone(),
+            expiry: self.expiry.clone(),
+            loader: None,
+            negative: self.negative.clone(),
+        }
+    }
+}
+
+impl<K, V, S> PartialEq for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          V: PartialEq,
+          S: BuildHasher
+{
+    // Two caches are equal if they contain the same (key, value) pairs.
+    // The capacity and the recency order of the caches are ignored.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.peek(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          V: Eq,
+          S: BuildHasher
+{
+}
+
+impl<K, V, S> LRUCache<K, V, S>
+    where K: Eq + Hash,
+          V: PartialEq,
+          S: BuildHasher
+{
+    // Strict equality that also requires both caches
+    // to have the same recency order.
+    pub fn ordered_eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K, V, S> MemSize for LRUCache<K, V, S>
+    where K: Eq + Hash + MemSize,
+          V: MemSize,
+          S: BuildHasher
+{
+    // The internal structures of the cache and the heap memory
+    // owned by the keys and the values.
+    fn heap_size(&self) -> usize {
+        self.memory_usage() - mem::size_of::<Self>() + self.contents_heap_size() +
+        self.negative.as_ref().map_or(0, |n| n.contents_heap_size())
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for LRUCache<K, V, S>
+    where K: Eq + Hash + Borrow<Q>,
+          Q: ?Sized + Eq + Hash,
+          S: BuildHasher
+{
+    type Output = V;
+
+    // Panics if the key is not present in the cache.
+    // Indexing does not update the recency of the entry.
+    fn index(&self, key: &Q) -> &V {
+        self.peek(key).expect("key not present in cache")
+    }
+}
+
+impl<K, Q, V, S> IndexMut<&Q> for LRUCache<K, V, S>
+    where K: Eq + Hash + Borrow<Q>,
+          Q: ?Sized + Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the key is not present in the cache.
+    // Mutable indexing promotes the entry like get_mut().
+    fn index_mut(&mut self, key: &Q) -> &mut V {
+        self.get_mut(key).expect("key not present in cache")
+    }
+}
+
+impl<K, V, S> Default for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher + Default
+{
+    fn default() -> Self {
+        LRUCache::with_hasher(DEFAULT_CAPACITY, S::default())
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.insert_many(iter);
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for LRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher + Default
+{
+    // The capacity of the cache is the number of pairs
+    // yielded by the iterator, so nothing is evicted.
+    // An empty iterator creates a cache with a capacity of one.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let pairs: Vec<(K, V)> = iter.into_iter().collect();
+        let mut cache = LRUCache::with_hasher(cmp::max(pairs.len(), 1), S::default());
+        cache.extend(pairs);
+        cache
+    }
+}
+
+#[test]
+fn lru_cache() {
+    let mut cache = LRUCache::new(3);
+    assert_eq!(0, cache.clock);
+    assert_eq!(0, cache.len());
+
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    assert_eq!(3, cache.clock);
+    assert_eq!(3, cache.len());
+    assert_eq!(Some(&2), cache.get(&1));
+    assert_eq!(Some(&4), cache.get(&3));
+    assert_eq!(Some(&6), cache.get(&5));
+    assert_eq!(None, cache.get(&7));
+    assert_eq!(6, cache.clock);
+
+    cache.insert(1, 1);
+    cache.insert(3, 3);
+    cache.insert(5, 6);
+    assert_eq!(9, cache.clock);
+    assert_eq!(3, cache.len());
+    assert_eq!(Some(&1), cache.get(&1));
+    assert_eq!(Some(&3), cache.get(&3));
+    assert_eq!(Some(&6), cache.get(&5));
+    assert_eq!(None, cache.get(&7));
+
+    cache.insert(7, 8);
+    assert_eq!(3, cache.len());
+    assert_eq!(None, cache.get(&1));
+    assert_eq!(Some(&3), cache.get(&3));
+    assert_eq!(Some(&6), cache.get(&5));
+    assert_eq!(Some(&8), cache.get(&7));
+    assert_eq!(16, cache.clock);
+}
+
+#[test]
+fn lru_remove() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some(2), cache.remove(&1));
+    assert_eq!(None, cache.remove(&1));
+    assert_eq!(1, cache.len());
+    assert_eq!(None, cache.get(&1));
+
+    // the freed slot is reused without evicting 3
+    cache.insert(5, 6);
+    assert_eq!(2, cache.len());
+    assert_eq!(Some(&4), cache.get(&3));
+    assert_eq!(Some(&6), cache.get(&5));
+}
+
+#[test]
+fn lru_borrow() {
+    let mut cache: LRUCache<String, i32> = LRUCache::new(2);
+    cache.insert("one".to_string(), 1);
+    cache.insert("two".to_string(), 2);
+    assert_eq!(Some(&1), cache.get("one"));
+    assert_eq!(None, cache.get("three"));
+    assert_eq!(Some(2), cache.remove("two"));
+    assert_eq!(1, cache.len());
+}
+
+#[test]
+fn lru_peek() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some(&2), cache.peek(&1));
+    assert_eq!(None, cache.peek(&5));
+    assert_eq!(2, cache.clock);
+
+    // peek does not protect 1 from eviction
+    cache.insert(5, 6);
+    assert_eq!(None, cache.peek(&1));
+    assert_eq!(Some(&4), cache.peek(&3));
+}
+
+#[test]
+fn lru_contains_key() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert!(cache.contains_key(&1));
+    assert!(!cache.contains_key(&5));
+
+    // contains_key does not protect 1 from eviction
+    cache.insert(5, 6);
+    assert!(!cache.contains_key(&1));
+    assert!(cache.contains_key(&5));
+}
+
+#[test]
+fn lru_get_mut() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    if let Some(v) = cache.get_mut(&1) {
+        *v = 10;
+    }
+    assert_eq!(None, cache.get_mut(&5));
+
+    // get_mut promotes 1 so that 3 is evicted
+    cache.insert(5, 6);
+    assert_eq!(Some(&10), cache.peek(&1));
+    assert_eq!(None, cache.peek(&3));
+}
+
+#[test]
+fn lru_clear() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.clear();
+    assert_eq!(0, cache.len());
+    assert_eq!(0, cache.clock);
+    assert_eq!(None, cache.get(&1));
+
+    cache.insert(5, 6);
+    assert_eq!(Some(&6), cache.get(&5));
+    assert_eq!(1, cache.len());
+}
+
+#[test]
+fn lru_pop_lru() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(Some((3, 4)), cache.pop_lru());
+    assert_eq!(Some((5, 6)), cache.pop_lru());
+    assert_eq!(Some((1, 2)), cache.pop_lru());
+    assert_eq!(None, cache.pop_lru());
+    assert_eq!(0, cache.len());
+}
+
+#[test]
+fn lru_pop_mru() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(Some((1, 2)), cache.pop_mru());
+    assert_eq!(Some((5, 6)), cache.pop_mru());
+    assert_eq!(Some((3, 4)), cache.pop_mru());
+    assert_eq!(None, cache.pop_mru());
+    assert_eq!(0, cache.len());
+}
+
+#[test]
+fn lru_peek_lru_mru() {
+    let mut cache = LRUCache::new(3);
+    assert_eq!(None, cache.peek_lru());
+    assert_eq!(None, cache.peek_mru());
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(Some((&3, &4)), cache.peek_lru());
+    assert_eq!(Some((&1, &2)), cache.peek_mru());
+    assert_eq!(4, cache.clock);
+    assert_eq!(3, cache.len());
+}
+
+#[test]
+fn lru_resize() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    cache.resize(1);
+    assert_eq!(1, cache.len());
+    assert_eq!(Some(&2), cache.get(&1));
+
+    cache.resize(2);
+    cache.insert(3, 4);
+    assert_eq!(2, cache.len());
+    cache.insert(5, 6);
+    assert_eq!(2, cache.len());
+    assert_eq!(None, cache.get(&1));
+}
+
+#[test]
+fn lru_watermarks() {
+    let mut cache = LRUCache::builder().watermarks(4, 2).build();
+    assert_eq!((Some(4), Some(2)), (cache.capacity(), cache.low_watermark()));
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    // Exceeding the high watermark evicts down to the low watermark,
+    // leaving room for the new entry and one more.
+    assert_eq!(Some((0, 0)), cache.insert(4, 4).evicted);
+    assert_eq!(vec![&4, &3], cache.keys().collect::<Vec<_>>());
+    assert_eq!(None, cache.insert(5, 5).evicted);
+    assert_eq!(None, cache.insert(6, 6).evicted);
+    assert_eq!(3, cache.stats().evictions());
+    // A smaller capacity lowers the low watermark.
+    cache.resize(1);
+    assert_eq!(Some(1), cache.low_watermark());
+    cache.set_watermarks(3, 3);
+    cache.insert(7, 7);
+    cache.insert(8, 8);
+    assert_eq!(Some((6, 6)), cache.insert(9, 9).evicted);
+}
+
+#[test]
+fn lru_probation() {
+    let mut cache = LRUCache::builder().capacity(4).probation(2).build();
+    assert_eq!(Some(2), cache.protected_capacity());
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&0);
+    cache.get(&1);
+    assert!(cache.metadata(&0).unwrap().protected);
+    // A scan of new keys only displaces the probationary entries.
+    for i in 10..20 {
+        cache.insert(i, i);
+    }
+    assert!(cache.contains_key(&0) && cache.contains_key(&1));
+    assert_eq!(4, cache.len());
+    // Protecting a third entry returns the oldest protected entry
+    // to the probationary segment, where it is the newest entry.
+    cache.get(&19);
+    assert!(!cache.metadata(&0).unwrap().protected);
+    assert_eq!(Some((18, 18)), cache.insert(20, 20).evicted);
+    assert_eq!(Some((0, 0)), cache.insert(21, 21).evicted);
+    assert_eq!(Some((20, 20)), cache.insert(22, 22).evicted);
+    let protected = cache.keys().filter(|&k| cache.metadata(k).unwrap().protected).count();
+    assert_eq!((2, 2), (protected, cache.protected_weight));
+    cache.remove(&1);
+    cache.clear();
+    assert_eq!(0, cache.protected_weight);
+}
+
+#[test]
+fn lru_probation_segments() {
+    let mut cache = LRUCache::builder().capacity(6).probation(2).build();
+    for i in 0..6 {
+        cache.insert(i, i);
+    }
+    cache.get(&0);
+    cache.get(&1);
+    cache.pin(&4);
+    // Protecting a third entry demotes the oldest protected entry
+    // to the front of the probationary segment.
+    cache.get(&2);
+    assert_eq!(vec![&2, &1, &0, &5, &4, &3], cache.keys().collect::<Vec<_>>());
+    // A demoted protected entry returns to the back of the
+    // probationary segment and is the next to be evicted.
+    cache.demote(&2);
+    assert_eq!(vec![&1, &0, &5, &4, &3, &2], cache.keys().collect::<Vec<_>>());
+    assert_eq!((false, 1), (cache.metadata(&2).unwrap().protected, cache.protected_weight));
+    assert_eq!(Some((2, 2)), cache.insert(6, 6).evicted);
+    // Eviction passes over a pinned entry, which keeps its place
+    // in the recency order when it is unpinned.
+    cache.demote(&4);
+    assert_eq!(Some((3, 3)), cache.insert(7, 7).evicted);
+    cache.unpin(&4);
+    assert_eq!(vec![&1, &7, &6, &0, &5, &4], cache.keys().collect::<Vec<_>>());
+    cache.retain(|&k, _| k != 0);
+    cache.shrink_to_fit();
+    cache.get(&7);
+    assert_eq!(vec![&7, &1, &6, &5, &4], cache.keys().collect::<Vec<_>>());
+    let protected: Vec<_> = cache.keys().filter(|&k| cache.metadata(k).unwrap().protected).collect();
+    assert_eq!((vec![&7, &1], 2), (protected, cache.protected_weight));
+}
+
+#[test]
+fn lru_capacity() {
+    let mut cache = LRUCache::new(2);
+    assert_eq!(Some(2), cache.capacity());
+    assert!(cache.is_empty());
+    assert!(!cache.is_full());
+    cache.insert(1, 2);
+    assert!(!cache.is_empty());
+    assert!(!cache.is_full());
+    cache.insert(3, 4);
+    assert!(cache.is_full());
+    cache.resize(3);
+    assert_eq!(Some(3), cache.capacity());
+    assert!(!cache.is_full());
+}
+
+#[test]
+fn lru_entry() {
+    let mut cache = LRUCache::new(2);
+    *cache.entry(1).or_insert(0) += 1;
+    *cache.entry(1).or_insert(0) += 1;
+    cache.entry(3).or_insert_with(|| 4);
+    assert_eq!(Some(&2), cache.peek(&1));
+    assert_eq!(Some(&4), cache.peek(&3));
+
+    // the occupied entry for 1 is promoted so 3 is evicted
+    cache.entry(1).and_modify(|v| *v *= 10).or_insert(0);
+    cache.entry(5).and_modify(|v| *v *= 10).or_insert(6);
+    assert_eq!(Some(&20), cache.peek(&1));
+    assert_eq!(None, cache.peek(&3));
+    assert_eq!(Some(&6), cache.peek(&5));
+
+    match cache.entry(5) {
+        Entry::Occupied(e) => assert_eq!(6, e.remove()),
+        Entry::Vacant(_) => unreachable!(),
+    }
+    assert_eq!(1, cache.len());
+    assert_eq!(0, *cache.entry(7).or_default());
+}
+
+#[test]
+fn lru_get_or_insert_with() {
+    let mut cache = LRUCache::new(2);
+    let mut loads = 0;
+    for _ in 0..3 {
+        let v = cache.get_or_insert_with(1, || {
+            loads += 1;
+            2
+        });
+        assert_eq!(2, *v);
+    }
+    assert_eq!(1, loads);
+
+    cache.insert(3, 4);
+    assert_eq!(&6, cache.get_or_insert_with(5, || 6));
+    assert_eq!(2, cache.len());
+    assert_eq!(None, cache.peek(&1));
+}
+
+#[test]
+fn lru_try_get_or_insert_with() {
+    let mut cache = LRUCache::new(2);
+    assert_eq!(Err("unavailable"), cache.try_get_or_insert_with(1, || Err("unavailable")));
+    assert_eq!(0, cache.len());
+    assert_eq!(Ok(&2), cache.try_get_or_insert_with(1, || Ok::<_, &str>(2)));
+    assert_eq!(Ok(&2), cache.try_get_or_insert_with(1, || Err("unavailable")));
+    assert_eq!(1, cache.len());
+}
+
+#[test]
+fn lru_insert_result() {
+    let mut cache = LRUCache::new(2);
+    assert_eq!(InsertResult { replaced: None, evicted: None }, cache.insert(1, 2));
+    assert_eq!(InsertResult { replaced: None, evicted: None }, cache.insert(3, 4));
+    assert_eq!(InsertResult { replaced: Some(2), evicted: None }, cache.insert(1, 1));
+    assert_eq!(InsertResult { replaced: None, evicted: Some((3, 4)) }, cache.insert(5, 6));
+}
+
+#[test]
+fn lru_iter() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    let entries: Vec<_> = cache.iter().collect();
+    assert_eq!(vec![(&1, &2), (&5, &6), (&3, &4)], entries);
+    assert_eq!(3, cache.iter().len());
+    assert_eq!(4, cache.clock);
+    let mut sum = 0;
+    for (_, v) in &cache {
+        sum += *v;
+    }
+    assert_eq!(12, sum);
+}
+
+#[test]
+fn lru_iter_mut() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    for (k, v) in cache.iter_mut() {
+        *v += *k;
+    }
+    for (_, v) in &mut cache {
+        *v *= 10;
+    }
+    assert_eq!(3, cache.iter_mut().len());
+    assert_eq!(3, cache.clock);
+    let entries: Vec<_> = cache.iter().collect();
+    assert_eq!(vec![(&5, &110), (&3, &70), (&1, &30)], entries);
+}
+
+#[test]
+fn lru_into_iter() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, "a".to_string());
+    cache.insert(3, "b".to_string());
+    cache.insert(5, "c".to_string());
+    cache.get(&1);
+    let mut iter = cache.into_iter();
+    assert_eq!(3, iter.len());
+    assert_eq!(Some((1, "a".to_string())), iter.next_back());
+    let entries: Vec<_> = iter.collect();
+    assert_eq!(vec![(3, "b".to_string()), (5, "c".to_string())], entries);
+}
+
+#[test]
+fn lru_keys_values() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(vec![&1, &5, &3], cache.keys().collect::<Vec<_>>());
+    assert_eq!(vec![&2, &6, &4], cache.values().collect::<Vec<_>>());
+    for v in cache.values_mut() {
+        *v += 1;
+    }
+    assert_eq!(15, cache.values().sum::<i32>());
+}
+
+#[test]
+fn lru_drain() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(vec![(3, 4), (5, 6), (1, 2)], cache.drain().collect::<Vec<_>>());
+    assert!(cache.is_empty());
+
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some((1, 2)), cache.drain().next());
+    assert!(cache.is_empty());
+    assert_eq!(Some(3), cache.capacity());
+}
+
+#[test]
+fn lru_retain() {
+    let mut cache = LRUCache::new(4);
+    for i in 0..4 {
+        cache.insert(i, i * 10);
+    }
+    cache.retain(|k, _| k % 2 == 0);
+    assert_eq!(vec![&2, &0], cache.keys().collect::<Vec<_>>());
+    cache.insert(5, 50);
+    cache.insert(7, 70);
+    cache.insert(9, 90);
+    assert_eq!(4, cache.len());
+    assert_eq!(None, cache.peek(&0));
+}
+
+#[test]
+fn lru_from_iter() {
+    let mut cache: LRUCache<_, _> = vec![(1, 2), (3, 4), (1, 5)].into_iter().collect();
+    assert_eq!(Some(3), cache.capacity());
+    assert_eq!(2, cache.len());
+    assert_eq!(vec![(&1, &5), (&3, &4)], cache.iter().collect::<Vec<_>>());
+
+    cache.extend(vec![(6, 7), (8, 9)]);
+    assert_eq!(3, cache.len());
+    assert_eq!(vec![&8, &6, &1], cache.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn lru_debug_clone_default() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!("{1: 2, 5: 6, 3: 4}", format!("{:?}", cache));
+
+    let mut copy = cache.clone();
+    assert_eq!(vec![&1, &5, &3], copy.keys().collect::<Vec<_>>());
+    copy.insert(7, 8);
+    assert_eq!(None, copy.peek(&3));
+    assert_eq!(Some(&4), cache.peek(&3));
+
+    let cache: LRUCache<i32, i32> = Default::default();
+    assert_eq!(Some(DEFAULT_CAPACITY), cache.capacity());
+}
+
+#[test]
+fn lru_eq() {
+    let mut a = LRUCache::new(3);
+    a.insert(1, 2);
+    a.insert(3, 4);
+    let mut b = LRUCache::new(2);
+    b.insert(3, 4);
+    b.insert(1, 2);
+    assert!(a == b);
+    assert!(!a.ordered_eq(&b));
+    b.get(&3);
+    assert!(a.ordered_eq(&b));
+    b.insert(1, 5);
+    assert!(a != b);
+    a.remove(&1);
+    assert!(a != b);
+}
+
+#[test]
+fn lru_index() {
+    let mut cache = LRUCache::new(2);
+    cache.insert("a".to_string(), 1);
+    cache.insert("b".to_string(), 2);
+    assert_eq!(1, cache["a"]);
+    cache["a"] += 10;
+    cache.insert("c".to_string(), 3);
+    assert_eq!(11, cache["a"]);
+    assert!(!cache.contains_key("b"));
+}
+
+#[test]
+#[should_panic]
+fn lru_index_missing() {
+    let cache: LRUCache<i32, i32> = LRUCache::new(2);
+    let _ = cache[&1];
+}
+
+#[test]
+fn lru_touch() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert!(cache.touch(&1));
+    assert!(!cache.touch(&5));
+    cache.insert(5, 6);
+    assert!(cache.contains_key(&1));
+    assert!(!cache.contains_key(&3));
+}
+
+#[test]
+fn lru_demote() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    assert!(cache.demote(&5));
+    assert!(cache.demote(&3));
+    assert!(!cache.demote(&7));
+    assert_eq!(vec![&1, &5, &3], cache.keys().collect::<Vec<_>>());
+    cache.insert(7, 8);
+    assert_eq!(None, cache.peek(&3));
+    assert!(cache.demote(&7));
+    assert_eq!(Some((&7, &8)), cache.peek_lru());
+    cache.get(&7);
+    assert_eq!(Some((&7, &8)), cache.peek_mru());
+    assert_eq!(3, cache.len());
+}
+
+#[test]
+fn lru_pin() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert!(cache.pin(&1));
+    assert!(!cache.pin(&5));
+    assert!(cache.is_pinned(&1));
+    assert_eq!(Some((3, 4)), cache.insert(5, 6).evicted);
+    assert_eq!(Some((5, 6)), cache.insert(7, 8).evicted);
+    assert!(cache.contains_key(&1));
+
+    // pinned entries survive a resize and
+    // the cache grows when every entry is pinned
+    cache.pin(&7);
+    cache.resize(1);
+    assert_eq!(2, cache.len());
+    assert_eq!(None, cache.insert(9, 10).evicted);
+    assert_eq!(3, cache.len());
+    assert!(cache.unpin(&1));
+    assert!(!cache.is_pinned(&1));
+    assert_eq!(Some((1, 2)), cache.insert(11, 12).evicted);
+
+    // explicit removal ignores pins
+    assert_eq!(Some((7, 8)), cache.pop_lru());
+}
+
+#[test]
+fn lru_get_key_value() {
+    let mut cache = LRUCache::new(2);
+    cache.insert("a".to_string(), 1);
+    cache.insert("b".to_string(), 2);
+    assert_eq!(Some((&"a".to_string(), &1)), cache.get_key_value("a"));
+    assert_eq!(None, cache.get_key_value("c"));
+    cache.insert("c".to_string(), 3);
+    assert!(cache.contains_key("a"));
+    assert!(!cache.contains_key("b"));
+}
+
+#[test]
+fn lru_many() {
+    let mut cache = LRUCache::new(3);
+    cache.insert_many(vec![(1, 2), (3, 4), (5, 6), (7, 8)]);
+    assert_eq!(3, cache.len());
+    assert_eq!(vec![None, Some(&6), Some(&4)], cache.get_many(&[1, 5, 3]));
+    assert_eq!(vec![&3, &5, &7], cache.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn lru_reserve_shrink() {
+    let mut cache = LRUCache::new(2);
+    cache.reserve(100);
+    assert!(cache.table.capacity() >= 100);
+    cache.insert(1, 2);
+    cache.shrink_to_fit();
+    assert!(cache.table.capacity() < 100);
+    assert_eq!(Some(&2), cache.get(&1));
+}
+
+#[test]
+fn lru_unbounded() {
+    let mut cache = LRUCache::unbounded();
+    assert_eq!(None, cache.capacity());
+    for i in 0..100 {
+        assert_eq!(None, cache.insert(i, i).evicted);
+    }
+    assert_eq!(100, cache.len());
+    assert!(!cache.is_full());
+    cache.get(&0);
+    assert_eq!(Some((1, 1)), cache.pop_lru());
+    cache.resize(10);
+    assert_eq!(10, cache.len());
+    assert_eq!(Some((&0, &0)), cache.peek_mru());
+}
+
+#[test]
+fn lru_zero_capacity() {
+    assert_eq!(Some(CapacityError), LRUCache::<i32, i32>::try_new(0).err());
+    assert_eq!(Some(1), LRUCache::<i32, i32>::try_new(1).unwrap().capacity());
+    let cache = LRUCache::<i32, i32>::with_nonzero_capacity(NonZeroUsize::new(4).unwrap());
+    assert_eq!(Some(4), cache.capacity());
+    let cache: LRUCache<i32, i32> = Vec::new().into_iter().collect();
+    assert_eq!(Some(1), cache.capacity());
+}
+
+#[test]
+#[should_panic(expected = "cache capacity must be greater than zero")]
+fn lru_new_zero_capacity() {
+    LRUCache::<i32, i32>::new(0);
+}
+
+#[test]
+#[should_panic(expected = "cache capacity must be greater than zero")]
+fn lru_resize_zero_capacity() {
+    LRUCache::<i32, i32>::new(1).resize(0);
+}
+
+#[test]
+fn lru_with_hasher() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    let mut cache: LRUCache<i32, i32, BuildHasherDefault<DefaultHasher>> =
+        LRUCache::with_hasher(2, BuildHasherDefault::default());
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    assert_eq!(vec![&5, &3], cache.keys().collect::<Vec<_>>());
+    let copy = cache.clone();
+    assert!(copy.ordered_eq(&cache));
+    let cache: LRUCache<i32, i32, BuildHasherDefault<DefaultHasher>> = Default::default();
+    assert_eq!(Some(DEFAULT_CAPACITY), cache.capacity());
+    let cache: LRUCache<i32, i32, BuildHasherDefault<DefaultHasher>> =
+        LRUCache::unbounded_with_hasher(BuildHasherDefault::default());
+    assert_eq!(None, cache.capacity());
+}
+
+#[test]
+fn lru_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<LRUCache<String, Vec<u8>>>();
+    assert_send_sync::<Entry<'static, String, Vec<u8>>>();
+    assert_send_sync::<Iter<'static, String, Vec<u8>>>();
+
+    // A key that is not shared between threads only needs to be Send.
+    #[derive(PartialEq, Eq, Hash)]
+    struct Key(u32, ::core::marker::PhantomData<::core::cell::Cell<()>>);
+    fn assert_send<T: Send>() {}
+    assert_send::<LRUCache<Key, Vec<u8>>>();
+
+    let cache = ::std::sync::Mutex::new(LRUCache::new(2));
+    ::std::thread::scope(|s| {
+        s.spawn(|| cache.lock().unwrap().insert(1, 2));
+    });
+    assert_eq!(Some(&2), cache.lock().unwrap().get(&1));
+}
+
+#[test]
+fn lru_swap() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some(2), cache.swap(&1, 10));
+    assert_eq!(None, cache.swap(&5, 6));
+    assert_eq!(2, cache.len());
+    // the swapped entry is still the least-recently used
+    assert_eq!(Some((1, 10)), cache.insert(5, 6).evicted);
+    assert_eq!(Some(4), cache.take(&3));
+    assert_eq!(None, cache.take(&3));
+
+    // a heavier value evicts the oldest entries to fit
+    let mut cache = LRUCache::new(10);
+    cache.set_weigher(|_: &u32, v: &String| v.len());
+    cache.insert(1, "abc".to_string());
+    cache.insert(2, "def".to_string());
+    cache.insert(3, "ghi".to_string());
+    assert_eq!(Some("def".to_string()), cache.swap(&2, "jklmno".to_string()));
+    assert_eq!((vec![&3, &2], 9), (cache.keys().collect::<Vec<_>>(), cache.weight()));
+}
+
+#[test]
+fn lru_get_cloned() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, "a".to_string());
+    cache.insert(3, "b".to_string());
+    let a = cache.get_cloned(&1);
+    cache.insert(5, "c".to_string());
+    assert_eq!(Some("a".to_string()), a);
+    assert_eq!(None, cache.get_cloned(&3));
+}
+
+#[test]
+fn lru_recent_keys() {
+    let mut cache = LRUCache::new(3);
+    assert_eq!(None, cache.most_recent_key());
+    assert_eq!(None, cache.least_recent_key());
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(Some(&1), cache.most_recent_key());
+    assert_eq!(Some(&3), cache.least_recent_key());
+}
+
+#[test]
+fn lru_on_evict() {
+    use std::sync::{Arc, Mutex};
+
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let mut cache = LRUCache::new(2);
+    let sink = evicted.clone();
+    cache.set_on_evict(move |k, v| sink.lock().unwrap().push((k, v)));
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(None, cache.insert(5, 6).evicted);
+    cache.resize(1);
+    // explicit removal does not invoke the callback
+    cache.remove(&5);
+    assert_eq!(vec![(1, 2), (3, 4)], *evicted.lock().unwrap());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_listener() {
+    let mut cache = LRUCache::new(2);
+    let events = cache.subscribe();
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(1, 20);
+    cache.insert(5, 6);
+    cache.remove(&1);
+    assert_eq!(Some(6), cache.swap(&5, 60));
+    cache.clear();
+    let events: Vec<_> = events.try_iter().collect();
+    assert_eq!(vec![(1, 2, EvictionReason::Replaced),
+                    (3, 4, EvictionReason::CapacityEvicted),
+                    (1, 20, EvictionReason::ExplicitlyRemoved),
+                    (5, 6, EvictionReason::Replaced),
+                    (5, 60, EvictionReason::ExplicitlyRemoved)],
+               events);
+}
+
+#[test]
+fn lru_stats() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(1, 20);
+    cache.insert(5, 6);
+    assert_eq!(Some(&20), cache.get(&1));
+    assert_eq!(None, cache.get(&3));
+    // peek() is not counted as a lookup
+    cache.peek(&5);
+    let stats = cache.stats();
+    assert_eq!(1, stats.hits());
+    assert_eq!(1, stats.misses());
+    assert_eq!(3, stats.insertions());
+    assert_eq!(1, stats.replacements());
+    assert_eq!(1, stats.evictions());
+}
+
+#[test]
+fn lru_stats_snapshot() {
+    let mut cache = LRUCache::new(2);
+    assert_eq!(0.0, cache.stats().hit_ratio());
+    cache.insert(1, 2);
+    cache.get(&1);
+    cache.get(&1);
+    cache.get(&1);
+    cache.get(&3);
+    let snapshot = cache.stats().snapshot();
+    assert_eq!(4, snapshot.lookups());
+    assert_eq!(0.75, snapshot.hit_ratio());
+    assert_eq!(snapshot, cache.reset_stats());
+    assert_eq!(StatsSnapshot::default(), cache.stats().snapshot());
+    cache.get(&3);
+    assert_eq!(0.0, cache.stats().hit_ratio());
+    assert_eq!(1, cache.stats().misses());
+}
+
+#[test]
+fn lru_weigher() {
+    let mut cache = LRUCache::new(10);
+    cache.set_weigher(|_: &u32, v: &String| v.len());
+    cache.insert(1, "abcd".to_string());
+    cache.insert(2, "efgh".to_string());
+    assert_eq!(8, cache.weight());
+    assert!(!cache.is_full());
+    // both older entries are evicted to make room
+    assert_eq!(Some((1, "abcd".to_string())), cache.insert(3, "ijklmnop".to_string()).evicted);
+    assert_eq!(vec![&3], cache.keys().collect::<Vec<_>>());
+    assert_eq!(8, cache.weight());
+    // replacing a value updates the weight
+    cache.insert(3, "q".to_string());
+    assert_eq!(1, cache.weight());
+    cache.insert(4, "rs".to_string());
+    cache.remove(&3);
+    assert_eq!(2, cache.weight());
+    cache.resize(1);
+    assert!(cache.is_empty());
+    assert_eq!(0, cache.weight());
+
+    // without a weigher every entry weighs one
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    assert_eq!(2, cache.weight());
+    cache.set_weigher(|_, v| *v / 3);
+    assert_eq!(vec![&5], cache.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn lru_memory_limit() {
+    let entry = entry_mem_size(&0u64, &String::new());
+    let mut cache = LRUCache::with_memory_limit(2 * entry + 100);
+    cache.insert(1u64, String::with_capacity(50));
+    cache.insert(2u64, String::with_capacity(50));
+    assert_eq!(2 * entry + 100, cache.weight());
+    assert!(cache.is_full());
+    assert_eq!(Some(1), cache.insert(3u64, String::with_capacity(1)).evicted.map(|(k, _)| k));
+    assert_eq!(2 * entry + 51, cache.weight());
+}
+
+#[test]
+fn lru_memory_usage() {
+    let mut cache = LRUCache::new(16);
+    let empty = cache.memory_usage();
+    assert!(empty > mem::size_of::<LRUCache<u64, String>>());
+    // The allocated capacity is counted whether or not it is occupied.
+    cache.insert(1u64, String::with_capacity(100));
+    assert_eq!(empty, cache.memory_usage());
+    assert_eq!(cache.memory_usage() + 100, cache.mem_size());
+    // The 16 slots of the new cache are already counted in its usage.
+    cache.reserve(100);
+    assert!(cache.memory_usage() > empty + (100 - 16) * mem::size_of::<CacheEntry<u64, String>>());
+    cache.clear();
+    cache.shrink_to_fit();
+    assert_eq!(cache.memory_usage(), cache.mem_size());
+    assert!(cache.memory_usage() < empty);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_ttl() {
+    use std::time::Duration;
+
+    let mut cache = LRUCache::new(4);
+    cache.insert(1, 2);
+    cache.set_ttl(Duration::from_secs(3600));
+    cache.insert(3, 4);
+    assert_eq!(Some(&4), cache.get(&3));
+    // a time-to-live of zero expires entries as soon as they are written
+    cache.set_ttl(Duration::ZERO);
+    let events = cache.subscribe();
+    cache.insert(5, 6);
+    assert!(!cache.contains_key(&5));
+    assert_eq!(None, cache.get(&5));
+    assert_eq!(Some((5, 6, EvictionReason::Expired)), events.try_recv().ok());
+    // entries written before the time-to-live was set do not expire
+    assert_eq!(Some(&2), cache.get(&1));
+    assert_eq!(Some(&4), cache.get(&3));
+    cache.insert(3, 40);
+    assert_eq!(None, cache.get(&3));
+    assert_eq!(1, cache.len());
+    // a time-to-live beyond the range of the clock never expires
+    cache.set_ttl(Duration::MAX);
+    cache.set_tti(Duration::MAX);
+    cache.insert(7, 8);
+    assert_eq!(Some(&8), cache.get(&7));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_tti() {
+    use std::time::Duration;
+
+    let mut cache = LRUCache::new(4);
+    cache.set_tti(Duration::from_secs(3600));
+    cache.insert(1, 2);
+    assert_eq!(Some(&2), cache.get(&1));
+    cache.set_tti(Duration::ZERO);
+    // the idle deadline is not postponed by peek()
+    assert_eq!(Some(&2), cache.peek(&1));
+    // the access refreshes the deadline to the time-to-idle of zero
+    assert!(cache.touch(&1));
+    assert_eq!(None, cache.get(&1));
+    assert!(cache.is_empty());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_insert_with_ttl() {
+    use std::time::Duration;
+
+    let mut cache = LRUCache::new(4);
+    cache.insert_with_ttl(1, 2, Duration::ZERO);
+    cache.insert_with_ttl(3, 4, Duration::from_secs(3600));
+    assert_eq!(None, cache.get(&1));
+    assert_eq!(Some(&4), cache.get(&3));
+    // the entry TTL overrides the cache TTL
+    cache.set_ttl(Duration::ZERO);
+    cache.insert_with_ttl(5, 6, Duration::from_secs(3600));
+    assert_eq!(Some(&6), cache.get(&5));
+    // a swapped value keeps the entry TTL
+    assert_eq!(Some(6), cache.swap(&5, 7));
+    assert_eq!(Some(&7), cache.get(&5));
+    cache.insert(5, 60);
+    assert_eq!(None, cache.get(&5));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_purge_expired() {
+    use std::time::Duration;
+
+    let mut cache = LRUCache::new(4);
+    assert_eq!(0, cache.purge_expired());
+    cache.insert(1, 2);
+    cache.insert_with_ttl(3, 4, Duration::ZERO);
+    cache.insert_with_ttl(5, 6, Duration::ZERO);
+    cache.insert_with_ttl(7, 8, Duration::from_secs(3600));
+    assert_eq!(4, cache.len());
+    assert_eq!(2, cache.purge_expired());
+    assert_eq!(2, cache.len());
+    assert_eq!(vec![&7, &1], cache.keys().collect::<Vec<_>>());
+    assert_eq!(0, cache.purge_expired());
+    // an expired entry that is replaced is not purged
+    cache.insert_with_ttl(9, 10, Duration::ZERO);
+    cache.insert_with_ttl(9, 11, Duration::from_secs(3600));
+    assert_eq!(0, cache.purge_expired());
+    assert_eq!(Some(&11), cache.peek(&9));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_clock() {
+    use std::time::Duration;
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = LRUCache::new(4);
+    cache.set_clock(clock.clone());
+    cache.set_ttl(Duration::from_secs(10));
+    cache.set_tti(Duration::from_secs(4));
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert_with_ttl(5, 6, Duration::from_secs(2));
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(1, cache.purge_expired());
+    assert_eq!(Some(&2), cache.get(&1));
+    clock.advance(Duration::from_secs(3));
+    // the idle entry expires and the accessed entry is rescheduled
+    assert_eq!(1, cache.purge_expired());
+    assert!(cache.contains_key(&1));
+    assert!(!cache.contains_key(&3));
+    cache.get(&1);
+    clock.advance(Duration::from_secs(5));
+    // the entry is expired by its time-to-live despite the access
+    assert!(!cache.contains_key(&1));
+    assert_eq!(1, cache.purge_expired());
+    assert!(cache.is_empty());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_get_stale_ok() {
+    use std::time::Duration;
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = LRUCache::new(4);
+    cache.set_clock(clock.clone());
+    cache.set_ttl(Duration::from_secs(10));
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some(CachedValue::Fresh(&2)), cache.get_stale_ok(&1));
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(Some(CachedValue::Stale(&2)), cache.get_stale_ok(&1));
+    assert!(cache.get_stale_ok(&1).unwrap().is_stale());
+    assert_eq!(None, cache.get_stale_ok(&5));
+    // revalidate the stale entry
+    cache.insert(1, 20);
+    assert_eq!(Some(&20), cache.get_stale_ok(&1).map(CachedValue::into_inner));
+    // other lookups reclaim the expired entry
+    assert_eq!(None, cache.get(&3));
+    assert_eq!(None, cache.get_stale_ok(&3));
+    let stats = cache.stats();
+    assert_eq!((2, 3, 2), (stats.hits(), stats.misses(), stats.stale_hits()));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_refresh_ahead() {
+    use std::time::Duration;
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = LRUCache::new(4);
+    cache.set_clock(clock.clone());
+    cache.set_ttl(Duration::from_secs(10));
+    cache.set_refresh_ahead(0.2, |k: &u32| if *k == 1 { Some(k * 100) } else { None });
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    clock.advance(Duration::from_secs(7));
+    assert_eq!(Some(&2), cache.get(&1));
+    clock.advance(Duration::from_secs(2));
+    // the loader is called within the final 20% of the time-to-live
+    assert_eq!(Some(&100), cache.get(&1));
+    assert_eq!(Some(&4), cache.get(&3));
+    clock.advance(Duration::from_secs(2));
+    // the refreshed entry has a new time-to-live
+    assert_eq!(Some(&100), cache.get(&1));
+    assert_eq!(None, cache.get(&3));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_refresh_ahead_ttl() {
+    use std::time::Duration;
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = LRUCache::new(4);
+    cache.set_clock(clock.clone());
+    cache.set_ttl(Duration::from_secs(10));
+    cache.set_refresh_ahead(0.5, |k: &u32| Some(k * 100));
+    cache.insert_with_ttl(1, 2, Duration::from_secs(100));
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(Some(&100), cache.get(&1));
+    // the reloaded value keeps the time-to-live of the entry
+    clock.advance(Duration::from_secs(20));
+    assert_eq!(Some(&100), cache.get(&1));
+    clock.advance(Duration::from_secs(90));
+    assert_eq!(None, cache.get(&1));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_negative_caching() {
+    use std::time::Duration;
+
+    let mut cache = LRUCache::new(4);
+    cache.insert_negative(1);
+    assert_eq!(CacheResult::Miss, cache.get_result(&1));
+    cache.set_negative_caching(2, Duration::from_secs(60));
+    cache.insert(1, 2);
+    cache.insert_negative(3);
+    assert_eq!(CacheResult::Hit(&2), cache.get_result(&1));
+    assert_eq!(CacheResult::NegativeHit, cache.get_result(&3));
+    assert_eq!(CacheResult::Miss, cache.get_result(&5));
+    assert_eq!(None, cache.get(&3));
+    // a negative entry replaces the value and vice versa
+    cache.insert_negative(1);
+    assert_eq!(CacheResult::NegativeHit, cache.get_result(&1));
+    assert!(!cache.contains_key(&1));
+    cache.insert(3, 4);
+    assert_eq!(CacheResult::Hit(&4), cache.get_result(&3));
+    let stats = cache.stats();
+    assert_eq!(2, stats.negative_hits());
+    assert_eq!(2, stats.hits());
+    assert_eq!(3, stats.misses());
+}
+
+#[test]
+fn lru_builder() {
+    use std::sync::{Arc, Mutex};
+
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let sink = evicted.clone();
+    let mut cache = LRUCache::builder()
+        .capacity(5)
+        .weigher(|_: &u32, v: &u32| *v as usize)
+        .on_evict(move |k, v| sink.lock().unwrap().push((k, v)))
+        .build();
+    cache.insert(1, 2);
+    cache.insert(3, 3);
+    cache.insert(5, 1);
+    assert_eq!(vec![(1, 2)], *evicted.lock().unwrap());
+    assert_eq!(Some(5), cache.capacity());
+
+    let cache: LRUCache<u32, u32, _> = LRUCache::builder()
+        .unbounded()
+        .hasher(DefaultHashBuilder::default())
+        .build();
+    assert_eq!(None, cache.capacity());
+    assert_eq!(Err(CapacityError),
+               LRUCache::<u32, u32>::builder().capacity(0).try_build().map(|_| ()));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn lru_builder_expiry() {
+    use std::time::Duration;
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = LRUCache::builder()
+        .capacity(4)
+        .ttl(Duration::from_secs(10))
+        .tti(Duration::from_secs(5))
+        .clock(clock.clone())
+        .build();
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    clock.advance(Duration::from_secs(4));
+    cache.get(&1);
+    clock.advance(Duration::from_secs(4));
+    assert_eq!(Some(&2), cache.get(&1));
+    assert_eq!(None, cache.get(&3));
+    assert_eq!(Some(Duration::from_secs(10)), cache.ttl());
+}
+
+#[test]
+fn lru_metadata() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.get(&1);
+    cache.get(&1);
+    cache.pin(&3);
+    assert_eq!(None, cache.metadata(&5));
+    assert_eq!(Some(EntryMeta {
+                   inserted: 0,
+                   last_access: 3,
+                   accesses: 2,
+                   recency_rank: 0,
+                   pinned: false,
+                   weight: 1,
+                   protected: false,
+               }),
+               cache.metadata(&1));
+    let meta = cache.metadata(&3).unwrap();
+    assert_eq!((1, 1, 0), (meta.inserted, meta.last_access, meta.accesses));
+    assert_eq!((1, true), (meta.recency_rank, meta.pinned));
+    // metadata() does not promote the entry
+    assert_eq!(Some(&3), cache.least_recent_key());
+}
+
+#[test]
+fn lru_iter_lru_first() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.insert(5, 6);
+    cache.get(&1);
+    assert_eq!(vec![(&3, &4), (&5, &6), (&1, &2)], cache.iter_lru_first().collect::<Vec<_>>());
+    assert_eq!(vec![&3, &5, &1], cache.keys().rev().collect::<Vec<_>>());
+    let mut iter = cache.iter();
+    assert_eq!(Some((&1, &2)), iter.next());
+    assert_eq!(Some((&3, &4)), iter.next_back());
+    assert_eq!(Some((&5, &6)), iter.next());
+    assert_eq!(None, iter.next_back());
+    assert_eq!(Some(&3), cache.least_recent_key());
+}
+
+#[test]
+fn lru_single_hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // counts the number of times that a key is hashed
+    struct Counting(Arc<AtomicUsize>);
+
+    impl BuildHasher for Counting {
+        type Hasher = DefaultHasher;
+
+        fn build_hasher(&self) -> DefaultHasher {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            DefaultHasher::new()
+        }
+    }
+
+    let hashes = Arc::new(AtomicUsize::new(0));
+    let mut cache = LRUCache::with_hasher(2, Counting(hashes.clone()));
+    cache.insert("a".to_string(), 1);
+    cache.insert("b".to_string(), 2);
+    hashes.store(0, Ordering::Relaxed);
+    assert_eq!(Some(("a".to_string(), 1)), cache.insert("c".to_string(), 3).evicted);
+    assert_eq!(1, hashes.load(Ordering::Relaxed));
+    assert_eq!(Some(&2), cache.get("b"));
+    assert_eq!(None, cache.get("a"));
+    assert_eq!(3, hashes.load(Ordering::Relaxed));
+    // growing the table and popping entries reuse the stored hashes
+    cache.reserve(100);
+    cache.pop_lru();
+    assert_eq!(3, hashes.load(Ordering::Relaxed));
 }
@@ -0,0 +1,251 @@
+//! An approximate LRU cache that evicts by sampling, in the manner of
+//! the `allkeys-lru` eviction policy of Redis.
+
+use DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use rng::Rng;
+use stats::Stats;
+
+use super::InsertResult;
+
+// number of entries that are sampled for each eviction by default
+pub const DEFAULT_SAMPLES: usize = 5;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+    // clock instant when the entry was most recently accessed
+    stamp: u64,
+}
+
+// There is no recency order. Each access records the instant of the
+// logical clock in the entry, and an eviction samples a few entries at
+// random and evicts the entry that was accessed least recently. More
+// samples make the eviction more accurate and more expensive.
+pub struct SampledLRUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // number of entries sampled for each eviction
+    samples: usize,
+    // logical clock that is incremented on each access
+    clock: u64,
+    hash_builder: S,
+    // indices of the entries, hashed by key
+    table: HashTable<usize>,
+    // dense storage of the entries, in arbitrary order
+    slots: Vec<Slot<K, V>>,
+    // chooses the entries that are sampled
+    rng: Rng,
+    stats: Stats,
+}
+
+impl<K, V> SampledLRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> SampledLRUCache<K, V> {
+        SampledLRUCache::with_samples(capacity, DEFAULT_SAMPLES)
+    }
+
+    // Panics if the capacity or the number of samples is zero.
+    pub fn with_samples(capacity: usize, samples: usize) -> SampledLRUCache<K, V> {
+        match SampledLRUCache::try_with_hasher(capacity, samples, DefaultHashBuilder::default()) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl<K, V, S> SampledLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the number of samples is zero.
+    pub fn try_with_hasher(capacity: usize,
+                           samples: usize,
+                           hash_builder: S)
+                           -> Result<SampledLRUCache<K, V, S>, CapacityError> {
+        assert!(samples > 0, "number of samples must be nonzero");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(SampledLRUCache {
+            capacity,
+            samples,
+            clock: 0,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            rng: Rng::default(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &slot.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|slot| &mut slot.val)
+    }
+
+    // Read the value without recording an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.slots[index].val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        let stamp = self.tick();
+        if let Some(index) = self.index_of(hash, &key) {
+            let slot = &mut self.slots[index];
+            slot.stamp = stamp;
+            self.stats.replacements += 1;
+            return InsertResult {
+                replaced: Some(mem::replace(&mut slot.val, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.slots.len() >= self.capacity {
+            let index = self.sample();
+            self.stats.evictions += 1;
+            Some(self.take_slot(index))
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.slots.len();
+        self.slots.push(Slot { key, val, hash, stamp });
+        let slots = &self.slots;
+        self.table.insert_unique(hash, index, |&i| slots[i].hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_slot(index).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.slots.clear();
+        self.clock = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Slot<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let stamp = self.tick();
+        let slot = &mut self.slots[index?];
+        slot.stamp = stamp;
+        Some(slot)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.slots[i].key.borrow() == key).copied()
+    }
+
+    fn tick(&mut self) -> u64 {
+        let now = self.clock;
+        self.clock = now.wrapping_add(1);
+        now
+    }
+
+    // Choose the least-recently used entry among the sampled entries.
+    fn sample(&mut self) -> usize {
+        let n = self.slots.len();
+        let mut oldest = self.rng.below(n);
+        for _ in 1..self.samples {
+            let index = self.rng.below(n);
+            if self.slots[index].stamp < self.slots[oldest].stamp {
+                oldest = index;
+            }
+        }
+        oldest
+    }
+
+    // Remove a slot by moving the last slot into its place.
+    fn take_slot(&mut self, index: usize) -> (K, V) {
+        let hash = self.slots[index].hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slots are out of sync")
+            .remove();
+        let slot = self.slots.swap_remove(index);
+        if let Some(moved) = self.slots.get(index) {
+            let last = self.slots.len();
+            *self.table
+                .find_mut(moved.hash, |&i| i == last)
+                .expect("table and slots are out of sync") = index;
+        }
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn sampled_lru_eviction() {
+    // Sampling every entry many times evicts the least-recently used entry.
+    let mut cache = SampledLRUCache::with_samples(4, 64);
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&0);
+    cache.get(&2);
+    assert_eq!(Some((1, 1)), cache.insert(4, 4).evicted);
+    assert_eq!(Some((3, 3)), cache.insert(5, 5).evicted);
+    // The remaining entries are found after the slots were moved.
+    for k in [0, 2, 4, 5].iter() {
+        assert_eq!(Some(k), cache.peek(k));
+    }
+}
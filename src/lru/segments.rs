@@ -0,0 +1,152 @@
+//! The segments of the recency order of an LRUCache.
+
+use list::List;
+
+use super::CacheEntry;
+
+// The segment of a cached entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    // the entries that were accessed since they were inserted or demoted
+    Protected,
+    // the other entries, which are evicted first
+    Probation,
+}
+
+fn segment_of<K, V>(e: &CacheEntry<K, V>) -> Segment {
+    if e.protected {
+        Segment::Protected
+    } else {
+        Segment::Probation
+    }
+}
+
+// The entries are linked in one list that is divided into two
+// consecutive segments: the protected entries at the front and the
+// probationary entries at the back. Each segment is ordered from its
+// most-recently used entry to its least-recently used entry, so the
+// entry that is evicted next is at the back of the list, and the
+// least-recently used protected entry is just in front of the first
+// probationary entry. Pinned entries keep their place in their segment.
+// The segment of an entry is given by its protected flag, which is
+// changed only while the entry is unlinked from its segment. Without a
+// probationary segment every entry is probationary.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Segments {
+    // first entry of the protected segment
+    protected: Option<usize>,
+    // first entry of the probationary segment
+    probation: Option<usize>,
+}
+
+impl Segments {
+    // Insert an entry at the front of its segment. Returns its index.
+    pub(super) fn push_front<K, V>(&mut self,
+                                   entries: &mut List<CacheEntry<K, V>>,
+                                   e: CacheEntry<K, V>)
+                                   -> usize {
+        let segment = segment_of(&e);
+        let index = match self.front_of(segment) {
+            Some(target) => entries.insert_before(target, e),
+            None => entries.push_back(e),
+        };
+        self.set_first(segment, Some(index));
+        index
+    }
+
+    // Move an entry to the front of its segment.
+    pub(super) fn move_to_front<K, V>(&mut self, entries: &mut List<CacheEntry<K, V>>, index: usize) {
+        self.unlink(entries, index);
+        self.link_front(entries, index);
+    }
+
+    pub(super) fn remove<K, V>(&mut self,
+                               entries: &mut List<CacheEntry<K, V>>,
+                               index: usize)
+                               -> CacheEntry<K, V> {
+        self.unlink(entries, index);
+        entries.remove(index)
+    }
+
+    // Release an entry from its segment before its flags are changed.
+    // The entry keeps its position until it is linked again.
+    pub(super) fn unlink<K, V>(&mut self, entries: &List<CacheEntry<K, V>>, index: usize) {
+        let segment = segment_of(entries.get(index));
+        if self.first(segment) == Some(index) {
+            let next = entries.next(index).filter(|&next| segment_of(entries.get(next)) == segment);
+            self.set_first(segment, next);
+        }
+    }
+
+    // Link an entry that was unlinked at the front of the segment of its flags.
+    pub(super) fn link_front<K, V>(&mut self, entries: &mut List<CacheEntry<K, V>>, index: usize) {
+        let segment = segment_of(entries.get(index));
+        match self.front_of(segment) {
+            Some(target) => entries.move_before(index, target),
+            None => entries.move_to_back(index),
+        }
+        self.set_first(segment, Some(index));
+    }
+
+    // Link an entry that was unlinked at the back of the segment of its flags.
+    pub(super) fn link_back<K, V>(&mut self, entries: &mut List<CacheEntry<K, V>>, index: usize) {
+        let segment = segment_of(entries.get(index));
+        match self.after(segment) {
+            Some(target) => entries.move_before(index, target),
+            None => entries.move_to_back(index),
+        }
+        if self.first(segment).is_none() {
+            self.set_first(segment, Some(index));
+        }
+    }
+
+    // The least-recently used protected entry.
+    pub(super) fn protected_back<K, V>(&self, entries: &List<CacheEntry<K, V>>) -> Option<usize> {
+        let index = match self.probation {
+            Some(first) => entries.prev(first)?,
+            None => entries.back()?,
+        };
+        Some(index).filter(|&index| segment_of(entries.get(index)) == Segment::Protected)
+    }
+
+    // Locate the segments of a list whose entries are in segment order.
+    pub(super) fn rebuild<K, V>(entries: &List<CacheEntry<K, V>>) -> Segments {
+        let mut segments = Segments::default();
+        for (index, e) in entries.iter().rev() {
+            segments.set_first(segment_of(e), Some(index));
+        }
+        segments
+    }
+
+    // The entry in front of which an entry is inserted at the front of a
+    // segment: the first entry of the segment, or of the segments behind
+    // it if it is empty. None if the entry goes at the back of the list.
+    fn front_of(&self, segment: Segment) -> Option<usize> {
+        match segment {
+            Segment::Protected => self.protected.or(self.probation),
+            Segment::Probation => self.probation,
+        }
+    }
+
+    // The first entry of the segments behind a segment.
+    fn after(&self, segment: Segment) -> Option<usize> {
+        match segment {
+            Segment::Protected => self.probation,
+            Segment::Probation => None,
+        }
+    }
+
+    fn first(&self, segment: Segment) -> Option<usize> {
+        match segment {
+            Segment::Protected => self.protected,
+            Segment::Probation => self.probation,
+        }
+    }
+
+    fn set_first(&mut self, segment: Segment, index: Option<usize>) {
+        match segment {
+            Segment::Protected => self.protected = index,
+            Segment::Probation => self.probation = index,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Serialization of an LRUCache that preserves the recency order of its entries.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use serde::Deserialize;
+use serde::de::{Deserializer, Error};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use super::LRUCache;
+
+// The entries are serialized from the most-recently used
+// entry to the least-recently used entry.
+struct Entries<'a, K: 'a + Eq + Hash, V: 'a, S: 'a>(&'a LRUCache<K, V, S>);
+
+impl<'a, K, V, S> Serialize for Entries<'a, K, V, S>
+    where K: Eq + Hash + Serialize,
+          V: Serialize,
+          S: BuildHasher
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+impl<K, V, S> Serialize for LRUCache<K, V, S>
+    where K: Eq + Hash + Serialize,
+          V: Serialize,
+          S: BuildHasher
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        let mut state = serializer.serialize_struct("LRUCache", 2)?;
+        state.serialize_field("capacity", &self.capacity)?;
+        state.serialize_field("entries", &Entries(self))?;
+        state.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "LRUCache")]
+struct Repr<K, V> {
+    capacity: Option<usize>,
+    entries: Vec<(K, V)>,
+}
+
+impl<'de, K, V, S> Deserialize<'de> for LRUCache<K, V, S>
+    where K: Eq + Hash + Deserialize<'de>,
+          V: Deserialize<'de>,
+          S: BuildHasher + Default
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = Repr::deserialize(deserializer)?;
+        let mut cache = match repr.capacity {
+            Some(capacity) => {
+                LRUCache::try_with_hasher(capacity, S::default()).map_err(D::Error::custom)?
+            }
+            None => LRUCache::unbounded_with_hasher(S::default()),
+        };
+        // Insert the least-recently used entry first
+        // to restore the recency order.
+        cache.insert_many(repr.entries.into_iter().rev());
+        Ok(cache)
+    }
+}
+
+#[test]
+fn lru_serde() {
+    let mut cache = LRUCache::new(3);
+    cache.insert(1, "a".to_string());
+    cache.insert(3, "b".to_string());
+    cache.insert(5, "c".to_string());
+    cache.get(&1);
+    let json = ::serde_json::to_string(&cache).unwrap();
+    assert_eq!(r#"{"capacity":3,"entries":[[1,"a"],[5,"c"],[3,"b"]]}"#, json);
+
+    let mut copy: LRUCache<i32, String> = ::serde_json::from_str(&json).unwrap();
+    assert!(cache.ordered_eq(&copy));
+    assert_eq!(Some(3), copy.capacity());
+    copy.insert(7, "d".to_string());
+    assert!(!copy.contains_key(&3));
+
+    let err = ::serde_json::from_str::<LRUCache<i32, String>>(r#"{"capacity":0,"entries":[]}"#);
+    assert!(err.is_err());
+}
@@ -0,0 +1,249 @@
+//! An LRUCache that is partitioned into independently locked shards.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use error::CapacityError;
+use stats::StatsSnapshot;
+
+use super::{InsertResult, LRUCache};
+
+// Each key belongs to one shard, so threads that access keys in
+// different shards do not contend for a lock. The recency order
+// and the capacity are maintained separately by each shard, so the
+// entry that is evicted is the least-recently used entry of its shard.
+pub struct ShardedLRUCache<K: Eq + Hash, V, S = DefaultHashBuilder> {
+    // selects the shard of a key
+    hash_builder: S,
+    shards: Box<[Mutex<LRUCache<K, V, S>>]>,
+}
+
+// Four shards for each thread that can run in parallel.
+pub(super) fn default_shards() -> usize {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    (threads * 4).next_power_of_two()
+}
+
+impl<K, V> ShardedLRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> ShardedLRUCache<K, V> {
+        ShardedLRUCache::with_shards(capacity, default_shards())
+    }
+
+    // Panics if the capacity or the number of shards is zero.
+    pub fn with_shards(capacity: usize, shards: usize) -> ShardedLRUCache<K, V> {
+        ShardedLRUCache::with_shards_and_hasher(capacity, shards, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ShardedLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher + Clone
+{
+    // The capacity is divided evenly between the shards, rounding up.
+    // There are never more shards than the capacity.
+    // Panics if the capacity or the number of shards is zero.
+    pub fn with_shards_and_hasher(capacity: usize,
+                                  shards: usize,
+                                  hash_builder: S)
+                                  -> ShardedLRUCache<K, V, S> {
+        match ShardedLRUCache::try_with_shards_and_hasher(capacity, shards, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the number of shards is zero.
+    pub fn try_with_shards_and_hasher(capacity: usize,
+                                      shards: usize,
+                                      hash_builder: S)
+                                      -> Result<ShardedLRUCache<K, V, S>, CapacityError> {
+        assert!(shards > 0, "number of shards must be nonzero");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        let shards = shards.min(capacity);
+        let per_shard = capacity.div_ceil(shards);
+        let shards = (0..shards)
+            .map(|_| Mutex::new(LRUCache::with_hasher(per_shard, hash_builder.clone())))
+            .collect();
+        Ok(ShardedLRUCache { hash_builder, shards })
+    }
+}
+
+impl<K, V, S> ShardedLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Lock the shard of a key. A panic while a shard is locked,
+    // such as in a listener, leaves the shard consistent, so the
+    // lock of a poisoned shard is recovered.
+    fn shard<Q>(&self, key: &Q) -> MutexGuard<'_, LRUCache<K, V, S>>
+        where Q: ?Sized + Hash
+    {
+        let hash = self.hash_builder.hash_one(key);
+        lock(&self.shards[shard_index(hash, self.shards.len())])
+    }
+
+    // Values are cloned out of the shard so that the lock
+    // is not held by the caller.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        self.shard(key).get(key).cloned()
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        self.shard(key).peek(key).cloned()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.shard(key).contains_key(key)
+    }
+
+    pub fn insert(&self, key: K, val: V) -> InsertResult<K, V> {
+        self.shard(&key).insert(key, val)
+    }
+
+    // The shard is locked while the value is computed,
+    // so the value of a key is computed at most once.
+    pub fn get_or_insert_with<F>(&self, key: K, f: F) -> V
+        where F: FnOnce() -> V,
+              V: Clone
+    {
+        self.shard(&key).get_or_insert_with(key, f).clone()
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.shard(key).remove(key)
+    }
+
+    // The shards are cleared one at a time.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            lock(shard).clear();
+        }
+    }
+
+    // The shards are locked one at a time, so the result may not
+    // reflect concurrent operations.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+
+    // Total capacity of the shards.
+    pub fn capacity(&self) -> usize {
+        self.shards.iter().filter_map(|shard| lock(shard).capacity()).sum()
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    // Sum of the counters of the shards.
+    pub fn stats(&self) -> StatsSnapshot {
+        self.shards.iter().map(|shard| lock(shard).stats().snapshot()).sum()
+    }
+
+    // Set the counters of the shards to zero and return the sum
+    // of their previous values.
+    pub fn reset_stats(&self) -> StatsSnapshot {
+        self.shards.iter().map(|shard| lock(shard).reset_stats()).sum()
+    }
+}
+
+// The shard of a hash is chosen by its high bits. The tables of the
+// shards use the same hasher and probe by the low bits, which would
+// be shared by all the keys of a shard if they chose it.
+pub(super) fn shard_index(hash: u64, shards: usize) -> usize {
+    ((hash as u128 * shards as u128) >> 64) as usize
+}
+
+pub(super) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn sharded_lru_cache() {
+    // Each shard can hold every key of the test, whichever shards they belong to.
+    let cache = ShardedLRUCache::with_shards(12, 4);
+    assert_eq!((4, 12), (cache.shards(), cache.capacity()));
+    assert!(cache.is_empty());
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some(2), cache.get(&1));
+    assert_eq!(None, cache.get(&5));
+    assert_eq!(Some(4), cache.peek(&3));
+    assert_eq!(4, cache.get_or_insert_with(3, || 6));
+    assert_eq!(6, cache.get_or_insert_with(5, || 6));
+    assert_eq!(3, cache.len());
+    assert_eq!(Some(2), cache.remove(&1));
+    assert!(!cache.contains_key(&1));
+    let stats = cache.reset_stats();
+    assert_eq!((2, 2, 3), (stats.hits, stats.misses, stats.insertions));
+    assert_eq!(StatsSnapshot::default(), cache.stats());
+    cache.clear();
+    assert_eq!(0, cache.len());
+}
+
+#[test]
+fn sharded_lru_capacity() {
+    // There are never more shards than entries.
+    let cache: ShardedLRUCache<i32, i32> = ShardedLRUCache::with_shards(3, 8);
+    assert_eq!((3, 3), (cache.shards(), cache.capacity()));
+    let cache: ShardedLRUCache<i32, i32> = ShardedLRUCache::with_shards(10, 4);
+    assert_eq!(12, cache.capacity());
+    let err = ShardedLRUCache::<i32, i32>::try_with_shards_and_hasher(0, 4,
+                                                                 DefaultHashBuilder::default());
+    assert!(err.is_err());
+}
+
+#[test]
+fn sharded_lru_distribution() {
+    // The keys of a shard do not share the low bits of their hashes.
+    let cache: ShardedLRUCache<u32, u32> = ShardedLRUCache::with_shards(1024, 8);
+    let hashes: Vec<u64> = (0..1000)
+        .map(|k| cache.hash_builder.hash_one(k))
+        .filter(|&hash| shard_index(hash, 8) == 0)
+        .collect();
+    assert!(hashes.len() > 50);
+    assert!(hashes.iter().any(|&hash| hash & 7 != hashes[0] & 7));
+    assert_eq!(vec![0, 3, 7], vec![shard_index(0, 8), shard_index(u64::MAX / 2, 7), shard_index(u64::MAX, 8)]);
+}
+
+#[test]
+fn sharded_lru_threads() {
+    let cache = ShardedLRUCache::with_shards(1000, 8);
+    std::thread::scope(|s| {
+        for t in 0..4 {
+            let cache = &cache;
+            s.spawn(move || {
+                for i in 0..100 {
+                    cache.insert(t * 100 + i, i);
+                    assert_eq!(Some(i), cache.get(&(t * 100 + i)));
+                }
+            });
+        }
+    });
+    assert_eq!(400, cache.len());
+    assert_eq!(400, cache.stats().hits);
+}
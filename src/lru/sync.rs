@@ -0,0 +1,153 @@
+//! A thread-safe LRUCache that is shared by reference.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use error::CapacityError;
+use stats::StatsSnapshot;
+
+use super::{InsertResult, LRUCache};
+use super::sharded::lock;
+
+// Every operation locks the whole cache, because lookups update the
+// recency order. A panic while the cache is locked, such as in a
+// listener, leaves the cache consistent, so the lock of a poisoned
+// cache is recovered instead of propagating the panic.
+pub struct SyncLRUCache<K: Eq + Hash, V, S = DefaultHashBuilder> {
+    inner: Mutex<LRUCache<K, V, S>>,
+}
+
+impl<K, V> SyncLRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> SyncLRUCache<K, V> {
+        SyncLRUCache::from(LRUCache::new(capacity))
+    }
+
+    pub fn try_new(capacity: usize) -> Result<SyncLRUCache<K, V>, CapacityError> {
+        LRUCache::try_new(capacity).map(SyncLRUCache::from)
+    }
+}
+
+impl<K, V, S> SyncLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Values are cloned out of the cache so that the lock
+    // is not held by the caller.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        self.lock().get(key).cloned()
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq,
+              V: Clone
+    {
+        self.lock().peek(key).cloned()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lock().contains_key(key)
+    }
+
+    pub fn insert(&self, key: K, val: V) -> InsertResult<K, V> {
+        self.lock().insert(key, val)
+    }
+
+    // The cache is locked while the value is computed,
+    // so the value of a key is computed at most once.
+    pub fn get_or_insert_with<F>(&self, key: K, f: F) -> V
+        where F: FnOnce() -> V,
+              V: Clone
+    {
+        self.lock().get_or_insert_with(key, f).clone()
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lock().remove(key)
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.lock().capacity()
+    }
+
+    pub fn stats(&self) -> StatsSnapshot {
+        self.lock().stats().snapshot()
+    }
+
+    // Lock the cache for the operations that are not provided
+    // by the wrapper, or for several operations at once.
+    pub fn lock(&self) -> MutexGuard<'_, LRUCache<K, V, S>> {
+        lock(&self.inner)
+    }
+
+    pub fn into_inner(self) -> LRUCache<K, V, S> {
+        self.inner.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K, V, S> From<LRUCache<K, V, S>> for SyncLRUCache<K, V, S>
+    where K: Eq + Hash
+{
+    fn from(cache: LRUCache<K, V, S>) -> Self {
+        SyncLRUCache { inner: Mutex::new(cache) }
+    }
+}
+
+#[test]
+fn sync_lru_cache() {
+    let cache = SyncLRUCache::new(2);
+    assert!(cache.is_empty());
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    assert_eq!(Some(2), cache.get(&1));
+    assert_eq!(Some(4), cache.peek(&3));
+    assert_eq!(Some((3, 4)), cache.insert(5, 6).evicted);
+    assert_eq!(6, cache.get_or_insert_with(5, || 7));
+    assert_eq!(Some(6), cache.remove(&5));
+    assert!(!cache.contains_key(&5));
+    assert_eq!((1, Some(2)), (cache.len(), cache.capacity()));
+    assert_eq!(2, cache.stats().hits);
+    assert_eq!(Some((1, 2)), cache.lock().pop_lru());
+    cache.clear();
+    assert!(cache.into_inner().is_empty());
+    assert!(SyncLRUCache::<i32, i32>::try_new(0).is_err());
+}
+
+#[test]
+fn sync_lru_poisoned() {
+    let cache = SyncLRUCache::new(2);
+    cache.lock().set_listener(|_, _, _| panic!("listener"));
+    cache.insert(1, 2);
+    std::thread::scope(|s| {
+        assert!(s.spawn(|| cache.remove(&1)).join().is_err());
+    });
+    // The entry was removed before the listener panicked.
+    assert_eq!(None, cache.get(&1));
+    assert_eq!(0, cache.len());
+}
@@ -0,0 +1,141 @@
+//! A hierarchical timer wheel that schedules items by their deadline tick.
+//! Items are removed when the wheel advances past their tick, so that the
+//! cost of advancing is proportional to the number of items that are due.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+
+// Each level of the wheel has 2^SLOT_BITS slots.
+const SLOT_BITS: u32 = 6;
+const SLOTS: u64 = 1 << SLOT_BITS;
+// A slot at level n spans 2^(n * SLOT_BITS) ticks.
+const LEVELS: usize = 6;
+
+#[derive(Debug, Clone)]
+pub(super) struct TimerWheel<T> {
+    // tick that the wheel has advanced to
+    now: u64,
+    // Level n holds the items whose tick is within SLOTS
+    // slots of level n from now, and not within level n - 1.
+    levels: Vec<Vec<Vec<(u64, T)>>>,
+    // items that are beyond the range of the top level
+    overflow: Vec<(u64, T)>,
+}
+
+impl<T> TimerWheel<T> {
+    pub(super) fn new() -> TimerWheel<T> {
+        TimerWheel {
+            now: 0,
+            levels: (0..LEVELS).map(|_| (0..SLOTS).map(|_| Vec::new()).collect()).collect(),
+            overflow: Vec::new(),
+        }
+    }
+
+    // Items with a tick in the past are due on the next advance.
+    pub(super) fn insert(&mut self, tick: u64, item: T) {
+        let at = cmp::max(tick, self.now);
+        for (level, slots) in self.levels.iter_mut().enumerate() {
+            let shift = level as u32 * SLOT_BITS;
+            if (at >> shift) - (self.now >> shift) < SLOTS {
+                let slot = ((at >> shift) % SLOTS) as usize;
+                slots[slot].push((tick, item));
+                return;
+            }
+        }
+        self.overflow.push((tick, item));
+    }
+
+    // Remove and return the items with a tick of at most now.
+    // The items in the coarser slots that are passed over
+    // and are not yet due move down to the finer levels.
+    pub(super) fn advance(&mut self, now: u64) -> Vec<(u64, T)> {
+        let now = cmp::max(now, self.now);
+        let mut due = Vec::new();
+        let mut pending = Vec::new();
+        for (level, slots) in self.levels.iter_mut().enumerate() {
+            let shift = level as u32 * SLOT_BITS;
+            let start = self.now >> shift;
+            let count = cmp::min((now >> shift) - start + 1, SLOTS);
+            for i in 0..count {
+                let slot = ((start + i) % SLOTS) as usize;
+                for (tick, item) in slots[slot].drain(..) {
+                    if tick <= now {
+                        due.push((tick, item));
+                    } else {
+                        pending.push((tick, item));
+                    }
+                }
+            }
+        }
+        for (tick, item) in self.overflow.drain(..) {
+            if tick <= now {
+                due.push((tick, item));
+            } else {
+                pending.push((tick, item));
+            }
+        }
+        self.now = now;
+        for (tick, item) in pending {
+            self.insert(tick, item);
+        }
+        due
+    }
+
+    pub(super) fn clear(&mut self) {
+        for slots in &mut self.levels {
+            for slot in slots.iter_mut() {
+                slot.clear();
+            }
+        }
+        self.overflow.clear();
+    }
+
+    // Bytes allocated for the slots of the wheel and the scheduled items.
+    pub(super) fn heap_size(&self) -> usize {
+        let slots: usize = self.levels
+            .iter()
+            .map(|slots| {
+                slots.capacity() * mem::size_of::<Vec<(u64, T)>>() +
+                slots.iter().map(|slot| slot.capacity()).sum::<usize>() * mem::size_of::<(u64, T)>()
+            })
+            .sum();
+        self.levels.capacity() * mem::size_of::<Vec<Vec<(u64, T)>>>() + slots +
+        self.overflow.capacity() * mem::size_of::<(u64, T)>()
+    }
+}
+
+#[test]
+fn wheel_advance() {
+    let mut wheel = TimerWheel::new();
+    wheel.insert(5, 'a');
+    wheel.insert(70, 'b');
+    wheel.insert(5000, 'c');
+    wheel.insert(1 << 40, 'd');
+    assert!(wheel.advance(4).is_empty());
+    assert_eq!(vec![(5, 'a')], wheel.advance(5));
+    assert_eq!(vec![(70, 'b')], wheel.advance(100));
+    // an item in the past is due immediately
+    wheel.insert(50, 'e');
+    assert_eq!(vec![(50, 'e')], wheel.advance(100));
+    assert!(wheel.advance(4999).is_empty());
+    assert_eq!(vec![(5000, 'c')], wheel.advance(1 << 20));
+    assert_eq!(vec![(1 << 40, 'd')], wheel.advance(1 << 41));
+}
+
+#[test]
+fn wheel_many() {
+    let mut wheel = TimerWheel::new();
+    for tick in (0..100_000).rev() {
+        wheel.insert(tick * 7 + 1, tick);
+    }
+    let mut seen = 0;
+    let mut now = 0;
+    while seen < 100_000 {
+        now += 1234;
+        let due = wheel.advance(now);
+        assert!(due.iter().all(|&(tick, _)| tick <= now && tick + 1234 > now));
+        seen += due.len();
+    }
+    assert!(wheel.advance(u64::MAX >> 1).is_empty());
+}
@@ -0,0 +1,380 @@
+//! LRU-K cache, as described by O'Neil, O'Neil and Weikum in "The LRU-K
+//! Page Replacement Algorithm For Database Disk Buffering" (SIGMOD 1993).
+
+use DefaultHashBuilder;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// default number of references that are tracked for each key
+pub const DEFAULT_K: usize = 2;
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // times of the last K references, from the oldest to the newest
+    history: Vec<u64>,
+}
+
+// The references of a key that was evicted.
+struct Ghost {
+    hash: u64,
+    history: Vec<u64>,
+    // time of the eviction
+    evicted: u64,
+}
+
+// Time is counted in references: every hit and every insertion advances
+// the clock by one. Eviction takes the entry whose K-th most recent
+// reference is the oldest. Entries with fewer than K references are
+// evicted first, in LRU order, so that a key must be referenced K times
+// within the span of the cache to compete with the keys that are in use.
+// With K = 1 the cache is an LRU cache.
+//
+// The references of an evicted key are retained for the retention
+// window, counted in references, so that a key that returns shortly
+// after its eviction keeps its history. Like the ghosts of ARCCache,
+// the retained histories record only the hashes of the keys.
+pub struct LRUKCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // number of references that are tracked for each key
+    k: usize,
+    // number of references for which the history of an evicted key is kept
+    retention: u64,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries, in arbitrary order
+    entries: List<Entry<K, V>>,
+    // indices of the entries ordered by their K-th most recent reference,
+    // then by their most recent reference
+    order: BTreeMap<(u64, u64), usize>,
+    // indices of the retained histories, hashed by key
+    ghosts: HashTable<usize>,
+    // retained histories from the newest eviction at the front
+    // to the oldest eviction at the back
+    history: List<Ghost>,
+    // time of the last reference
+    clock: u64,
+    stats: Stats,
+}
+
+impl<K, V> LRUKCache<K, V>
+    where K: Eq + Hash
+{
+    // The history of an evicted key is retained for as many
+    // references as the cache has entries.
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> LRUKCache<K, V> {
+        LRUKCache::with_k(capacity, DEFAULT_K, capacity as u64)
+    }
+
+    // Panics if the capacity or k is zero.
+    pub fn with_k(capacity: usize, k: usize, retention: u64) -> LRUKCache<K, V> {
+        LRUKCache::with_hasher(capacity, k, retention, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<LRUKCache<K, V>, CapacityError> {
+        LRUKCache::try_with_hasher(capacity, DEFAULT_K, capacity as u64, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> LRUKCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity or k is zero.
+    pub fn with_hasher(capacity: usize, k: usize, retention: u64, hash_builder: S) -> LRUKCache<K, V, S> {
+        match LRUKCache::try_with_hasher(capacity, k, retention, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if k is zero.
+    pub fn try_with_hasher(capacity: usize,
+                           k: usize,
+                           retention: u64,
+                           hash_builder: S)
+                           -> Result<LRUKCache<K, V, S>, CapacityError> {
+        assert!(k > 0, "k must be positive");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(LRUKCache {
+            capacity,
+            k,
+            retention,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            order: BTreeMap::new(),
+            ghosts: HashTable::new(),
+            history: List::new(),
+            clock: 0,
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without counting a reference.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Number of references of a key that are tracked, at most K.
+    pub fn references<Q>(&self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| self.entries.get(index).history.len())
+    }
+
+    // Replacing the value of a key counts as a reference.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.stats.replacements += 1;
+            self.reference(index);
+            let prev = mem::replace(&mut self.entries.get_mut(index).val, val);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        self.clock += 1;
+        self.expire();
+        let mut history = self.take_ghost(hash).unwrap_or_default();
+        self.record(&mut history);
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.evict()
+        } else {
+            None
+        };
+        let rank = rank(&history, self.k);
+        let index = self.entries.push_front(Entry {
+            key,
+            val,
+            hash,
+            history,
+        });
+        self.order.insert(rank, index);
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    // The history of a removed key is not retained.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_entry(index).val)
+    }
+
+    // The entry that is evicted next.
+    pub fn peek_victim(&self) -> Option<(&K, &V)> {
+        self.order.values().next().map(|&index| {
+            let e = self.entries.get(index);
+            (&e.key, &e.val)
+        })
+    }
+
+    // The retained histories are cleared as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+        self.order.clear();
+        self.ghosts.clear();
+        self.history.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    // Number of evicted keys whose history is retained.
+    pub fn ghosts(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.reference(index);
+        Some(index)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Count a reference to a cached entry and reorder it.
+    fn reference(&mut self, index: usize) {
+        self.clock += 1;
+        let mut history = mem::take(&mut self.entries.get_mut(index).history);
+        self.order.remove(&rank(&history, self.k));
+        self.record(&mut history);
+        self.order.insert(rank(&history, self.k), index);
+        self.entries.get_mut(index).history = history;
+    }
+
+    // Add the current time to a history, keeping the last K references.
+    fn record(&self, history: &mut Vec<u64>) {
+        if history.len() == self.k {
+            history.remove(0);
+        }
+        history.push(self.clock);
+    }
+
+    // Evict the entry with the oldest K-th most recent reference
+    // and retain its history.
+    fn evict(&mut self) -> Option<(K, V)> {
+        let (_, &index) = self.order.iter().next()?;
+        let e = self.take_entry(index);
+        if self.retention > 0 {
+            let ghost = Ghost {
+                hash: e.hash,
+                history: e.history,
+                evicted: self.clock,
+            };
+            let index = self.history.push_front(ghost);
+            let history = &self.history;
+            self.ghosts.insert_unique(e.hash, index, |&i| history.get(i).hash);
+        }
+        Some((e.key, e.val))
+    }
+
+    // Forget the histories that were retained longer than the window.
+    fn expire(&mut self) {
+        while let Some(index) = self.history.back() {
+            let (hash, evicted) = {
+                let ghost = self.history.get(index);
+                (ghost.hash, ghost.evicted)
+            };
+            if self.clock - evicted <= self.retention {
+                break;
+            }
+            self.ghosts
+                .find_entry(hash, |&i| i == index)
+                .expect("ghost table and histories are out of sync")
+                .remove();
+            self.history.remove(index);
+        }
+    }
+
+    // Remove the retained history of a hash.
+    fn take_ghost(&mut self, hash: u64) -> Option<Vec<u64>> {
+        let history = &self.history;
+        let (index, _) = self.ghosts.find_entry(hash, |&i| history.get(i).hash == hash).ok()?.remove();
+        Some(self.history.remove(index).history)
+    }
+
+    // Remove the entry with the given index from the table, the order and the slab.
+    fn take_entry(&mut self, index: usize) -> Entry<K, V> {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        self.order.remove(&rank(&self.entries.get(index).history, self.k));
+        self.entries.remove(index)
+    }
+}
+
+// The eviction order of a history: the K-th most recent reference, which
+// is zero with fewer than K references, then the most recent reference.
+// Every reference has a distinct time, so the ranks of the entries are
+// distinct.
+fn rank(history: &[u64], k: usize) -> (u64, u64) {
+    let kth = if history.len() == k { history[0] } else { 0 };
+    (kth, history.last().copied().unwrap_or(0))
+}
+
+#[test]
+fn lruk_history() {
+    let mut cache = LRUKCache::with_k(2, 2, 4);
+    assert_eq!(2, cache.k());
+    cache.insert("a", 1);
+    cache.get("a");
+    cache.insert("b", 2);
+    // b has a single reference, so it is evicted before a,
+    // although a is less recently used.
+    assert_eq!(Some((&"b", &2)), cache.peek_victim());
+    assert_eq!(Some(("b", 2)), cache.insert("c", 3).evicted);
+    // b returns within the retention window and keeps its history.
+    assert_eq!(Some(("c", 3)), cache.insert("b", 4).evicted);
+    assert_eq!((Some(2), 1), (cache.references("b"), cache.ghosts()));
+    for _ in 0..2 {
+        cache.get("a");
+        cache.get("b");
+    }
+    // The history of c has expired.
+    assert_eq!(Some(("a", 1)), cache.insert("c", 5).evicted);
+    assert_eq!((Some(1), 1), (cache.references("c"), cache.ghosts()));
+}
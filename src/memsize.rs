@@ -0,0 +1,116 @@
+//! Approximate memory usage of keys and values.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem;
+
+// A type that can report the number of bytes it occupies.
+// The sizes are approximate: allocator overhead is not counted
+// and shared allocations are counted once for every owner.
+pub trait MemSize {
+    // bytes allocated on the heap that are owned by the value
+    fn heap_size(&self) -> usize;
+
+    // bytes occupied by the value, including its inline representation
+    fn mem_size(&self) -> usize {
+        mem::size_of_val(self) + self.heap_size()
+    }
+}
+
+macro_rules! inline_mem_size {
+    ($($t:ty),*) => {
+        $(
+            impl MemSize for $t {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    }
+}
+
+inline_mem_size!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, ());
+
+impl MemSize for &'static str {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl MemSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: MemSize> MemSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        // The unused capacity is counted at the inline size of the elements.
+        let unused = self.capacity() - self.len();
+        self.iter().map(MemSize::mem_size).sum::<usize>() + unused * mem::size_of::<T>()
+    }
+}
+
+impl<T: MemSize> MemSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        T::mem_size(self)
+    }
+}
+
+impl<T: MemSize> MemSize for Rc<T> {
+    fn heap_size(&self) -> usize {
+        // strong and weak reference counts
+        2 * mem::size_of::<usize>() + T::mem_size(self)
+    }
+}
+
+impl<T: MemSize> MemSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        // strong and weak reference counts
+        2 * mem::size_of::<usize>() + T::mem_size(self)
+    }
+}
+
+impl<T: MemSize> MemSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, MemSize::heap_size)
+    }
+}
+
+impl<T: MemSize, const N: usize> MemSize for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(MemSize::heap_size).sum()
+    }
+}
+
+impl<A: MemSize, B: MemSize> MemSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl<A: MemSize, B: MemSize, C: MemSize> MemSize for (A, B, C) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size() + self.2.heap_size()
+    }
+}
+
+#[test]
+fn memsize_std_types() {
+    assert_eq!(8, 5u64.mem_size());
+    assert_eq!(0, 5u64.heap_size());
+    let s = String::with_capacity(10);
+    assert_eq!(10, s.heap_size());
+    assert_eq!(mem::size_of::<String>() + 10, s.mem_size());
+    let mut v: Vec<u32> = Vec::with_capacity(4);
+    v.push(1);
+    assert_eq!(16, v.heap_size());
+    let v = vec![String::with_capacity(3)];
+    assert_eq!(mem::size_of::<String>() + 3, v.heap_size());
+    assert_eq!(4, Box::new(1u32).heap_size());
+    assert_eq!(0, None::<String>.heap_size());
+    assert_eq!(7, (String::with_capacity(7), 1u8).heap_size());
+}
@@ -0,0 +1,400 @@
+//! Multi-Queue cache, as described by Zhou, Philbin and Li in "The
+//! Multi-Queue Replacement Algorithm for Second Level Buffer Caches"
+//! (USENIX ATC 2001).
+
+use DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// default number of LRU queues
+pub const DEFAULT_QUEUES: usize = 8;
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // number of accesses, counting the accesses before an eviction
+    // if the key was still remembered when it returned
+    freq: u64,
+    // time after which the entry is demoted if it is not accessed
+    expire: u64,
+}
+
+// The frequency of a key that was evicted.
+struct Ghost {
+    hash: u64,
+    freq: u64,
+}
+
+// An entry that has been accessed f times is in the LRU queue number
+// log2(f), up to the last queue, and eviction takes the least-recently
+// used entry of the lowest queue that is not empty. Time is counted in
+// accesses: an entry that is not accessed for the lifetime is demoted to
+// the next lower queue, so that entries that were popular once do not
+// stay in the cache forever. Only the least-recently used entry of each
+// queue is checked for demotion, once per access.
+//
+// The history, Qout, remembers the frequencies of the evicted keys, so
+// that a key that returns before it is forgotten resumes from its
+// frequency. It holds up to four times the capacity, and records only
+// the hashes of the keys.
+pub struct MQCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // number of accesses after which an entry that is not accessed is demoted
+    lifetime: u64,
+    hash_builder: S,
+    // locations of the entries in the queues, hashed by key
+    table: HashTable<(usize, usize)>,
+    // the queues, each from the most-recently used entry at the front
+    // to the least-recently used entry at the back
+    queues: Vec<List<Entry<K, V>>>,
+    // indices of the ghosts in the history, hashed by key
+    ghosts: HashTable<usize>,
+    // Qout from the most recently evicted key at the front
+    history: List<Ghost>,
+    // time of the last access
+    clock: u64,
+    stats: Stats,
+}
+
+impl<K, V> MQCache<K, V>
+    where K: Eq + Hash
+{
+    // The lifetime is the capacity.
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> MQCache<K, V> {
+        MQCache::with_queues(capacity, DEFAULT_QUEUES, capacity as u64)
+    }
+
+    // Panics if the capacity or the number of queues is zero.
+    pub fn with_queues(capacity: usize, queues: usize, lifetime: u64) -> MQCache<K, V> {
+        MQCache::with_hasher(capacity, queues, lifetime, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<MQCache<K, V>, CapacityError> {
+        MQCache::try_with_hasher(capacity, DEFAULT_QUEUES, capacity as u64, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> MQCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity or the number of queues is zero.
+    pub fn with_hasher(capacity: usize, queues: usize, lifetime: u64, hash_builder: S) -> MQCache<K, V, S> {
+        match MQCache::try_with_hasher(capacity, queues, lifetime, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the number of queues is zero.
+    pub fn try_with_hasher(capacity: usize,
+                           queues: usize,
+                           lifetime: u64,
+                           hash_builder: S)
+                           -> Result<MQCache<K, V, S>, CapacityError> {
+        assert!(queues > 0, "there must be at least one queue");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(MQCache {
+            capacity,
+            lifetime,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            queues: (0..queues).map(|_| List::new()).collect(),
+            ghosts: HashTable::new(),
+            history: List::new(),
+            clock: 0,
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(queue, index)| &self.queues[queue].get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // The number of the queue of the entry of a key, from zero
+    // for the queue that is evicted first.
+    pub fn queue<Q>(&self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(queue, _)| queue)
+    }
+
+    // Number of accesses of the entry of a key.
+    pub fn frequency<Q>(&self, key: &Q) -> Option<u64>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(queue, index)| self.queues[queue].get(index).freq)
+    }
+
+    // Replacing the value of a key counts as an access.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(loc) = self.locate(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.access(loc);
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        self.clock += 1;
+        let freq = self.take_ghost(hash).unwrap_or(0) + 1;
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.evict()
+        } else {
+            None
+        };
+        let queue = self.queue_of(freq);
+        let index = self.queues[queue].push_front(Entry {
+            key,
+            val,
+            hash,
+            freq,
+            expire: self.clock.saturating_add(self.lifetime),
+        });
+        let queues = &self.queues;
+        self.table.insert_unique(hash, (queue, index), |&(q, i)| queues[q].get(i).hash);
+        self.adjust();
+        InsertResult { replaced: None, evicted }
+    }
+
+    // The frequency of a removed key is not remembered.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // The history is cleared as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        for queue in &mut self.queues {
+            queue.clear();
+        }
+        self.ghosts.clear();
+        self.history.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(List::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Number of evicted keys whose frequency is remembered.
+    pub fn ghosts(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        Some(self.access(loc?))
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(usize, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(queue, i)| self.queues[queue].get(i).key.borrow() == key)
+            .copied()
+    }
+
+    // The queue of the entries that were accessed freq times.
+    fn queue_of(&self, freq: u64) -> usize {
+        let log = (63 - freq.max(1).leading_zeros()) as usize;
+        cmp::min(log, self.queues.len() - 1)
+    }
+
+    // Count an access and move the entry to the front of its queue.
+    fn access(&mut self, (queue, index): (usize, usize)) -> &mut Entry<K, V> {
+        self.clock += 1;
+        let freq = {
+            let e = self.queues[queue].get_mut(index);
+            e.freq += 1;
+            e.expire = self.clock.saturating_add(self.lifetime);
+            e.freq
+        };
+        let target = self.queue_of(freq);
+        let index = if target == queue {
+            self.queues[queue].move_to_front(index);
+            index
+        } else {
+            self.transfer((queue, index), target)
+        };
+        // The accessed entry has not expired, so it is not demoted.
+        self.adjust();
+        self.queues[target].get_mut(index)
+    }
+
+    // Demote the least-recently used entry of each queue above the first
+    // if its lifetime has expired.
+    fn adjust(&mut self) {
+        for queue in 1..self.queues.len() {
+            let index = match self.queues[queue].back() {
+                Some(index) => index,
+                None => continue,
+            };
+            if self.queues[queue].get(index).expire < self.clock {
+                let index = self.transfer((queue, index), queue - 1);
+                self.queues[queue - 1].get_mut(index).expire = self.clock.saturating_add(self.lifetime);
+            }
+        }
+    }
+
+    // Move an entry to the front of another queue. Returns its new index.
+    fn transfer(&mut self, from: (usize, usize), to: usize) -> usize {
+        let e = self.queues[from.0].remove(from.1);
+        let hash = e.hash;
+        let index = self.queues[to].push_front(e);
+        *self.table
+            .find_mut(hash, |&loc| loc == from)
+            .expect("table and queues are out of sync") = (to, index);
+        index
+    }
+
+    // Evict the least-recently used entry of the lowest queue
+    // that is not empty and remember its frequency.
+    fn evict(&mut self) -> Option<(K, V)> {
+        let queue = self.queues.iter().position(|q| q.len() > 0)?;
+        let index = self.queues[queue].back()?;
+        let e = self.take_entry((queue, index));
+        if self.history.len() >= 4 * self.capacity {
+            if let Some(oldest) = self.history.back() {
+                let ghost = self.history.remove(oldest);
+                self.ghosts
+                    .find_entry(ghost.hash, |&i| i == oldest)
+                    .expect("ghost table and history are out of sync")
+                    .remove();
+            }
+        }
+        let ghost = self.history.push_front(Ghost {
+            hash: e.hash,
+            freq: e.freq,
+        });
+        let history = &self.history;
+        self.ghosts.insert_unique(e.hash, ghost, |&i| history.get(i).hash);
+        Some((e.key, e.val))
+    }
+
+    // Remove the remembered frequency of a hash.
+    fn take_ghost(&mut self, hash: u64) -> Option<u64> {
+        let history = &self.history;
+        let (index, _) = self.ghosts.find_entry(hash, |&i| history.get(i).hash == hash).ok()?.remove();
+        Some(self.history.remove(index).freq)
+    }
+
+    // Remove an entry from the table and its queue.
+    fn take_entry(&mut self, (queue, index): (usize, usize)) -> Entry<K, V> {
+        let hash = self.queues[queue].get(index).hash;
+        self.table
+            .find_entry(hash, |&loc| loc == (queue, index))
+            .expect("table and queues are out of sync")
+            .remove();
+        self.queues[queue].remove(index)
+    }
+}
+
+#[test]
+fn mq_queues() {
+    let mut cache = MQCache::with_queues(3, 4, 100);
+    cache.insert("a", 1);
+    for _ in 0..3 {
+        cache.get("a");
+    }
+    cache.insert("b", 2);
+    cache.get("b");
+    cache.insert("c", 3);
+    assert_eq!((Some(2), Some(1), Some(0)), (cache.queue("a"), cache.queue("b"), cache.queue("c")));
+    // The lowest queue is evicted first, and the evicted key is remembered.
+    assert_eq!(Some(("c", 3)), cache.insert("d", 4).evicted);
+    assert_eq!(Some(("d", 4)), cache.insert("c", 5).evicted);
+    assert_eq!((Some(2), Some(1)), (cache.frequency("c"), cache.queue("c")));
+    assert_eq!(1, cache.ghosts());
+    // An entry that is not accessed for its lifetime is demoted.
+    let mut cache = MQCache::with_queues(2, 4, 2);
+    cache.insert("a", 1);
+    cache.get("a");
+    cache.insert("b", 2);
+    cache.get("b");
+    cache.get("b");
+    assert_eq!((Some(0), Some(1)), (cache.queue("a"), cache.queue("b")));
+    assert_eq!(Some(("a", 1)), cache.insert("c", 3).evicted);
+}
+
+#[test]
+fn mq_unbounded_lifetime() {
+    // A lifetime of u64::MAX never demotes an entry.
+    let mut cache = MQCache::with_queues(2, 4, u64::MAX);
+    cache.insert("a", 1);
+    cache.get("a");
+    for i in 0..10 {
+        cache.insert("b", i);
+        cache.get("b");
+    }
+    assert_eq!(Some(1), cache.queue("a"));
+}
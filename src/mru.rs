@@ -0,0 +1,228 @@
+//! Most-recently-used cache that evicts the entry that was accessed last.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+}
+
+// The entry that was accessed last is the one least likely to be
+// accessed again soon when keys are accessed in cycles, such as by
+// repeated scans of a sequence that is larger than the cache. An LRU
+// cache evicts each key just before it is accessed again under such a
+// workload, while an MRU cache retains most of the sequence.
+pub struct MRUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries, linked from the most-recently used
+    // entry at the front to the least-recently used entry at the back
+    entries: List<Slot<K, V>>,
+    stats: Stats,
+}
+
+impl<K, V> MRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> MRUCache<K, V> {
+        MRUCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<MRUCache<K, V>, CapacityError> {
+        MRUCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> MRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> MRUCache<K, V, S> {
+        match MRUCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<MRUCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(MRUCache {
+            capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without promoting the entry.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.entries.move_to_front(index);
+            self.stats.replacements += 1;
+            let prev = mem::replace(&mut self.entries.get_mut(index).val, val);
+            return InsertResult {
+                replaced: Some(prev),
+                evicted: None,
+            };
+        }
+        // The entry is evicted before the new entry is inserted,
+        // because the new entry becomes the most-recently used.
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.pop_mru()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.entries.push_front(Slot { key, val, hash });
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_entry(index).1)
+    }
+
+    // The entry that is evicted next.
+    pub fn peek_mru(&self) -> Option<(&K, &V)> {
+        self.entries.front().map(|index| {
+            let slot = self.entries.get(index);
+            (&slot.key, &slot.val)
+        })
+    }
+
+    pub fn pop_mru(&mut self) -> Option<(K, V)> {
+        let index = self.entries.front()?;
+        Some(self.take_entry(index))
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        self.entries.move_to_front(index?);
+        index
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Remove the entry with the given index from the table and the slab.
+    fn take_entry(&mut self, index: usize) -> (K, V) {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let slot = self.entries.remove(index);
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn mru_cyclic_scan() {
+    let mut cache = MRUCache::new(3);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    cache.get("a");
+    assert_eq!(Some((&"a", &1)), cache.peek_mru());
+    cache.insert("c", 3);
+    assert_eq!(Some(("c", 3)), cache.insert("d", 4).evicted);
+    assert_eq!(Some(("d", 4)), cache.pop_mru());
+    // Repeated scans of five keys hit the cache on most lookups.
+    let mut cache = MRUCache::new(4);
+    for _ in 0..10 {
+        for k in 0..5 {
+            if cache.get(&k).is_none() {
+                cache.insert(k, k);
+            }
+        }
+    }
+    assert!(cache.stats().hits() > cache.stats().misses());
+}
@@ -0,0 +1,141 @@
+//! Belady's optimal offline replacement, as described by Belady in "A
+//! study of replacement algorithms for a virtual-storage computer" (IBM
+//! Systems Journal 1966). It needs the whole trace in advance, so it is
+//! a bound for the online caches rather than a cache.
+
+use DefaultHashBuilder;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use error::CapacityError;
+use hashbrown::HashTable;
+use stats::Stats;
+
+// position of the next access of a key that is never accessed again
+const NEVER: usize = usize::MAX;
+
+// Replay a trace of keys against a clairvoyant cache of the given
+// capacity and return its statistics. Every access is a lookup, and a
+// miss inserts the key. When the cache is full, the key whose next
+// access is the farthest in the future is evicted, or the missing key
+// is not cached at all if its own next access is the farthest, so the
+// number of misses is the smallest that any cache of the capacity can
+// achieve on the trace.
+// Panics if the capacity is zero.
+pub fn replay<K, I>(capacity: usize, trace: I) -> Stats
+    where K: Eq + Hash,
+          I: IntoIterator<Item = K>
+{
+    if capacity == 0 {
+        panic!("{}", CapacityError);
+    }
+    let (ids, distinct) = identify(trace);
+    let next = next_accesses(&ids, distinct);
+    let mut stats = Stats::default();
+    // the next access of each cached key, by id
+    let mut cached: Vec<Option<usize>> = vec![None; distinct];
+    // the cached keys ordered by their next access
+    let mut order = BTreeSet::new();
+    for (i, &id) in ids.iter().enumerate() {
+        if let Some(at) = cached[id] {
+            stats.record_lookup(true);
+            order.remove(&(at, id));
+            order.insert((next[i], id));
+            cached[id] = Some(next[i]);
+            continue;
+        }
+        stats.record_lookup(false);
+        if order.len() >= capacity {
+            let &(farthest, victim) = order.iter().next_back().expect("a full cache has a victim");
+            if next[i] >= farthest {
+                continue;
+            }
+            stats.evictions += 1;
+            order.remove(&(farthest, victim));
+            cached[victim] = None;
+        }
+        stats.insertions += 1;
+        order.insert((next[i], id));
+        cached[id] = Some(next[i]);
+    }
+    stats
+}
+
+// Number the distinct keys of a trace in the order of their
+// first access. Returns the ids of the accesses and the number
+// of distinct keys.
+fn identify<K, I>(trace: I) -> (Vec<usize>, usize)
+    where K: Eq + Hash,
+          I: IntoIterator<Item = K>
+{
+    let hash_builder = DefaultHashBuilder::default();
+    let mut keys: Vec<(K, u64)> = Vec::new();
+    let mut table: HashTable<usize> = HashTable::new();
+    let ids = trace.into_iter()
+        .map(|key| {
+            let hash = hash_builder.hash_one(&key);
+            match table.find(hash, |&id| keys[id].0 == key) {
+                Some(&id) => id,
+                None => {
+                    let id = keys.len();
+                    keys.push((key, hash));
+                    table.insert_unique(hash, id, |&id| keys[id].1);
+                    id
+                }
+            }
+        })
+        .collect();
+    (ids, keys.len())
+}
+
+// The position of the next access of the key of each access.
+fn next_accesses(ids: &[usize], distinct: usize) -> Vec<usize> {
+    let mut last = vec![NEVER; distinct];
+    let mut next = vec![NEVER; ids.len()];
+    for (i, &id) in ids.iter().enumerate().rev() {
+        next[i] = last[id];
+        last[id] = i;
+    }
+    next
+}
+
+#[test]
+fn opt_replay() {
+    let trace = [7, 0, 1, 2, 0, 3, 0, 4, 2, 3, 0, 3, 2, 1, 2, 0, 1, 7, 0, 1];
+    // One miss fewer than demand paging, which must also cache 4 and 7.
+    let stats = replay(3, trace.iter());
+    assert_eq!((12, 8), (stats.hits(), stats.misses()));
+    assert_eq!((6, 3), (stats.insertions(), stats.evictions()));
+    // A key that is never accessed again is not cached.
+    let stats = replay(1, ["a", "b", "a", "c", "a"]);
+    assert_eq!((2, 3, 1), (stats.hits(), stats.misses(), stats.insertions()));
+}
+
+#[test]
+fn opt_bound() {
+    use lru::LRUCache;
+
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let trace: Vec<u64> = (0..2000)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % 50
+        })
+        .collect();
+    for &capacity in &[1, 5, 20, 50] {
+        let mut lru = LRUCache::new(capacity);
+        for &key in &trace {
+            if lru.get(&key).is_none() {
+                lru.insert(key, ());
+            }
+        }
+        let opt = replay(capacity, trace.iter());
+        assert!(opt.misses() <= lru.stats().misses());
+        assert_eq!(2000, opt.hits() + opt.misses());
+    }
+    // Every miss is compulsory once all the keys fit.
+    assert_eq!(50, replay(50, trace.iter()).misses());
+}
@@ -0,0 +1,896 @@
+//! A cache whose replacement policy is a pluggable strategy. The cache
+//! stores the entries and maintains the statistics, the callbacks and
+//! the expiration, and the policy only orders the slots of the entries.
+//!
+//! The policies that are provided are LRU, FIFO, MRU, CLOCK, SIEVE and
+//! LFU. The other policies of the crate remain separate caches with their
+//! own statistics and without the callbacks or the time-to-live of this
+//! cache, because they need more than the order of the slots: ARC, 2Q,
+//! LIRS, CAR, S3-FIFO, CLOCK-Pro, MQ and LRU-K remember the keys of
+//! evicted entries, W-TinyLFU hashes the keys into its frequency sketch,
+//! GDSF weighs the cost of each entry, and the random, sampled and
+//! hyperbolic caches sample the entries with a random number generator.
+
+use DefaultHashBuilder;
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use cache::Cache;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+#[cfg(feature = "std")]
+use core::time::Duration;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::{EvictionReason, InsertResult};
+use stats::Stats;
+#[cfg(feature = "std")]
+use std::time::Instant;
+#[cfg(feature = "std")]
+use time::{Clock, SystemClock};
+
+// A replacement policy. The cache identifies each entry by the index
+// of its slot, which is reused after the entry leaves the cache. The
+// cache calls insert() for each new entry, access() for each lookup
+// that hits and each replaced value, and remove() for each entry that
+// leaves the cache other than through evict().
+pub trait Policy {
+    fn insert(&mut self, slot: usize);
+
+    fn access(&mut self, slot: usize);
+
+    fn remove(&mut self, slot: usize);
+
+    // Choose the slot of the entry to evict and forget it.
+    // None if the policy has no slots.
+    fn evict(&mut self) -> Option<usize>;
+
+    fn clear(&mut self);
+}
+
+// called with the (key, value) pairs that are evicted by capacity
+type EvictionCallback<K, V> = Box<dyn FnMut(K, V) + Send + Sync>;
+
+// called with every (key, value) pair before it leaves the cache
+type Listener<K, V> = Box<dyn FnMut(&K, &V, EvictionReason) + Send + Sync>;
+
+// index of a slot that is not in the order
+const NIL: usize = usize::MAX;
+
+// The slots in an order, with the node of each slot in the order.
+#[derive(Default)]
+struct Order {
+    list: List<usize>,
+    // node of each slot in the list, or NIL
+    nodes: Vec<usize>,
+}
+
+impl Order {
+    fn push_front(&mut self, slot: usize) {
+        if self.nodes.len() <= slot {
+            self.nodes.resize(slot + 1, NIL);
+        }
+        self.nodes[slot] = self.list.push_front(slot);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        let node = mem::replace(&mut self.nodes[slot], NIL);
+        self.list.remove(node);
+    }
+
+    fn move_to_front(&mut self, slot: usize) {
+        self.list.move_to_front(self.nodes[slot]);
+    }
+
+    fn pop_back(&mut self) -> Option<usize> {
+        let slot = *self.list.get(self.list.back()?);
+        self.remove(slot);
+        Some(slot)
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.nodes.clear();
+    }
+}
+
+// Evicts the least-recently used entry.
+#[derive(Default)]
+pub struct LRUPolicy {
+    // from the most-recently used slot at the front
+    order: Order,
+}
+
+impl Policy for LRUPolicy {
+    fn insert(&mut self, slot: usize) {
+        self.order.push_front(slot);
+    }
+
+    fn access(&mut self, slot: usize) {
+        self.order.move_to_front(slot);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.order.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        self.order.pop_back()
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+    }
+}
+
+// Evicts the entry that was inserted first.
+#[derive(Default)]
+pub struct FIFOPolicy {
+    // from the newest slot at the front
+    order: Order,
+}
+
+impl Policy for FIFOPolicy {
+    fn insert(&mut self, slot: usize) {
+        self.order.push_front(slot);
+    }
+
+    fn access(&mut self, _slot: usize) {}
+
+    fn remove(&mut self, slot: usize) {
+        self.order.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        self.order.pop_back()
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+    }
+}
+
+// Evicts the most-recently used entry, like MRUCache.
+#[derive(Default)]
+pub struct MRUPolicy {
+    // from the most-recently used slot at the front
+    order: Order,
+}
+
+impl Policy for MRUPolicy {
+    fn insert(&mut self, slot: usize) {
+        self.order.push_front(slot);
+    }
+
+    fn access(&mut self, slot: usize) {
+        self.order.move_to_front(slot);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.order.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let slot = *self.order.list.get(self.order.list.front()?);
+        self.order.remove(slot);
+        Some(slot)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+    }
+}
+
+// Evicts like ClockCache: the slots form a circle that is swept by a
+// hand, which clears the reference bits that are set and evicts the
+// first entry that was not accessed since the hand last passed it.
+// New slots are placed just behind the hand, so that they are examined
+// last.
+#[derive(Default)]
+pub struct ClockPolicy {
+    // the circle, which continues from the back to the front
+    order: Order,
+    // set by each access and cleared when the hand passes the slot
+    referenced: Vec<bool>,
+    // the node that is examined first by the next eviction,
+    // or none to start from the front
+    hand: Option<usize>,
+}
+
+impl ClockPolicy {
+    // The hand moves on to the next node if it was on the node.
+    fn unlink(&mut self, slot: usize) {
+        let node = self.order.nodes[slot];
+        if self.hand == Some(node) {
+            self.hand = self.order.list.next(node);
+        }
+        self.order.remove(slot);
+    }
+}
+
+impl Policy for ClockPolicy {
+    fn insert(&mut self, slot: usize) {
+        if self.order.nodes.len() <= slot {
+            self.order.nodes.resize(slot + 1, NIL);
+            self.referenced.resize(slot + 1, false);
+        }
+        self.order.nodes[slot] = match self.hand {
+            Some(hand) => self.order.list.insert_before(hand, slot),
+            None => self.order.list.push_back(slot),
+        };
+        self.referenced[slot] = false;
+    }
+
+    fn access(&mut self, slot: usize) {
+        self.referenced[slot] = true;
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.unlink(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let list = &self.order.list;
+        let mut node = self.hand.or_else(|| list.front())?;
+        while self.referenced[*list.get(node)] {
+            self.referenced[*list.get(node)] = false;
+            node = list.next(node).or_else(|| list.front())?;
+        }
+        let slot = *list.get(node);
+        self.hand = Some(node);
+        self.unlink(slot);
+        Some(slot)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.referenced.clear();
+        self.hand = None;
+    }
+}
+
+// Evicts like SieveCache: a hand moves from the oldest entry towards
+// the newest, sparing and clearing the entries that were accessed, and
+// evicts the first entry that was not accessed.
+#[derive(Default)]
+pub struct SievePolicy {
+    // from the newest slot at the front
+    order: Order,
+    // set by each access and cleared when the hand passes the slot
+    visited: Vec<bool>,
+    // the node that is examined first by the next eviction,
+    // or none to start from the oldest slot
+    hand: Option<usize>,
+}
+
+impl SievePolicy {
+    // The hand moves on to the next newer node if it was on the node.
+    fn unlink(&mut self, slot: usize) {
+        let node = self.order.nodes[slot];
+        if self.hand == Some(node) {
+            self.hand = self.order.list.prev(node);
+        }
+        self.order.remove(slot);
+    }
+}
+
+impl Policy for SievePolicy {
+    fn insert(&mut self, slot: usize) {
+        self.order.push_front(slot);
+        if self.visited.len() <= slot {
+            self.visited.resize(slot + 1, false);
+        }
+        self.visited[slot] = false;
+    }
+
+    fn access(&mut self, slot: usize) {
+        self.visited[slot] = true;
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.unlink(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let list = &self.order.list;
+        let mut node = self.hand.or_else(|| list.back())?;
+        while self.visited[*list.get(node)] {
+            self.visited[*list.get(node)] = false;
+            node = list.prev(node).or_else(|| list.back())?;
+        }
+        let slot = *list.get(node);
+        self.hand = Some(node);
+        self.unlink(slot);
+        Some(slot)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.visited.clear();
+        self.hand = None;
+    }
+}
+
+// The slots that have been used the same number of times.
+struct Bucket {
+    uses: u64,
+    // node of the most-recently used slot of the bucket
+    head: usize,
+}
+
+// Evicts the least-frequently used entry, and the least-recently
+// used of the entries that were used equally often. Like LFUCache,
+// the slots are ordered from the most used at the front to the least
+// used at the back, the slots of each bucket are adjacent from the
+// most-recently used, and every operation takes constant time.
+#[derive(Default)]
+pub struct LFUPolicy {
+    order: Order,
+    // node of the bucket of each slot
+    bucket: Vec<usize>,
+    // buckets from the most uses at the front
+    // to the fewest uses at the back
+    buckets: List<Bucket>,
+}
+
+impl LFUPolicy {
+    // The node after a node in the same bucket.
+    fn next_in_bucket(&self, node: usize) -> Option<usize> {
+        let list = &self.order.list;
+        let bucket = self.bucket[*list.get(node)];
+        list.next(node).filter(|&n| self.bucket[*list.get(n)] == bucket)
+    }
+
+    // Update a bucket when one of its slots leaves it. The bucket is
+    // removed if it has no other slot.
+    fn unlink_head(&mut self, bucket: usize, node: usize, next: Option<usize>) {
+        if self.buckets.get(bucket).head == node {
+            match next {
+                Some(next) => self.buckets.get_mut(bucket).head = next,
+                None => drop(self.buckets.remove(bucket)),
+            }
+        }
+    }
+}
+
+impl Policy for LFUPolicy {
+    fn insert(&mut self, slot: usize) {
+        if self.bucket.len() <= slot {
+            self.bucket.resize(slot + 1, NIL);
+            self.order.nodes.resize(slot + 1, NIL);
+        }
+        let lowest = self.buckets.back().filter(|&b| self.buckets.get(b).uses == 1);
+        let node = match lowest {
+            Some(bucket) => {
+                let head = self.buckets.get(bucket).head;
+                let node = self.order.list.insert_before(head, slot);
+                self.buckets.get_mut(bucket).head = node;
+                self.bucket[slot] = bucket;
+                node
+            }
+            None => {
+                let node = self.order.list.push_back(slot);
+                self.bucket[slot] = self.buckets.push_back(Bucket { uses: 1, head: node });
+                node
+            }
+        };
+        self.order.nodes[slot] = node;
+    }
+
+    // Move the slot to the front of the bucket of the next number of
+    // uses, creating the bucket if no slot has that number of uses.
+    fn access(&mut self, slot: usize) {
+        let node = self.order.nodes[slot];
+        let bucket = self.bucket[slot];
+        let (uses, head) = {
+            let b = self.buckets.get(bucket);
+            (b.uses, b.head)
+        };
+        let higher = self.buckets.prev(bucket).filter(|&b| self.buckets.get(b).uses == uses + 1);
+        let next = self.next_in_bucket(node);
+        let target = match higher {
+            Some(higher) => {
+                let head = self.buckets.get(higher).head;
+                self.order.list.move_before(node, head);
+                self.buckets.get_mut(higher).head = node;
+                higher
+            }
+            None => {
+                self.order.list.move_before(node, head);
+                self.buckets.insert_before(bucket,
+                                           Bucket {
+                                               uses: uses + 1,
+                                               head: node,
+                                           })
+            }
+        };
+        self.bucket[slot] = target;
+        self.unlink_head(bucket, node, next);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        let node = self.order.nodes[slot];
+        let next = self.next_in_bucket(node);
+        self.unlink_head(self.bucket[slot], node, next);
+        self.bucket[slot] = NIL;
+        self.order.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let slot = *self.order.list.get(self.order.list.back()?);
+        self.remove(slot);
+        Some(slot)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.bucket.clear();
+        self.buckets.clear();
+    }
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // time after which the entry is treated as absent
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+}
+
+// A cache that delegates the choice of the evicted entries to a policy.
+// Every policy shares the storage of the entries, the statistics, the
+// eviction callback, the listener and the time-to-live of the cache,
+// so a new policy only implements the Policy trait.
+pub struct PolicyCache<K, V, P, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // slots of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries, in arbitrary order
+    entries: List<Entry<K, V>>,
+    policy: P,
+    on_evict: Option<EvictionCallback<K, V>>,
+    listener: Option<Listener<K, V>>,
+    // entries expire this long after their value is written
+    #[cfg(feature = "std")]
+    ttl: Option<Duration>,
+    // source of the current time
+    #[cfg(feature = "std")]
+    clock: Arc<dyn Clock>,
+    stats: Stats,
+}
+
+impl<K, V, P> PolicyCache<K, V, P>
+    where K: Eq + Hash,
+          P: Policy + Default
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> PolicyCache<K, V, P> {
+        PolicyCache::with_policy(capacity, P::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<PolicyCache<K, V, P>, CapacityError> {
+        PolicyCache::try_with_hasher(capacity, P::default(), DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, P> PolicyCache<K, V, P>
+    where K: Eq + Hash,
+          P: Policy
+{
+    // Panics if the capacity is zero.
+    pub fn with_policy(capacity: usize, policy: P) -> PolicyCache<K, V, P> {
+        PolicyCache::with_hasher(capacity, policy, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, P, S> PolicyCache<K, V, P, S>
+    where K: Eq + Hash,
+          P: Policy,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, policy: P, hash_builder: S) -> PolicyCache<K, V, P, S> {
+        match PolicyCache::try_with_hasher(capacity, policy, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           policy: P,
+                           hash_builder: S)
+                           -> Result<PolicyCache<K, V, P, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(PolicyCache {
+            capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            policy,
+            on_evict: None,
+            listener: None,
+            #[cfg(feature = "std")]
+            ttl: None,
+            #[cfg(feature = "std")]
+            clock: Arc::new(SystemClock),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let slot = self.lookup(key)?;
+        Some(&self.entries.get(slot).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let slot = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(slot).val)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.slot_of(hash, key)
+            .map(|slot| self.entries.get(slot))
+            .filter(|e| !self.is_expired(e))
+            .map(|e| &e.val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Replacing the value of a key counts as an access
+    // and restarts the time-to-live of the entry.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(slot) = self.live_slot(hash, &key) {
+            self.stats.replacements += 1;
+            self.policy.access(slot);
+            #[cfg(feature = "std")]
+            let deadline = self.deadline();
+            let e = self.entries.get_mut(slot);
+            if let Some(ref mut f) = self.listener {
+                f(&e.key, &e.val, EvictionReason::Replaced);
+            }
+            #[cfg(feature = "std")]
+            {
+                e.deadline = deadline;
+            }
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.len() >= self.capacity {
+            self.evict()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let slot = self.entries.push_front(Entry {
+            key,
+            val,
+            hash,
+            #[cfg(feature = "std")]
+            deadline: self.deadline(),
+        });
+        self.policy.insert(slot);
+        let entries = &self.entries;
+        self.table.insert_unique(hash, slot, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let slot = self.live_slot(hash, key)?;
+        self.policy.remove(slot);
+        Some(self.take_entry(slot, EvictionReason::ExplicitlyRemoved).1)
+    }
+
+    // The listener is notified of every entry that is removed.
+    pub fn clear(&mut self) {
+        if let Some(ref mut f) = self.listener {
+            for (_, e) in self.entries.iter() {
+                f(&e.key, &e.val, EvictionReason::ExplicitlyRemoved);
+            }
+        }
+        self.table.clear();
+        self.entries.clear();
+        self.policy.clear();
+    }
+
+    // Expired entries that have not been reclaimed are counted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub(crate) fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    pub(crate) fn hash_builder(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    // The callback receives the pairs that are evicted by capacity,
+    // which are then no longer returned by insert().
+    pub fn set_on_evict<F>(&mut self, f: F)
+        where F: FnMut(K, V) + Send + Sync + 'static
+    {
+        self.on_evict = Some(Box::new(f));
+    }
+
+    // The listener is invoked before any entry leaves the cache,
+    // and before a value is overwritten, together with the reason.
+    pub fn set_listener<F>(&mut self, f: F)
+        where F: FnMut(&K, &V, EvictionReason) + Send + Sync + 'static
+    {
+        self.listener = Some(Box::new(f));
+    }
+
+    // Entries that are written after this call expire once the
+    // time-to-live has elapsed. Expired entries are treated as absent
+    // and are reclaimed when their key is accessed or their slot is
+    // evicted by the policy.
+    #[cfg(feature = "std")]
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = Some(ttl);
+    }
+
+    // Expiration is measured with the clock, which is the system clock
+    // by default. The clock should be set before entries are written:
+    // existing deadlines are kept as they are.
+    #[cfg(feature = "std")]
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Arc::new(clock);
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let slot = self.live_slot(hash, key);
+        self.stats.record_lookup(slot.is_some());
+        let slot = slot?;
+        self.policy.access(slot);
+        Some(slot)
+    }
+
+    fn slot_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Find the slot of the entry of a key.
+    // An entry that has expired is reclaimed and not returned.
+    fn live_slot<Q>(&mut self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        let slot = self.slot_of(hash, key)?;
+        if self.is_expired(self.entries.get(slot)) {
+            self.policy.remove(slot);
+            drop(self.take_entry(slot, EvictionReason::Expired));
+            return None;
+        }
+        Some(slot)
+    }
+
+    #[cfg(feature = "std")]
+    fn deadline(&self) -> Option<Instant> {
+        // A deadline that cannot be represented is never reached.
+        self.ttl.and_then(|ttl| self.clock.now().checked_add(ttl))
+    }
+
+    #[cfg(feature = "std")]
+    fn is_expired(&self, e: &Entry<K, V>) -> bool {
+        e.deadline.is_some_and(|deadline| deadline <= self.clock.now())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn is_expired(&self, _e: &Entry<K, V>) -> bool {
+        false
+    }
+
+    // Evict the entry chosen by the policy and hand it to the eviction
+    // callback. The pair is returned if there is no eviction callback.
+    fn evict(&mut self) -> Option<(K, V)> {
+        let slot = self.policy.evict()?;
+        self.stats.evictions += 1;
+        let pair = self.take_entry(slot, EvictionReason::CapacityEvicted);
+        match self.on_evict {
+            Some(ref mut f) => {
+                f(pair.0, pair.1);
+                None
+            }
+            None => Some(pair),
+        }
+    }
+
+    // Remove the entry in a slot from the table and the slab.
+    // The policy must have forgotten the slot.
+    fn take_entry(&mut self, slot: usize, reason: EvictionReason) -> (K, V) {
+        let hash = self.entries.get(slot).hash;
+        self.table
+            .find_entry(hash, |&i| i == slot)
+            .expect("table and slab are out of sync")
+            .remove();
+        let e = self.entries.remove(slot);
+        if let Some(ref mut f) = self.listener {
+            f(&e.key, &e.val, reason);
+        }
+        (e.key, e.val)
+    }
+}
+
+impl<K, V, P, S> Cache<K, V> for PolicyCache<K, V, P, S>
+    where K: Eq + Hash,
+          P: Policy,
+          S: BuildHasher
+{
+    fn get(&mut self, key: &K) -> Option<&V> {
+        PolicyCache::get(self, key)
+    }
+
+    fn peek(&self, key: &K) -> Option<&V> {
+        PolicyCache::peek(self, key)
+    }
+
+    fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        PolicyCache::insert(self, key, val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        PolicyCache::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        PolicyCache::clear(self)
+    }
+
+    fn len(&self) -> usize {
+        PolicyCache::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(PolicyCache::capacity(self))
+    }
+
+    fn stats(&self) -> &Stats {
+        PolicyCache::stats(self)
+    }
+}
+
+#[test]
+fn policy_eviction() {
+    use std::sync::{Arc, Mutex};
+
+    // The same accesses evict different entries under each policy.
+    fn victims<P: Policy + Default>() -> Vec<i32> {
+        let mut cache = PolicyCache::<i32, i32, P>::new(3);
+        let mut victims = Vec::new();
+        for i in 0..3 {
+            cache.insert(i, i);
+        }
+        cache.get(&0);
+        for i in 3..5 {
+            victims.extend(cache.insert(i, i).evicted.map(|(k, _)| k));
+        }
+        victims
+    }
+    assert_eq!(vec![1, 2], victims::<LRUPolicy>());
+    assert_eq!(vec![0, 1], victims::<FIFOPolicy>());
+    assert_eq!(vec![1, 2], victims::<SievePolicy>());
+    assert_eq!(vec![1, 2], victims::<LFUPolicy>());
+    assert_eq!(vec![0, 3], victims::<MRUPolicy>());
+    assert_eq!(vec![1, 2], victims::<ClockPolicy>());
+    // LFU evicts the least-recently used of the least used entries.
+    let mut cache = PolicyCache::<&str, i32, LFUPolicy>::new(3);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    cache.insert("c", 3);
+    for _ in 0..3 {
+        cache.get("a");
+    }
+    cache.get("b");
+    cache.get("c");
+    assert_eq!(Some(("b", 2)), cache.insert("d", 4).evicted);
+    assert_eq!(Some(("d", 4)), cache.insert("e", 5).evicted);
+    cache.get("e");
+    cache.get("e");
+    assert_eq!(Some(("c", 3)), cache.insert("f", 6).evicted);
+    // A removed entry leaves its bucket.
+    cache.remove("e");
+    cache.insert("g", 7);
+    assert_eq!(Some(("f", 6)), cache.insert("h", 8).evicted);
+    assert_eq!(2, cache.policy().buckets.len());
+    // The callbacks are shared by every policy.
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let mut cache = PolicyCache::<i32, i32, SievePolicy>::new(1);
+    let sink = events.clone();
+    cache.set_listener(move |&k, _, reason| sink.lock().unwrap().push((k, reason)));
+    let sink = evicted.clone();
+    cache.set_on_evict(move |k, v| sink.lock().unwrap().push((k, v)));
+    cache.insert(1, 1);
+    cache.insert(1, 2);
+    assert_eq!(None, cache.insert(3, 3).evicted);
+    cache.remove(&3);
+    assert_eq!(vec![(1, 2)], *evicted.lock().unwrap());
+    assert_eq!(vec![(1, EvictionReason::Replaced),
+                    (1, EvictionReason::CapacityEvicted),
+                    (3, EvictionReason::ExplicitlyRemoved)],
+               *events.lock().unwrap());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn policy_ttl() {
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = PolicyCache::<&str, i32, FIFOPolicy>::new(2);
+    cache.set_clock(clock.clone());
+    cache.set_ttl(Duration::from_secs(10));
+    cache.insert("a", 1);
+    clock.advance(Duration::from_secs(5));
+    cache.insert("b", 2);
+    clock.advance(Duration::from_secs(5));
+    // The expired entry is absent and reclaimed on access.
+    assert_eq!(None, cache.peek("a"));
+    assert_eq!(None, cache.get("a"));
+    assert_eq!(Some(&2), cache.get("b"));
+    assert_eq!(1, cache.len());
+    // Replacing a value restarts its time-to-live.
+    cache.insert("b", 3);
+    clock.advance(Duration::from_secs(9));
+    assert_eq!(Some(&3), cache.get("b"));
+    // A time-to-live beyond the range of the clock never expires.
+    cache.set_ttl(Duration::MAX);
+    cache.insert("c", 4);
+    assert_eq!(Some(&4), cache.get("c"));
+}
@@ -0,0 +1,130 @@
+//! Renders cache statistics in the [Prometheus text exposition format](
+//! https://prometheus.io/docs/instrumenting/exposition_formats/).
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+use core::hash::{BuildHasher, Hash};
+use lru::LRUCache;
+use stats::StatsSnapshot;
+
+// The values that are exported for a cache.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    // number of entries in the cache
+    pub size: usize,
+    // maximum number of entries, or None if the cache is unbounded
+    pub capacity: Option<usize>,
+    pub stats: StatsSnapshot,
+    // mean number of logical clock ticks since each entry was last accessed
+    pub average_age: f64,
+}
+
+impl Metrics {
+    // Every metric name is prefixed with the namespace.
+    pub fn render(&self, namespace: &str) -> String {
+        let mut out = String::new();
+        self.write_to(namespace, &mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    pub fn write_to<W: Write>(&self, namespace: &str, w: &mut W) -> fmt::Result {
+        metric(w,
+               namespace,
+               "size",
+               "gauge",
+               "Number of entries in the cache.",
+               self.size)?;
+        match self.capacity {
+            Some(capacity) => {
+                metric(w,
+                       namespace,
+                       "capacity",
+                       "gauge",
+                       "Maximum number of entries in the cache.",
+                       capacity)?
+            }
+            None => {
+                metric(w,
+                       namespace,
+                       "capacity",
+                       "gauge",
+                       "Maximum number of entries in the cache.",
+                       "+Inf")?
+            }
+        }
+        metric(w,
+               namespace,
+               "hits_total",
+               "counter",
+               "Number of lookups that located the key.",
+               self.stats.hits)?;
+        metric(w,
+               namespace,
+               "misses_total",
+               "counter",
+               "Number of lookups that did not locate the key.",
+               self.stats.misses)?;
+        metric(w,
+               namespace,
+               "evictions_total",
+               "counter",
+               "Number of entries evicted by capacity.",
+               self.stats.evictions)?;
+        metric(w,
+               namespace,
+               "average_entry_age",
+               "gauge",
+               "Mean number of cache operations since each entry was last accessed.",
+               self.average_age)
+    }
+}
+
+fn metric<W, T>(w: &mut W,
+                namespace: &str,
+                name: &str,
+                kind: &str,
+                help: &str,
+                value: T)
+                -> fmt::Result
+    where W: Write,
+          T: fmt::Display
+{
+    writeln!(w, "# HELP {}_{} {}", namespace, name, help)?;
+    writeln!(w, "# TYPE {}_{} {}", namespace, name, kind)?;
+    writeln!(w, "{}_{} {}", namespace, name, value)
+}
+
+impl<'a, K, V, S> From<&'a LRUCache<K, V, S>> for Metrics
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    fn from(cache: &'a LRUCache<K, V, S>) -> Metrics {
+        Metrics {
+            size: cache.len(),
+            capacity: cache.capacity(),
+            stats: cache.stats().snapshot(),
+            average_age: cache.average_age(),
+        }
+    }
+}
+
+#[test]
+fn prometheus_render() {
+    let mut cache = LRUCache::new(2);
+    cache.insert(1, 2);
+    cache.insert(3, 4);
+    cache.get(&1);
+    cache.get(&5);
+    let text = Metrics::from(&cache).render("specie");
+    let samples: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(vec!["specie_size 2",
+                    "specie_capacity 2",
+                    "specie_hits_total 1",
+                    "specie_misses_total 1",
+                    "specie_evictions_total 0",
+                    "specie_average_entry_age 1.5"],
+               samples);
+    assert!(text.contains("# TYPE specie_hits_total counter\n"));
+    let unbounded: LRUCache<u32, u32> = LRUCache::unbounded();
+    assert!(Metrics::from(&unbounded).render("specie").contains("specie_capacity +Inf\n"));
+}
@@ -0,0 +1,237 @@
+//! Random-replacement cache that evicts an entry chosen uniformly at random.
+
+use DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use lru::InsertResult;
+use rng::{DEFAULT_SEED, Rng};
+use stats::Stats;
+
+struct Slot<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the slot was inserted
+    hash: u64,
+}
+
+// No information about the accesses is kept, so lookups do not write
+// to the cache. The entry that is evicted depends only on the seed and
+// on the sequence of insertions and removals, not on the hasher, so a
+// cache with a given seed evicts the same entries on every run.
+pub struct RandomCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // indices of the entries, hashed by key
+    table: HashTable<usize>,
+    // dense storage of the entries, in arbitrary order
+    slots: Vec<Slot<K, V>>,
+    // chooses the entries that are evicted
+    rng: Rng,
+    stats: Stats,
+}
+
+impl<K, V> RandomCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> RandomCache<K, V> {
+        RandomCache::with_seed(capacity, DEFAULT_SEED)
+    }
+
+    // Panics if the capacity is zero.
+    pub fn with_seed(capacity: usize, seed: u64) -> RandomCache<K, V> {
+        RandomCache::with_hasher(capacity, seed, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<RandomCache<K, V>, CapacityError> {
+        RandomCache::try_with_hasher(capacity, DEFAULT_SEED, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> RandomCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, seed: u64, hash_builder: S) -> RandomCache<K, V, S> {
+        match RandomCache::try_with_hasher(capacity, seed, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           seed: u64,
+                           hash_builder: S)
+                           -> Result<RandomCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(RandomCache {
+            capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            rng: Rng::new(seed),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.slots[index].val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.slots[index].val)
+    }
+
+    // Read the value without counting the lookup.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.slots[index].val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.stats.replacements += 1;
+            return InsertResult {
+                replaced: Some(mem::replace(&mut self.slots[index].val, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.slots.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.pop_random()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.slots.len();
+        self.slots.push(Slot { key, val, hash });
+        let slots = &self.slots;
+        self.table.insert_unique(hash, index, |&i| slots[i].hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_slot(index).1)
+    }
+
+    // Remove an entry chosen at random.
+    pub fn pop_random(&mut self) -> Option<(K, V)> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let index = self.rng.below(self.slots.len());
+        Some(self.take_slot(index))
+    }
+
+    // Restart the sequence of random choices from a seed.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.slots.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        index
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.slots[i].key.borrow() == key).copied()
+    }
+
+    // Remove a slot by moving the last slot into its place.
+    fn take_slot(&mut self, index: usize) -> (K, V) {
+        let hash = self.slots[index].hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slots are out of sync")
+            .remove();
+        let slot = self.slots.swap_remove(index);
+        if let Some(moved) = self.slots.get(index) {
+            let last = self.slots.len();
+            *self.table
+                .find_mut(moved.hash, |&i| i == last)
+                .expect("table and slots are out of sync") = index;
+        }
+        (slot.key, slot.val)
+    }
+}
+
+#[test]
+fn random_seeded() {
+    // Caches with the same seed evict the same entries.
+    let evictions = |seed| {
+        let mut cache = RandomCache::with_seed(8, seed);
+        (0..100).filter_map(|i| cache.insert(i, i).evicted).collect::<Vec<_>>()
+    };
+    assert_eq!(evictions(1), evictions(1));
+    assert_ne!(evictions(1), evictions(2));
+    // Reseeding restarts the random choices.
+    let mut a = RandomCache::with_seed(4, 3);
+    let mut b = RandomCache::with_seed(4, 9);
+    for i in 0..4 {
+        a.insert(i, i);
+        b.insert(i, i);
+    }
+    b.reseed(3);
+    assert_eq!(a.pop_random(), b.pop_random());
+}
@@ -0,0 +1,53 @@
+//! A small pseudo-random number generator for the randomized eviction policies.
+//! The generator is SplitMix64, which is fast and has a 64-bit state that can be
+//! seeded for reproducible evictions. It is not suitable for cryptography.
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+// seed of the generators that are not seeded explicitly
+pub const DEFAULT_SEED: u64 = 0x853c_49e6_748f_ea9b;
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number that is less than n. Panics if n is zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "range must be nonempty");
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(DEFAULT_SEED)
+    }
+}
+
+#[test]
+fn rng_seeded() {
+    let mut a = Rng::new(7);
+    let mut b = Rng::new(7);
+    let xs: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+    assert_eq!(xs, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+    let mut c = Rng::new(8);
+    assert_ne!(xs, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+    let mut rng = Rng::default();
+    let mut seen = [false; 10];
+    for _ in 0..1000 {
+        seen[rng.below(10)] = true;
+    }
+    assert!(seen.iter().all(|&s| s));
+}
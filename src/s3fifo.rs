@@ -0,0 +1,396 @@
+//! S3-FIFO cache, as described by Yang, Zhang, Qiu, Yue and Vinayak in
+//! "FIFO queues are all you need for cache eviction" (SOSP 2023).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// default share of the capacity that is held by the small queue
+pub const DEFAULT_SMALL_RATIO: f64 = 0.1;
+// largest value of the access counter of an entry
+const MAX_FREQ: u8 = 3;
+
+// The queue of a cached entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Queue {
+    // the entries that were inserted and not yet admitted to main
+    Small,
+    // the entries that were accessed in the small queue, or
+    // that returned while their key was remembered by the ghosts
+    Main,
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // accesses since the entry was inserted or moved, up to MAX_FREQ
+    freq: u8,
+}
+
+// Three FIFO queues: new entries enter the small queue, which holds
+// about a tenth of the capacity. When the small queue is evicted, its
+// oldest entry moves to the main queue if it was accessed more than
+// once, and is evicted otherwise, with its key remembered by the ghost
+// queue. A key that is inserted while it is remembered enters the main
+// queue directly. The main queue is a clock: its oldest entry is
+// reinserted if it was accessed since it was last examined, with its
+// counter decremented, and is evicted otherwise.
+//
+// Lookups only increment a counter and never reorder the queues, and
+// a scan of keys that are used once passes through the small queue
+// without disturbing the main queue. The ghost queue remembers as many
+// keys as the main queue holds entries and records only their hashes.
+pub struct S3FIFOCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // number of entries in the small queue at which it is evicted first
+    small_capacity: usize,
+    // maximum number of keys remembered by the ghost queue
+    ghost_capacity: usize,
+    hash_builder: S,
+    // locations of the cached entries in the queues, hashed by key
+    table: HashTable<(Queue, usize)>,
+    // the small queue from the newest entry at the front to the oldest at the back
+    small: List<Entry<K, V>>,
+    // the main queue from the newest entry at the front to the oldest at the back
+    main: List<Entry<K, V>>,
+    // indices of the hashes in the ghost queue
+    ghosts: HashTable<usize>,
+    // the ghost queue from the most recently evicted hash at the front
+    ghost: List<u64>,
+    stats: Stats,
+}
+
+impl<K, V> S3FIFOCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> S3FIFOCache<K, V> {
+        S3FIFOCache::with_ratio(capacity, DEFAULT_SMALL_RATIO)
+    }
+
+    // The small queue holds small_ratio of the capacity, rounded,
+    // and at least one entry.
+    // Panics if the capacity is zero or the ratio is not between zero and one.
+    pub fn with_ratio(capacity: usize, small_ratio: f64) -> S3FIFOCache<K, V> {
+        S3FIFOCache::with_hasher(capacity, small_ratio, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<S3FIFOCache<K, V>, CapacityError> {
+        S3FIFOCache::try_with_hasher(capacity, DEFAULT_SMALL_RATIO, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> S3FIFOCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero or the ratio is not between zero and one.
+    pub fn with_hasher(capacity: usize, small_ratio: f64, hash_builder: S) -> S3FIFOCache<K, V, S> {
+        match S3FIFOCache::try_with_hasher(capacity, small_ratio, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the ratio is not between zero and one.
+    pub fn try_with_hasher(capacity: usize,
+                           small_ratio: f64,
+                           hash_builder: S)
+                           -> Result<S3FIFOCache<K, V, S>, CapacityError> {
+        assert!((0.0..=1.0).contains(&small_ratio), "small ratio must be between zero and one");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        let small_capacity = cmp::max((capacity as f64 * small_ratio + 0.5) as usize, 1);
+        let ghost_capacity = capacity.saturating_sub(small_capacity);
+        Ok(S3FIFOCache {
+            capacity,
+            small_capacity,
+            ghost_capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            small: List::new(),
+            main: List::new(),
+            ghosts: HashTable::with_capacity(ghost_capacity),
+            ghost: List::with_capacity(ghost_capacity),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(queue, index)| &self.queue(queue).get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Replacing the value of a key counts as an access.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(loc) = self.locate(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.access(loc);
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        let queue = if self.take_ghost(hash) {
+            Queue::Main
+        } else {
+            Queue::Small
+        };
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.evict()
+        } else {
+            None
+        };
+        let index = self.queue_mut(queue).push_front(Entry {
+            key,
+            val,
+            hash,
+            freq: 0,
+        });
+        let (small, main) = (&self.small, &self.main);
+        self.table.insert_unique(hash, (queue, index), |&loc| entry_hash(small, main, loc));
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // The ghost queue is cleared as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.small.clear();
+        self.main.clear();
+        self.ghosts.clear();
+        self.ghost.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.small.len() + self.main.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The number of cached entries in the small and the main queues,
+    // and the number of keys remembered by the ghost queue.
+    pub fn queues(&self) -> (usize, usize, usize) {
+        (self.small.len(), self.main.len(), self.ghost.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        Some(self.access(loc?))
+    }
+
+    // An access only increments the counter of the entry.
+    fn access(&mut self, (queue, index): (Queue, usize)) -> &mut Entry<K, V> {
+        let e = self.queue_mut(queue).get_mut(index);
+        e.freq = cmp::min(e.freq + 1, MAX_FREQ);
+        e
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(Queue, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(queue, i)| self.queue(queue).get(i).key.borrow() == key)
+            .copied()
+    }
+
+    fn queue(&self, queue: Queue) -> &List<Entry<K, V>> {
+        match queue {
+            Queue::Small => &self.small,
+            Queue::Main => &self.main,
+        }
+    }
+
+    fn queue_mut(&mut self, queue: Queue) -> &mut List<Entry<K, V>> {
+        match queue {
+            Queue::Small => &mut self.small,
+            Queue::Main => &mut self.main,
+        }
+    }
+
+    // Evict one entry. The small queue is evicted while it holds at
+    // least its share of the capacity, and the main queue otherwise.
+    // The entries that are spared are moved or reinserted, so the
+    // loop ends once the counters they spent have run out.
+    fn evict(&mut self) -> Option<(K, V)> {
+        loop {
+            if self.small.len() >= self.small_capacity || self.main.len() == 0 {
+                let index = self.small.back()?;
+                if self.small.get(index).freq > 1 {
+                    let index = self.transfer((Queue::Small, index), Queue::Main);
+                    self.main.get_mut(index).freq = 0;
+                    continue;
+                }
+                let e = self.take_entry((Queue::Small, index));
+                self.remember(e.hash);
+                return Some((e.key, e.val));
+            }
+            let index = self.main.back()?;
+            let e = self.main.get_mut(index);
+            if e.freq > 0 {
+                e.freq -= 1;
+                self.main.move_to_front(index);
+                continue;
+            }
+            let e = self.take_entry((Queue::Main, index));
+            return Some((e.key, e.val));
+        }
+    }
+
+    // Move an entry to the front of another queue. Returns its new index.
+    fn transfer(&mut self, from: (Queue, usize), to: Queue) -> usize {
+        let e = self.queue_mut(from.0).remove(from.1);
+        let hash = e.hash;
+        let index = self.queue_mut(to).push_front(e);
+        *self.table
+            .find_mut(hash, |&loc| loc == from)
+            .expect("table and queues are out of sync") = (to, index);
+        index
+    }
+
+    // Add a hash to the ghost queue and forget the oldest hash if it is full.
+    fn remember(&mut self, hash: u64) {
+        if self.ghost_capacity == 0 {
+            return;
+        }
+        if self.ghost.len() >= self.ghost_capacity {
+            if let Some(index) = self.ghost.back() {
+                let hash = self.ghost.remove(index);
+                self.ghosts
+                    .find_entry(hash, |&i| i == index)
+                    .expect("ghost table and ghost queue are out of sync")
+                    .remove();
+            }
+        }
+        let index = self.ghost.push_front(hash);
+        let ghost = &self.ghost;
+        self.ghosts.insert_unique(hash, index, |&i| *ghost.get(i));
+    }
+
+    // Remove a hash from the ghost queue. Returns true if it was remembered.
+    fn take_ghost(&mut self, hash: u64) -> bool {
+        let ghost = &self.ghost;
+        match self.ghosts.find_entry(hash, |&i| *ghost.get(i) == hash) {
+            Ok(entry) => {
+                let (index, _) = entry.remove();
+                self.ghost.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    // Remove a cached entry from the table and its queue.
+    fn take_entry(&mut self, loc: (Queue, usize)) -> Entry<K, V> {
+        let hash = self.queue(loc.0).get(loc.1).hash;
+        self.table
+            .find_entry(hash, |&l| l == loc)
+            .expect("table and queues are out of sync")
+            .remove();
+        self.queue_mut(loc.0).remove(loc.1)
+    }
+}
+
+fn entry_hash<K, V>(small: &List<Entry<K, V>>,
+                    main: &List<Entry<K, V>>,
+                    (queue, index): (Queue, usize))
+                    -> u64 {
+    match queue {
+        Queue::Small => small.get(index).hash,
+        Queue::Main => main.get(index).hash,
+    }
+}
+
+#[test]
+fn s3fifo_queues() {
+    // The small queue holds one entry and the ghosts remember three keys.
+    let mut cache = S3FIFOCache::with_ratio(4, 0.25);
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&0);
+    cache.get(&0);
+    cache.get(&1);
+    // An entry that was accessed more than once moves to the main queue.
+    assert_eq!(Some((1, 1)), cache.insert(4, 4).evicted);
+    assert_eq!((3, 1, 1), cache.queues());
+    // A key that is remembered by the ghosts enters the main queue.
+    assert_eq!(Some((2, 2)), cache.insert(1, 1).evicted);
+    assert_eq!((2, 2, 1), cache.queues());
+    // A scan only evicts the small queue.
+    for i in 10..20 {
+        cache.insert(i, i);
+    }
+    assert!(cache.contains_key(&0) && cache.contains_key(&1));
+    assert_eq!((2, 2, 3), cache.queues());
+    cache.get(&0);
+    cache.insert(17, 17);
+    cache.insert(16, 16);
+    assert_eq!((0, 4, 3), cache.queues());
+    // The main queue spares the entries that were accessed.
+    assert_eq!(Some((1, 1)), cache.insert(20, 20).evicted);
+    assert!(cache.contains_key(&0));
+}
@@ -0,0 +1,278 @@
+//! SIEVE cache, as described by Zhang, Yang, Yue, Vigfusson and Rashmi
+//! in "SIEVE is Simpler than LRU: an Efficient Turn-Key Eviction
+//! Algorithm for Web Caches" (NSDI 2024).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // set by each access and cleared when the hand passes the entry
+    visited: bool,
+}
+
+// The entries are kept in insertion order, and a hand moves from the
+// oldest entry towards the newest. An access only marks its entry as
+// visited. To evict, the hand clears the marks of the visited entries
+// it passes, the first entry that was not visited is evicted, and the
+// hand stays where the evicted entry was, wrapping to the oldest entry
+// once it reaches the newest. Unlike CLOCK, the entries that are spared
+// are not moved, so new entries are quickly evicted unless they are
+// accessed, while the popular entries stay behind the hand.
+pub struct SieveCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // slab that stores the entries, linked from the newest
+    // entry at the front to the oldest entry at the back
+    entries: List<Entry<K, V>>,
+    // the entry that is examined first by the next eviction,
+    // or none to start from the oldest entry
+    hand: Option<usize>,
+    stats: Stats,
+}
+
+impl<K, V> SieveCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> SieveCache<K, V> {
+        SieveCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<SieveCache<K, V>, CapacityError> {
+        SieveCache::try_with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> SieveCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> SieveCache<K, V, S> {
+        match SieveCache::try_with_hasher(capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           hash_builder: S)
+                           -> Result<SieveCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(SieveCache {
+            capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            hand: None,
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without marking the entry as visited.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).map(|index| &self.entries.get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // True if the entry of a key was accessed since the hand last passed it.
+    pub fn is_visited<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.index_of(hash, key).is_some_and(|index| self.entries.get(index).visited)
+    }
+
+    // Replacing the value of a key counts as an access
+    // and keeps the position of the key.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.index_of(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.entries.get_mut(index);
+            e.visited = true;
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.pop_victim()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.entries.push_front(Entry {
+            key,
+            val,
+            hash,
+            visited: false,
+        });
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key)?;
+        Some(self.take_entry(index).1)
+    }
+
+    // The entry that is evicted next, if no entry is accessed before.
+    pub fn peek_victim(&self) -> Option<(&K, &V)> {
+        let start = self.hand.or_else(|| self.entries.back())?;
+        // The hand passes every visited entry at most once, and
+        // stops at its starting entry if every entry was visited.
+        let mut index = start;
+        while self.entries.get(index).visited {
+            index = self.entries.prev(index).or_else(|| self.entries.back())?;
+            if index == start {
+                break;
+            }
+        }
+        let e = self.entries.get(index);
+        Some((&e.key, &e.val))
+    }
+
+    // Move the hand to the next entry that was not visited and evict it.
+    pub fn pop_victim(&mut self) -> Option<(K, V)> {
+        let mut index = self.hand.or_else(|| self.entries.back())?;
+        while self.entries.get(index).visited {
+            self.entries.get_mut(index).visited = false;
+            index = self.entries.prev(index).or_else(|| self.entries.back())?;
+        }
+        self.hand = Some(index);
+        Some(self.take_entry(index))
+    }
+
+    // The hand starts again from the oldest entry.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+        self.hand = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.index_of(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.entries.get_mut(index).visited = true;
+        Some(index)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Remove the entry with the given index from the table and the slab.
+    // The hand moves on to the next newer entry if it was on the entry.
+    fn take_entry(&mut self, index: usize) -> (K, V) {
+        if self.hand == Some(index) {
+            self.hand = self.entries.prev(index);
+        }
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let e = self.entries.remove(index);
+        (e.key, e.val)
+    }
+}
+
+#[test]
+fn sieve_eviction() {
+    let mut cache = SieveCache::new(3);
+    for i in 1..4 {
+        cache.insert(i, i);
+    }
+    cache.get(&1);
+    // The hand spares the visited entry and clears its mark.
+    assert_eq!(Some((2, 2)), cache.insert(4, 4).evicted);
+    assert!(!cache.is_visited(&1));
+    assert_eq!(Some((&3, &3)), cache.peek_victim());
+    cache.get(&4);
+    assert_eq!(Some((3, 3)), cache.insert(5, 5).evicted);
+    // A new entry ahead of the hand is evicted unless it is accessed.
+    assert_eq!(Some((5, 5)), cache.insert(6, 6).evicted);
+    // The hand wraps to the oldest entry.
+    assert_eq!(Some((&1, &1)), cache.peek_victim());
+    assert_eq!(Some((1, 1)), cache.insert(7, 7).evicted);
+    // Every entry was visited, so the hand evicts where it started.
+    cache.get(&4);
+    cache.get(&6);
+    cache.get(&7);
+    assert_eq!(Some((&4, &4)), cache.peek_victim());
+    assert_eq!(Some((4, 4)), cache.pop_victim());
+}
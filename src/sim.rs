@@ -0,0 +1,369 @@
+//! Replay of recorded access traces, to size a cache and to choose its
+//! replacement policy before it is deployed.
+
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use cache::Cache;
+use stats::StatsSnapshot;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, Write};
+#[cfg(feature = "std")]
+use std::panic;
+#[cfg(feature = "std")]
+use std::string::String;
+#[cfg(feature = "std")]
+use std::thread;
+
+// The outcome of a replay. The counters cover only the accesses of the
+// trace, even if the cache was used before the replay.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    // counters of the whole trace
+    pub total: StatsSnapshot,
+    // counters of each phase of the trace, in order.
+    // The last phase may be shorter than the others.
+    pub phases: Vec<StatsSnapshot>,
+}
+
+impl Report {
+    // number of accesses in the trace
+    pub fn accesses(&self) -> u64 {
+        self.total.lookups()
+    }
+
+    // fraction of the accesses that hit
+    pub fn hit_ratio(&self) -> f64 {
+        self.total.hit_ratio()
+    }
+
+    // number of entries that were evicted by capacity
+    pub fn evictions(&self) -> u64 {
+        self.total.evictions
+    }
+}
+
+// Replay a trace of keys against a cache. Every access is a lookup, and
+// a miss inserts the key with the default value, as a cache in front of
+// a slower store would. The whole trace is a single phase.
+pub fn replay<K, V, C, I>(cache: &mut C, trace: I) -> Report
+    where V: Default,
+          C: Cache<K, V> + ?Sized,
+          I: IntoIterator<Item = K>
+{
+    replay_phased(cache, trace, usize::MAX)
+}
+
+// Replay a trace like replay(), and report the counters of each run of
+// the given number of accesses separately, to show how the cache warms
+// up and how it follows the changes of the workload.
+// Panics if the length of a phase is zero.
+pub fn replay_phased<K, V, C, I>(cache: &mut C, trace: I, phase: usize) -> Report
+    where V: Default,
+          C: Cache<K, V> + ?Sized,
+          I: IntoIterator<Item = K>
+{
+    assert!(phase > 0, "a phase must have at least one access");
+    let start = cache.stats().snapshot();
+    let mut report = Report::default();
+    let mut mark = start;
+    let mut count = 0;
+    for key in trace {
+        if cache.get(&key).is_none() {
+            cache.insert(key, V::default());
+        }
+        count += 1;
+        if count == phase {
+            let now = cache.stats().snapshot();
+            report.phases.push(now - mark);
+            mark = now;
+            count = 0;
+        }
+    }
+    let end = cache.stats().snapshot();
+    if count > 0 {
+        report.phases.push(end - mark);
+    }
+    report.total = end - start;
+    report
+}
+
+// The keys of a trace in the text format: each line holds one key, and
+// the surrounding whitespace is not part of the key. Empty lines and
+// lines that start with '#' are skipped.
+pub fn parse_trace(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+// Read a trace in the text format of parse_trace().
+#[cfg(feature = "std")]
+pub fn read_trace<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut keys = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let key = line.trim();
+        if !key.is_empty() && !key.starts_with('#') {
+            keys.push(String::from(key));
+        }
+    }
+    Ok(keys)
+}
+
+// builds an empty cache of the given capacity
+#[cfg(feature = "std")]
+type Factory<K, V> = Box<dyn Fn(usize) -> Box<dyn Cache<K, V>> + Send + Sync>;
+
+// Replays of one trace against several policies at several capacities.
+// Every policy is replayed at every capacity against a cache of its own.
+// The replays run on threads in batches, with at most one thread per
+// available core by default.
+#[cfg(feature = "std")]
+pub struct Comparison<K, V> {
+    // the names of the policies and how to build their caches
+    policies: Vec<(String, Factory<K, V>)>,
+    capacities: Vec<usize>,
+    // maximum number of replays that run at once, or None for
+    // the available parallelism of the machine
+    threads: Option<usize>,
+}
+
+// The replay of a trace against one policy at one capacity.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub policy: String,
+    pub capacity: usize,
+    pub report: Report,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> Default for Comparison<K, V> {
+    fn default() -> Comparison<K, V> {
+        Comparison::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> Comparison<K, V> {
+    pub fn new() -> Comparison<K, V> {
+        Comparison {
+            policies: Vec::new(),
+            capacities: Vec::new(),
+            threads: None,
+        }
+    }
+
+    // Add a policy with the function that builds an empty cache of the
+    // policy for a capacity, such as |capacity| Box::new(LRUCache::new(capacity)).
+    pub fn add_policy<F>(&mut self, name: &str, build: F)
+        where F: Fn(usize) -> Box<dyn Cache<K, V>> + Send + Sync + 'static
+    {
+        self.policies.push((String::from(name), Box::new(build)));
+    }
+
+    pub fn add_capacity(&mut self, capacity: usize) {
+        self.capacities.push(capacity);
+    }
+
+    // Limit the number of replays that run at once. A limit of zero
+    // is treated as one.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = Some(threads.max(1));
+    }
+
+    // Replay the trace against every policy at every capacity. The
+    // outcomes are ordered by policy, then by capacity, in the order
+    // that they were added. A panic of a replay is propagated.
+    pub fn run(&self, trace: &[K]) -> Vec<Outcome>
+        where K: Clone + Sync,
+              V: Default
+    {
+        let threads = self.threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+        let mut jobs = Vec::new();
+        for (name, build) in &self.policies {
+            for &capacity in &self.capacities {
+                jobs.push((name, build, capacity));
+            }
+        }
+        let mut outcomes = Vec::with_capacity(jobs.len());
+        for batch in jobs.chunks(threads) {
+            thread::scope(|scope| {
+                let replays: Vec<_> = batch.iter()
+                    .map(|&(name, build, capacity)| {
+                        let replay = scope.spawn(move || {
+                            let mut cache = build(capacity);
+                            replay(&mut *cache, trace.iter().cloned())
+                        });
+                        (name, capacity, replay)
+                    })
+                    .collect();
+                for (name, capacity, replay) in replays {
+                    outcomes.push(Outcome {
+                        policy: name.clone(),
+                        capacity,
+                        report: replay.join().unwrap_or_else(|e| panic::resume_unwind(e)),
+                    });
+                }
+            });
+        }
+        outcomes
+    }
+}
+
+// Write one line for each outcome, after a line of column names.
+// The names of the policies are quoted as in RFC 4180 if they
+// contain a comma, a quote or a line break.
+#[cfg(feature = "std")]
+pub fn write_csv<W: Write>(outcomes: &[Outcome], mut out: W) -> io::Result<()> {
+    writeln!(out, "policy,capacity,accesses,hits,misses,evictions,hit_ratio")?;
+    for o in outcomes {
+        let total = &o.report.total;
+        writeln!(out,
+                 "{},{},{},{},{},{},{:.4}",
+                 csv_field(&o.policy),
+                 o.capacity,
+                 total.lookups(),
+                 total.hits,
+                 total.misses,
+                 total.evictions,
+                 total.hit_ratio())?;
+    }
+    Ok(())
+}
+
+// A field of a CSV record, in quotes if it must be.
+#[cfg(feature = "std")]
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+// Write the hit ratios in a table with a row for each policy and a
+// column for each capacity, in the order of the outcomes.
+#[cfg(feature = "std")]
+pub fn write_table<W: Write>(outcomes: &[Outcome], mut out: W) -> io::Result<()> {
+    let mut policies: Vec<&str> = Vec::new();
+    let mut capacities: Vec<usize> = Vec::new();
+    for o in outcomes {
+        if !policies.contains(&o.policy.as_str()) {
+            policies.push(&o.policy);
+        }
+        if !capacities.contains(&o.capacity) {
+            capacities.push(o.capacity);
+        }
+    }
+    let width = policies.iter().map(|p| p.len()).max().unwrap_or(0).max("policy".len());
+    write!(out, "{:<1$}", "policy", width)?;
+    for capacity in &capacities {
+        write!(out, " {:>8}", capacity)?;
+    }
+    writeln!(out)?;
+    for policy in policies {
+        write!(out, "{:<1$}", policy, width)?;
+        for &capacity in &capacities {
+            match outcomes.iter().find(|o| o.policy == policy && o.capacity == capacity) {
+                Some(o) => write!(out, " {:>8.4}", o.report.hit_ratio())?,
+                None => write!(out, " {:>8}", "-")?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn sim_replay() {
+    use lru::LRUCache;
+
+    let mut cache = LRUCache::new(2);
+    cache.get(&0);
+    // The miss before the replay is not reported.
+    let report = replay::<_, (), _, _>(&mut cache, vec![1, 2, 1, 3, 2, 1]);
+    assert_eq!((6, 1, 3), (report.accesses(), report.total.hits, report.evictions()));
+    assert_eq!(1, report.phases.len());
+    assert!((report.hit_ratio() - 1.0 / 6.0).abs() < 1e-9);
+    // A cache that holds the loop misses only in the first phase.
+    let mut cache = LRUCache::new(3);
+    let trace = (0..10).map(|i| i % 3);
+    let report = replay_phased::<_, (), _, _>(&mut cache, trace, 3);
+    let hits: Vec<u64> = report.phases.iter().map(|p| p.hits).collect();
+    assert_eq!(vec![0, 3, 3, 1], hits);
+    assert_eq!(report.total, report.phases.iter().cloned().sum());
+}
+
+#[test]
+fn sim_trace() {
+    use sieve::SieveCache;
+
+    let text = "# trace\na\n  b \n\na\n#c\nd\n";
+    let keys: Vec<&str> = parse_trace(text).collect();
+    assert_eq!(vec!["a", "b", "a", "d"], keys);
+    #[cfg(feature = "std")]
+    assert_eq!(keys, read_trace(text.as_bytes()).unwrap());
+    let mut cache: Box<dyn Cache<&str, u64>> = Box::new(SieveCache::new(4));
+    let report = replay(&mut *cache, parse_trace(text));
+    assert_eq!((1, 3, 0), (report.total.hits, report.total.insertions, report.evictions()));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sim_compare() {
+    use lru::LRUCache;
+    use mru::MRUCache;
+
+    let mut comparison = Comparison::<u32, ()>::new();
+    comparison.add_policy("lru", |capacity| Box::new(LRUCache::new(capacity)));
+    comparison.add_policy("mru", |capacity| Box::new(MRUCache::new(capacity)));
+    comparison.add_capacity(4);
+    comparison.add_capacity(8);
+    let trace: Vec<u32> = (0..48).map(|i| i % 6).collect();
+    let outcomes = comparison.run(&trace);
+    // Fewer threads than replays give the same outcomes in the same order.
+    comparison.set_threads(3);
+    assert_eq!(outcomes, comparison.run(&trace));
+    let order: Vec<(&str, usize)> = outcomes.iter().map(|o| (o.policy.as_str(), o.capacity)).collect();
+    assert_eq!(vec![("lru", 4), ("lru", 8), ("mru", 4), ("mru", 8)], order);
+    // The loop defeats LRU unless it fits in the cache.
+    assert_eq!(0, outcomes[0].report.total.hits);
+    assert_eq!(42, outcomes[1].report.total.hits);
+    assert!(outcomes[2].report.total.hits > 0);
+    assert_eq!(outcomes[1].report, outcomes[3].report);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn sim_output() {
+    let outcome = |policy: &str, capacity, hits, misses| {
+        Outcome {
+            policy: String::from(policy),
+            capacity,
+            report: Report {
+                total: StatsSnapshot { hits, misses, ..StatsSnapshot::default() },
+                phases: Vec::new(),
+            },
+        }
+    };
+    let outcomes = vec![outcome("lru", 10, 1, 3), outcome("tinylfu", 10, 3, 1), outcome("lru", 20, 2, 2)];
+    let mut csv = Vec::new();
+    write_csv(&[outcome("lru, \"scan\"\nresistant", 10, 1, 3)], &mut csv).unwrap();
+    assert_eq!("policy,capacity,accesses,hits,misses,evictions,hit_ratio\n\
+                \"lru, \"\"scan\"\"\nresistant\",10,4,1,3,0,0.2500\n",
+               String::from_utf8(csv).unwrap());
+    let mut csv = Vec::new();
+    write_csv(&outcomes, &mut csv).unwrap();
+    assert_eq!("policy,capacity,accesses,hits,misses,evictions,hit_ratio\n\
+                lru,10,4,1,3,0,0.2500\n\
+                tinylfu,10,4,3,1,0,0.7500\n\
+                lru,20,4,2,2,0,0.5000\n",
+               String::from_utf8(csv).unwrap());
+    let mut table = Vec::new();
+    write_table(&outcomes, &mut table).unwrap();
+    assert_eq!("policy        10       20\n\
+                lru       0.2500   0.5000\n\
+                tinylfu   0.7500        -\n",
+               String::from_utf8(table).unwrap());
+}
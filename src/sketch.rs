@@ -0,0 +1,106 @@
+//! A count-min sketch that estimates how often the hashes of the keys
+//! were seen, with periodic aging so that old popularity fades.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+// number of rows, each indexed by a different mix of the hash
+const DEPTH: usize = 4;
+// largest value of a counter
+const MAX_COUNT: u8 = 15;
+// seeds of the mixes of the rows
+const SEEDS: [u64; DEPTH] = [0x97cb_3127_3c6f_1c45, 0xb8f1_9d0a_56e2_e6b1, 0xe43f_6c8d_b0a1_f94b, 0x61c8_8646_80b5_83eb];
+
+// The estimate of a hash is the smallest of its counters, one in each
+// row, so it is never lower than the true count of increments, up to
+// the saturation of the counters. After the number of increments reaches
+// the sample size, every counter is halved.
+#[derive(Debug, Clone)]
+pub struct FrequencySketch {
+    counters: Vec<u8>,
+    // number of counters in a row, a power of two
+    width: usize,
+    // increments since the last aging
+    additions: usize,
+    // increments after which the counters are halved
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    // Each row has four counters for each of the expected distinct keys.
+    pub fn new(capacity: usize) -> FrequencySketch {
+        let width = (capacity.max(8) * 4).next_power_of_two();
+        FrequencySketch {
+            counters: vec![0; DEPTH * width],
+            width,
+            additions: 0,
+            sample_size: capacity.max(1) * 10,
+        }
+    }
+
+    // The estimated count of a hash since it last aged, at most 15.
+    pub fn frequency(&self, hash: u64) -> u8 {
+        (0..DEPTH).map(|row| self.counters[self.index(row, hash)]).min().unwrap_or(0)
+    }
+
+    pub fn increment(&mut self, hash: u64) {
+        let mut added = false;
+        for row in 0..DEPTH {
+            let i = self.index(row, hash);
+            if self.counters[i] < MAX_COUNT {
+                self.counters[i] += 1;
+                added = true;
+            }
+        }
+        if added {
+            self.additions += 1;
+            if self.additions >= self.sample_size {
+                self.age();
+            }
+        }
+    }
+
+    // Halve every counter.
+    pub fn age(&mut self) {
+        for c in &mut self.counters {
+            *c /= 2;
+        }
+        self.additions /= 2;
+    }
+
+    pub fn clear(&mut self) {
+        for c in &mut self.counters {
+            *c = 0;
+        }
+        self.additions = 0;
+    }
+
+    fn index(&self, row: usize, hash: u64) -> usize {
+        let mut h = (hash ^ SEEDS[row]).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        h ^= h >> 32;
+        row * self.width + (h as usize & (self.width - 1))
+    }
+}
+
+#[test]
+fn sketch_aging() {
+    let mut sketch = FrequencySketch::new(100);
+    for _ in 0..5 {
+        sketch.increment(1);
+    }
+    sketch.increment(2);
+    assert!(sketch.frequency(1) >= 5 && sketch.frequency(2) >= 1);
+    assert!(sketch.frequency(1) > sketch.frequency(2));
+    // The counters saturate.
+    for _ in 0..100 {
+        sketch.increment(3);
+    }
+    assert_eq!(MAX_COUNT, sketch.frequency(3));
+    // The sample size of 1000 increments halves the counters.
+    for hash in 10..1000 {
+        sketch.increment(hash);
+    }
+    assert_eq!(MAX_COUNT / 2, sketch.frequency(3));
+    sketch.clear();
+    assert_eq!(0, sketch.frequency(3));
+}
@@ -0,0 +1,345 @@
+//! Segmented LRU cache, which promotes an entry to a protected segment
+//! on its second access.
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// default share of the capacity that is held by the protected segment
+pub const DEFAULT_PROTECTED_RATIO: f64 = 0.8;
+
+// The segment of a cached entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    // the entries that were not accessed since they were inserted
+    // or demoted
+    Probation,
+    // the entries that were accessed at least twice
+    Protected,
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+}
+
+// New entries enter the probationary segment and are promoted to the
+// protected segment when they are accessed again. When the protected
+// segment outgrows its share of the capacity, its least-recently used
+// entry is demoted to the front of the probationary segment, so it has
+// another chance to be accessed before it is evicted. Eviction takes the
+// least-recently used entry of the probationary segment, and the
+// protected segment only when there is no probationary entry. An entry
+// that is used once cannot displace an entry that is used repeatedly.
+//
+// LRUCache::set_probation() applies the same policy to an LRUCache,
+// together with its weights, pins and expiration.
+pub struct SLRUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // maximum number of entries in the protected segment
+    protected_capacity: usize,
+    hash_builder: S,
+    // locations of the entries in the segments, hashed by key
+    table: HashTable<(Segment, usize)>,
+    // segments from the most-recently used entry at the front
+    // to the least-recently used entry at the back
+    probation: List<Entry<K, V>>,
+    protected: List<Entry<K, V>>,
+    stats: Stats,
+}
+
+impl<K, V> SLRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> SLRUCache<K, V> {
+        SLRUCache::with_ratio(capacity, DEFAULT_PROTECTED_RATIO)
+    }
+
+    // The protected segment holds protected_ratio of the capacity, rounded.
+    // Panics if the capacity is zero or the ratio is not between 0 and 1.
+    pub fn with_ratio(capacity: usize, protected_ratio: f64) -> SLRUCache<K, V> {
+        SLRUCache::with_hasher(capacity, protected_ratio, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<SLRUCache<K, V>, CapacityError> {
+        SLRUCache::try_with_hasher(capacity, DEFAULT_PROTECTED_RATIO, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> SLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero or the ratio is not between 0 and 1.
+    pub fn with_hasher(capacity: usize, protected_ratio: f64, hash_builder: S) -> SLRUCache<K, V, S> {
+        match SLRUCache::try_with_hasher(capacity, protected_ratio, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the ratio is not between 0 and 1.
+    pub fn try_with_hasher(capacity: usize,
+                           protected_ratio: f64,
+                           hash_builder: S)
+                           -> Result<SLRUCache<K, V, S>, CapacityError> {
+        assert!((0.0..=1.0).contains(&protected_ratio), "protected ratio must be between 0 and 1");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(SLRUCache {
+            capacity,
+            protected_capacity: (capacity as f64 * protected_ratio + 0.5) as usize,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            probation: List::with_capacity(capacity),
+            protected: List::new(),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without promoting the entry.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(segment, index)| &self.segment(segment).get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Whether the entry of a key is in the protected segment.
+    pub fn is_protected<Q>(&self, key: &Q) -> Option<bool>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(segment, _)| segment == Segment::Protected)
+    }
+
+    // Replacing the value of a key counts as an access.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(loc) = self.locate(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.access(loc);
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.pop_lru()
+        } else {
+            None
+        };
+        self.stats.insertions += 1;
+        let index = self.probation.push_front(Entry { key, val, hash });
+        let (probation, protected) = (&self.probation, &self.protected);
+        self.table.insert_unique(hash, (Segment::Probation, index), |&loc| {
+            entry_hash(probation, protected, loc)
+        });
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // Remove the entry that is evicted next, the least-recently used
+    // probationary entry if there is one.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let loc = match self.probation.back() {
+            Some(index) => (Segment::Probation, index),
+            None => (Segment::Protected, self.protected.back()?),
+        };
+        let e = self.take_entry(loc);
+        Some((e.key, e.val))
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.probation.clear();
+        self.protected.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.probation.len() + self.protected.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn protected_capacity(&self) -> usize {
+        self.protected_capacity
+    }
+
+    // The number of entries in the probationary and the protected segment.
+    pub fn segments(&self) -> (usize, usize) {
+        (self.probation.len(), self.protected.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        Some(self.access(loc?))
+    }
+
+    // Promote a probationary entry, or move a protected entry to the
+    // front of the protected segment. Without a protected segment,
+    // the probationary segment is a plain LRU queue.
+    fn access(&mut self, (segment, index): (Segment, usize)) -> &mut Entry<K, V> {
+        match segment {
+            Segment::Protected => self.protected.move_to_front(index),
+            Segment::Probation if self.protected_capacity == 0 => self.probation.move_to_front(index),
+            Segment::Probation => {
+                let index = self.transfer((Segment::Probation, index), Segment::Protected);
+                if self.protected.len() > self.protected_capacity {
+                    if let Some(lru) = self.protected.back() {
+                        self.transfer((Segment::Protected, lru), Segment::Probation);
+                    }
+                }
+                return self.protected.get_mut(index);
+            }
+        }
+        self.segment_mut(segment).get_mut(index)
+    }
+
+    // Move an entry to the front of another segment. Returns its new index.
+    fn transfer(&mut self, from: (Segment, usize), to: Segment) -> usize {
+        let e = self.segment_mut(from.0).remove(from.1);
+        let hash = e.hash;
+        let index = self.segment_mut(to).push_front(e);
+        *self.table
+            .find_mut(hash, |&loc| loc == from)
+            .expect("table and segments are out of sync") = (to, index);
+        index
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(Segment, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(segment, i)| self.segment(segment).get(i).key.borrow() == key)
+            .copied()
+    }
+
+    fn segment(&self, segment: Segment) -> &List<Entry<K, V>> {
+        match segment {
+            Segment::Probation => &self.probation,
+            Segment::Protected => &self.protected,
+        }
+    }
+
+    fn segment_mut(&mut self, segment: Segment) -> &mut List<Entry<K, V>> {
+        match segment {
+            Segment::Probation => &mut self.probation,
+            Segment::Protected => &mut self.protected,
+        }
+    }
+
+    // Remove an entry from the table and its segment.
+    fn take_entry(&mut self, loc: (Segment, usize)) -> Entry<K, V> {
+        let hash = self.segment(loc.0).get(loc.1).hash;
+        self.table
+            .find_entry(hash, |&l| l == loc)
+            .expect("table and segments are out of sync")
+            .remove();
+        self.segment_mut(loc.0).remove(loc.1)
+    }
+}
+
+fn entry_hash<K, V>(probation: &List<Entry<K, V>>,
+                    protected: &List<Entry<K, V>>,
+                    (segment, index): (Segment, usize))
+                    -> u64 {
+    match segment {
+        Segment::Probation => probation.get(index).hash,
+        Segment::Protected => protected.get(index).hash,
+    }
+}
+
+#[test]
+fn slru_segments() {
+    let mut cache = SLRUCache::with_ratio(4, 0.5);
+    for i in 0..4 {
+        cache.insert(i, i);
+    }
+    // Entries are promoted on their second access.
+    cache.get(&0);
+    cache.get(&1);
+    assert_eq!((Some(true), Some(false)), (cache.is_protected(&0), cache.is_protected(&2)));
+    assert_eq!((2, 2), cache.segments());
+    // A scan of new keys only evicts probationary entries.
+    for i in 10..20 {
+        cache.insert(i, i);
+    }
+    assert!(cache.contains_key(&0) && cache.contains_key(&1));
+    // A promotion beyond the protected capacity demotes
+    // the least-recently used protected entry.
+    cache.get(&19);
+    assert_eq!((Some(false), Some(true)), (cache.is_protected(&0), cache.is_protected(&19)));
+    assert_eq!((2, 2), cache.segments());
+    // The demoted entry is evicted after the older probationary entry.
+    assert_eq!(Some((18, 18)), cache.insert(20, 20).evicted);
+    assert_eq!(Some((0, 0)), cache.insert(21, 21).evicted);
+    // Without a protected segment, the cache is an LRU cache.
+    let mut cache = SLRUCache::with_ratio(2, 0.0);
+    cache.insert(0, 0);
+    cache.insert(1, 1);
+    cache.get(&0);
+    assert_eq!((2, 0), cache.segments());
+    assert_eq!(Some((1, 1)), cache.insert(2, 2).evicted);
+    assert_eq!(0, cache.protected_capacity());
+    assert_eq!(Some((0, 0)), cache.pop_lru());
+}
@@ -0,0 +1,156 @@
+//! Counters that describe the effectiveness of a cache.
+
+use core::iter::Sum;
+use core::ops::{Add, AddAssign, Sub};
+
+// Running totals that are maintained by a cache as it is used.
+// Lookups through peek() and contains_key() are not counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+    pub(crate) insertions: u64,
+    pub(crate) replacements: u64,
+    pub(crate) evictions: u64,
+    pub(crate) negative_hits: u64,
+    pub(crate) stale_hits: u64,
+}
+
+impl Stats {
+    // number of lookups that located the key
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    // number of lookups that did not locate the key
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    // number of keys that were added to the cache
+    pub fn insertions(&self) -> u64 {
+        self.insertions
+    }
+
+    // number of values that were overwritten for a cached key
+    pub fn replacements(&self) -> u64 {
+        self.replacements
+    }
+
+    // number of entries that were evicted by capacity
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    // number of lookups that located a key that is known to be absent.
+    // These lookups are neither hits nor misses.
+    pub fn negative_hits(&self) -> u64 {
+        self.negative_hits
+    }
+
+    // number of lookups that returned an expired value as stale.
+    // These lookups are neither hits nor misses.
+    pub fn stale_hits(&self) -> u64 {
+        self.stale_hits
+    }
+
+    // fraction of lookups that located the key.
+    // Zero if there have been no lookups.
+    pub fn hit_ratio(&self) -> f64 {
+        self.snapshot().hit_ratio()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            hits: self.hits,
+            misses: self.misses,
+            insertions: self.insertions,
+            replacements: self.replacements,
+            evictions: self.evictions,
+            negative_hits: self.negative_hits,
+            stale_hits: self.stale_hits,
+        }
+    }
+
+    pub(crate) fn record_lookup(&mut self, hit: bool) {
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+}
+
+// A copy of the counters at a point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct StatsSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    pub insertions: u64,
+    pub replacements: u64,
+    pub evictions: u64,
+    pub negative_hits: u64,
+    pub stale_hits: u64,
+}
+
+impl StatsSnapshot {
+    // total number of lookups
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    // fraction of lookups that located the key.
+    // Zero if there have been no lookups.
+    pub fn hit_ratio(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            n => self.hits as f64 / n as f64,
+        }
+    }
+}
+
+// Snapshots of several caches are added to describe them together.
+impl Add for StatsSnapshot {
+    type Output = StatsSnapshot;
+
+    fn add(mut self, other: StatsSnapshot) -> StatsSnapshot {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for StatsSnapshot {
+    fn add_assign(&mut self, other: StatsSnapshot) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.insertions += other.insertions;
+        self.replacements += other.replacements;
+        self.evictions += other.evictions;
+        self.negative_hits += other.negative_hits;
+        self.stale_hits += other.stale_hits;
+    }
+}
+
+// The difference of two snapshots of one cache counts the
+// operations between them. The earlier snapshot is subtracted.
+impl Sub for StatsSnapshot {
+    type Output = StatsSnapshot;
+
+    fn sub(self, earlier: StatsSnapshot) -> StatsSnapshot {
+        StatsSnapshot {
+            hits: self.hits - earlier.hits,
+            misses: self.misses - earlier.misses,
+            insertions: self.insertions - earlier.insertions,
+            replacements: self.replacements - earlier.replacements,
+            evictions: self.evictions - earlier.evictions,
+            negative_hits: self.negative_hits - earlier.negative_hits,
+            stale_hits: self.stale_hits - earlier.stale_hits,
+        }
+    }
+}
+
+impl Sum for StatsSnapshot {
+    fn sum<I: Iterator<Item = StatsSnapshot>>(iter: I) -> StatsSnapshot {
+        iter.fold(StatsSnapshot::default(), Add::add)
+    }
+}
@@ -0,0 +1,65 @@
+//! Sources of time for the time-based features of the caches.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use std::time::Instant;
+
+// A source of the current time. The expiration of entries
+// is measured with the clock of the cache.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+// The monotonic clock of the operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// A clock that only moves when it is advanced.
+// Clones of the clock share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: Instant,
+    // nanoseconds that the clock has been advanced
+    elapsed: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new() -> MockClock {
+        MockClock {
+            start: Instant::now(),
+            elapsed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> MockClock {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.elapsed.load(Ordering::SeqCst))
+    }
+}
+
+#[test]
+fn mock_clock() {
+    let clock = MockClock::new();
+    let start = clock.now();
+    assert_eq!(start, clock.now());
+    clock.clone().advance(Duration::from_secs(5));
+    assert_eq!(Duration::from_secs(5), clock.now() - start);
+}
@@ -0,0 +1,388 @@
+//! Window TinyLFU cache, as described by Einziger, Friedman and Manes in
+//! "TinyLFU: A Highly Efficient Cache Admission Policy" (ACM ToS 2017).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::cmp;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use sketch::FrequencySketch;
+use slru::DEFAULT_PROTECTED_RATIO;
+use stats::Stats;
+
+// default share of the capacity that is held by the window
+pub const DEFAULT_WINDOW_RATIO: f64 = 0.01;
+
+// The region of a cached entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    // the entries that were inserted recently
+    Window,
+    // the entries of the main region that were admitted or demoted,
+    // and not accessed since
+    Probation,
+    // the entries of the main region that were accessed again
+    Protected,
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+}
+
+// New entries enter the window, a small LRU queue. The entry that leaves
+// the window is a candidate for the main region, an SLRUCache-style pair
+// of probationary and protected segments. While the main region is not
+// full, every candidate is admitted. Otherwise the candidate is compared
+// with the victim, the entry that the main region would evict, and the
+// one that was accessed less often according to the frequency sketch is
+// evicted. The sketch counts every access, including the misses and the
+// accesses of keys that were evicted, and ages periodically, so that a
+// burst of new keys passes through the window without displacing the
+// popular entries while a key that becomes popular is admitted.
+pub struct WTinyLFUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // maximum number of entries in the window
+    window_capacity: usize,
+    // maximum number of entries in the protected segment
+    protected_capacity: usize,
+    hash_builder: S,
+    // locations of the entries in the regions, hashed by key
+    table: HashTable<(Region, usize)>,
+    // regions from the most-recently used entry at the front
+    // to the least-recently used entry at the back
+    window: List<Entry<K, V>>,
+    probation: List<Entry<K, V>>,
+    protected: List<Entry<K, V>>,
+    // estimated frequencies of the hashes of the accessed keys
+    sketch: FrequencySketch,
+    stats: Stats,
+}
+
+impl<K, V> WTinyLFUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> WTinyLFUCache<K, V> {
+        WTinyLFUCache::with_window(capacity, DEFAULT_WINDOW_RATIO)
+    }
+
+    // The window holds window_ratio of the capacity, rounded, and at
+    // least one entry. The protected segment holds 80% of the main region.
+    // Panics if the capacity is zero or the ratio is not between 0 and 1.
+    pub fn with_window(capacity: usize, window_ratio: f64) -> WTinyLFUCache<K, V> {
+        WTinyLFUCache::with_hasher(capacity, window_ratio, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<WTinyLFUCache<K, V>, CapacityError> {
+        WTinyLFUCache::try_with_hasher(capacity, DEFAULT_WINDOW_RATIO, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> WTinyLFUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero or the ratio is not between 0 and 1.
+    pub fn with_hasher(capacity: usize, window_ratio: f64, hash_builder: S) -> WTinyLFUCache<K, V, S> {
+        match WTinyLFUCache::try_with_hasher(capacity, window_ratio, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the ratio is not between 0 and 1.
+    pub fn try_with_hasher(capacity: usize,
+                           window_ratio: f64,
+                           hash_builder: S)
+                           -> Result<WTinyLFUCache<K, V, S>, CapacityError> {
+        assert!((0.0..=1.0).contains(&window_ratio), "window ratio must be between 0 and 1");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        let window_capacity = cmp::max(1, (capacity as f64 * window_ratio + 0.5) as usize);
+        let window_capacity = cmp::min(window_capacity, capacity);
+        let main = capacity - window_capacity;
+        Ok(WTinyLFUCache {
+            capacity,
+            window_capacity,
+            protected_capacity: (main as f64 * DEFAULT_PROTECTED_RATIO + 0.5) as usize,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            window: List::new(),
+            probation: List::new(),
+            protected: List::new(),
+            sketch: FrequencySketch::new(capacity),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without counting an access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(region, index)| &self.region(region).get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // The estimated number of accesses of a key since the sketch last
+    // aged, at most 15. The key does not have to be cached.
+    pub fn frequency<Q>(&self, key: &Q) -> u8
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.sketch.frequency(self.hash_builder.hash_one(key))
+    }
+
+    // Replacing the value of a key counts as an access. The entry that
+    // is evicted may be the one that left the window rather than the
+    // least-recently used entry.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        self.sketch.increment(hash);
+        if let Some(loc) = self.locate(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.access(loc);
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        let index = self.window.push_front(Entry { key, val, hash });
+        let (window, probation, protected) = (&self.window, &self.probation, &self.protected);
+        self.table.insert_unique(hash, (Region::Window, index), |&loc| {
+            entry_hash(window, probation, protected, loc)
+        });
+        let evicted = if self.window.len() > self.window_capacity {
+            self.admit()
+        } else {
+            None
+        };
+        if evicted.is_some() {
+            self.stats.evictions += 1;
+        }
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // The frequency sketch is cleared as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.window.clear();
+        self.probation.clear();
+        self.protected.clear();
+        self.sketch.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.window.len() + self.probation.len() + self.protected.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn window_capacity(&self) -> usize {
+        self.window_capacity
+    }
+
+    // The number of entries in the window, the probationary segment
+    // and the protected segment.
+    pub fn regions(&self) -> (usize, usize, usize) {
+        (self.window.len(), self.probation.len(), self.protected.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.sketch.increment(hash);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        Some(self.access(loc?))
+    }
+
+    // Move an entry of the window or the protected segment to the front
+    // of its region, or promote a probationary entry.
+    fn access(&mut self, (region, index): (Region, usize)) -> &mut Entry<K, V> {
+        match region {
+            Region::Window => self.window.move_to_front(index),
+            Region::Protected => self.protected.move_to_front(index),
+            Region::Probation if self.protected_capacity == 0 => self.probation.move_to_front(index),
+            Region::Probation => {
+                let index = self.transfer((Region::Probation, index), Region::Protected);
+                if self.protected.len() > self.protected_capacity {
+                    if let Some(lru) = self.protected.back() {
+                        self.transfer((Region::Protected, lru), Region::Probation);
+                    }
+                }
+                return self.protected.get_mut(index);
+            }
+        }
+        self.region_mut(region).get_mut(index)
+    }
+
+    // Move the least-recently used entry of the window to the main region,
+    // evicting either that entry or the victim of the main region if the
+    // main region is full.
+    fn admit(&mut self) -> Option<(K, V)> {
+        let candidate = self.window.back()?;
+        if self.probation.len() + self.protected.len() < self.capacity - self.window_capacity {
+            self.transfer((Region::Window, candidate), Region::Probation);
+            return None;
+        }
+        let victim = match self.probation.back() {
+            Some(index) => Some((Region::Probation, index)),
+            None => self.protected.back().map(|index| (Region::Protected, index)),
+        };
+        let evicted = match victim {
+            Some(victim) if self.frequency_of(victim) < self.frequency_of((Region::Window, candidate)) => {
+                let e = self.take_entry(victim);
+                self.transfer((Region::Window, candidate), Region::Probation);
+                e
+            }
+            _ => self.take_entry((Region::Window, candidate)),
+        };
+        Some((evicted.key, evicted.val))
+    }
+
+    fn frequency_of(&self, (region, index): (Region, usize)) -> u8 {
+        self.sketch.frequency(self.region(region).get(index).hash)
+    }
+
+    // Move an entry to the front of another region. Returns its new index.
+    fn transfer(&mut self, from: (Region, usize), to: Region) -> usize {
+        let e = self.region_mut(from.0).remove(from.1);
+        let hash = e.hash;
+        let index = self.region_mut(to).push_front(e);
+        *self.table
+            .find_mut(hash, |&loc| loc == from)
+            .expect("table and regions are out of sync") = (to, index);
+        index
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(Region, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(region, i)| self.region(region).get(i).key.borrow() == key)
+            .copied()
+    }
+
+    fn region(&self, region: Region) -> &List<Entry<K, V>> {
+        match region {
+            Region::Window => &self.window,
+            Region::Probation => &self.probation,
+            Region::Protected => &self.protected,
+        }
+    }
+
+    fn region_mut(&mut self, region: Region) -> &mut List<Entry<K, V>> {
+        match region {
+            Region::Window => &mut self.window,
+            Region::Probation => &mut self.probation,
+            Region::Protected => &mut self.protected,
+        }
+    }
+
+    // Remove an entry from the table and its region.
+    fn take_entry(&mut self, loc: (Region, usize)) -> Entry<K, V> {
+        let hash = self.region(loc.0).get(loc.1).hash;
+        self.table
+            .find_entry(hash, |&l| l == loc)
+            .expect("table and regions are out of sync")
+            .remove();
+        self.region_mut(loc.0).remove(loc.1)
+    }
+}
+
+fn entry_hash<K, V>(window: &List<Entry<K, V>>,
+                    probation: &List<Entry<K, V>>,
+                    protected: &List<Entry<K, V>>,
+                    (region, index): (Region, usize))
+                    -> u64 {
+    match region {
+        Region::Window => window.get(index).hash,
+        Region::Probation => probation.get(index).hash,
+        Region::Protected => protected.get(index).hash,
+    }
+}
+
+#[test]
+fn tinylfu_admission() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    // The hasher is deterministic so that the estimates of the sketch are.
+    let mut cache = WTinyLFUCache::with_hasher(10, 0.1, BuildHasherDefault::<DefaultHasher>::default());
+    assert_eq!(1, cache.window_capacity());
+    for i in 0..9 {
+        cache.insert(i, i);
+    }
+    for _ in 0..4 {
+        for i in 0..9 {
+            cache.get(&i);
+        }
+    }
+    // Every candidate of a scan is evicted in favor of the popular entries.
+    for i in 100..120 {
+        cache.insert(i, i);
+    }
+    assert!((0..9).all(|i| cache.contains_key(&i)));
+    assert_eq!((19, (1, 2, 7)), (cache.stats().evictions(), cache.regions()));
+    // A key that becomes popular is admitted.
+    for _ in 0..6 {
+        cache.get(&119);
+    }
+    let (evicted, _) = cache.insert(200, 200).evicted.unwrap();
+    assert!(evicted < 9 && cache.contains_key(&119));
+    assert!(cache.frequency(&119) >= 6);
+}
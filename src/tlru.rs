@@ -0,0 +1,343 @@
+//! Time-aware least-recently used cache, as described by Bilal and Kang
+//! in "Time Aware Least Recent Used (TLRU) Cache Management Policy in
+//! ICN" (ICACT 2014).
+
+use DefaultHashBuilder;
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use core::time::Duration;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+use std::time::Instant;
+use time::{Clock, SystemClock};
+
+// deadline of the time-to-use that is too long to be represented,
+// which is about a century from the time of the insertion
+const FAR_FUTURE: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+    // time after which the value must not be used
+    deadline: Instant,
+}
+
+// Every entry has a time-to-use, after which its value is stale and
+// treated as absent. An access does not extend the time-to-use, which
+// is set when the value is written. When the cache is full, the entry
+// whose time-to-use ran out first is evicted, and the least-recently
+// used entry is evicted only when no entry has run out, so fresh
+// content that is popular is kept over content that has gone stale.
+//
+// Expired entries are reclaimed when their key is accessed, when room
+// is needed, or by purge_expired(). Until then they count towards the
+// length of the cache.
+pub struct TLRUCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // time-to-use of the values that are inserted without their own
+    ttu: Duration,
+    hash_builder: S,
+    // indices of the entries in the slab, hashed by key
+    table: HashTable<usize>,
+    // the entries from the most-recently used at the front
+    // to the least-recently used at the back
+    entries: List<Entry<K, V>>,
+    // indices of the entries ordered by their deadlines
+    deadlines: BTreeSet<(Instant, usize)>,
+    // source of the current time
+    clock: Arc<dyn Clock>,
+    stats: Stats,
+}
+
+impl<K, V> TLRUCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize, ttu: Duration) -> TLRUCache<K, V> {
+        TLRUCache::with_hasher(capacity, ttu, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize, ttu: Duration) -> Result<TLRUCache<K, V>, CapacityError> {
+        TLRUCache::try_with_hasher(capacity, ttu, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> TLRUCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero.
+    pub fn with_hasher(capacity: usize, ttu: Duration, hash_builder: S) -> TLRUCache<K, V, S> {
+        match TLRUCache::try_with_hasher(capacity, ttu, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_with_hasher(capacity: usize,
+                           ttu: Duration,
+                           hash_builder: S)
+                           -> Result<TLRUCache<K, V, S>, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        Ok(TLRUCache {
+            capacity,
+            ttu,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            entries: List::with_capacity(capacity),
+            deadlines: BTreeSet::new(),
+            clock: Arc::new(SystemClock),
+            stats: Stats::default(),
+        })
+    }
+
+    // The time-to-use is measured with the clock, which is the system
+    // clock by default. The clock should be set before entries are
+    // written: existing deadlines are kept as they are.
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Arc::new(clock);
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&self.entries.get(index).val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries.get_mut(index).val)
+    }
+
+    // Read the value without promoting the entry.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let now = self.clock.now();
+        self.index_of(hash, key)
+            .map(|index| self.entries.get(index))
+            .filter(|e| now < e.deadline)
+            .map(|e| &e.val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // The time left before the value of a key expires.
+    pub fn remaining<Q>(&self, key: &Q) -> Option<Duration>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let now = self.clock.now();
+        self.index_of(hash, key)
+            .map(|index| self.entries.get(index).deadline)
+            .filter(|&deadline| now < deadline)
+            .map(|deadline| deadline - now)
+    }
+
+    // Insert a value with the time-to-use of the cache.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let ttu = self.ttu;
+        self.insert_with_ttu(key, val, ttu)
+    }
+
+    // Replacing the value of a key promotes the entry and
+    // restarts its time-to-use.
+    pub fn insert_with_ttu(&mut self, key: K, val: V, ttu: Duration) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        let now = self.clock.now();
+        let deadline = now.checked_add(ttu).unwrap_or_else(|| now + FAR_FUTURE);
+        if let Some(index) = self.live_index(hash, &key) {
+            self.stats.replacements += 1;
+            self.entries.move_to_front(index);
+            let e = self.entries.get_mut(index);
+            self.deadlines.remove(&(e.deadline, index));
+            self.deadlines.insert((deadline, index));
+            e.deadline = deadline;
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        let evicted = if self.len() >= self.capacity {
+            self.stats.evictions += 1;
+            self.evict()
+        } else {
+            None
+        };
+        let index = self.entries.push_front(Entry {
+            key,
+            val,
+            hash,
+            deadline,
+        });
+        self.deadlines.insert((deadline, index));
+        let entries = &self.entries;
+        self.table.insert_unique(hash, index, |&i| entries.get(i).hash);
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.live_index(hash, key)?;
+        Some(self.take_entry(index).val)
+    }
+
+    // Expired entries are reclaimed lazily. The sweep reclaims every
+    // expired entry and returns the number of entries that were removed.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut purged = 0;
+        while let Some(&(deadline, index)) = self.deadlines.iter().next() {
+            if now < deadline {
+                break;
+            }
+            drop(self.take_entry(index));
+            purged += 1;
+        }
+        purged
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.entries.clear();
+        self.deadlines.clear();
+    }
+
+    // The expired entries that have not been reclaimed are counted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The time-to-use of the values that are inserted without their own.
+    pub fn ttu(&self) -> Duration {
+        self.ttu
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.live_index(hash, key);
+        self.stats.record_lookup(index.is_some());
+        let index = index?;
+        self.entries.move_to_front(index);
+        Some(index)
+    }
+
+    fn index_of<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table.find(hash, |&i| self.entries.get(i).key.borrow() == key).copied()
+    }
+
+    // Find the index of the entry of a key in the slab.
+    // An entry that has expired is reclaimed and not returned.
+    fn live_index<Q>(&mut self, hash: u64, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        let index = self.index_of(hash, key)?;
+        if self.entries.get(index).deadline <= self.clock.now() {
+            drop(self.take_entry(index));
+            return None;
+        }
+        Some(index)
+    }
+
+    // Evict the entry whose time-to-use ran out first if there
+    // is one, or else the least-recently used entry.
+    fn evict(&mut self) -> Option<(K, V)> {
+        let expired = self.deadlines
+            .iter()
+            .next()
+            .filter(|&&(deadline, _)| deadline <= self.clock.now())
+            .map(|&(_, index)| index);
+        let index = expired.or_else(|| self.entries.back())?;
+        let e = self.take_entry(index);
+        Some((e.key, e.val))
+    }
+
+    // Remove the entry with the given index from the table, the deadlines and the slab.
+    fn take_entry(&mut self, index: usize) -> Entry<K, V> {
+        let hash = self.entries.get(index).hash;
+        self.table
+            .find_entry(hash, |&i| i == index)
+            .expect("table and slab are out of sync")
+            .remove();
+        let e = self.entries.remove(index);
+        self.deadlines.remove(&(e.deadline, index));
+        e
+    }
+}
+
+#[test]
+fn tlru_eviction() {
+    use time::MockClock;
+
+    let clock = MockClock::new();
+    let mut cache = TLRUCache::new(3, Duration::from_secs(10));
+    assert_eq!(Duration::from_secs(10), cache.ttu());
+    cache.set_clock(clock.clone());
+    cache.insert("a", 1);
+    cache.insert_with_ttu("b", 2, Duration::from_secs(2));
+    cache.insert("c", 3);
+    cache.get("b");
+    clock.advance(Duration::from_secs(3));
+    cache.get("a");
+    // The expired entry is evicted before the least-recently used one.
+    assert_eq!(Some(("b", 2)), cache.insert("d", 4).evicted);
+    assert_eq!(Some(("c", 3)), cache.insert("e", 5).evicted);
+    assert_eq!(Some(Duration::from_secs(7)), cache.remaining("a"));
+    // An access does not extend the time-to-use.
+    clock.advance(Duration::from_secs(7));
+    assert_eq!(None, cache.get("a"));
+    assert_eq!((None, 2), (cache.remaining("a"), cache.len()));
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(2, cache.purge_expired());
+    assert!(cache.is_empty());
+    // A time-to-use beyond the range of the clock lasts about a century.
+    cache.insert_with_ttu("f", 6, Duration::MAX);
+    assert!(cache.remaining("f").unwrap() >= FAR_FUTURE);
+}
@@ -0,0 +1,369 @@
+//! The full 2Q cache, as described by Johnson and Shasha in "2Q: A Low
+//! Overhead High Performance Buffer Management Replacement Algorithm"
+//! (VLDB 1994).
+
+use DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use error::CapacityError;
+use hashbrown::HashTable;
+use list::List;
+use lru::InsertResult;
+use stats::Stats;
+
+// default share of the capacity that is held by A1in
+pub const DEFAULT_IN_RATIO: f64 = 0.25;
+// default number of keys remembered by A1out, relative to the capacity
+pub const DEFAULT_OUT_RATIO: f64 = 0.5;
+
+// The queue of a cached entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Queue {
+    // A1in, the entries that were inserted and not accessed since
+    In,
+    // Am, the entries that returned after they were evicted from A1in
+    Main,
+}
+
+struct Entry<K, V> {
+    key: K,
+    val: V,
+    // hash of the key, computed once when the entry was inserted
+    hash: u64,
+}
+
+// New entries enter A1in, a FIFO queue in which lookups do not reorder
+// the entries. An entry that leaves A1in is evicted and its key is
+// remembered in A1out, and a key that is inserted again while it is
+// remembered enters Am, an LRU queue of the entries that were needed
+// more than once. While A1in holds more than its share of the capacity,
+// eviction takes the oldest entry of A1in, and otherwise the
+// least-recently used entry of Am. A scan only cycles through A1in.
+//
+// A1out records only the hashes of the keys, so that keys are not
+// cloned or kept alive after their eviction.
+pub struct TwoQueueCache<K, V, S = DefaultHashBuilder> {
+    // maximum number of entries stored in the cache
+    capacity: usize,
+    // number of entries in A1in above which A1in is evicted first
+    in_capacity: usize,
+    // maximum number of keys remembered by A1out
+    out_capacity: usize,
+    hash_builder: S,
+    // locations of the cached entries in A1in and Am, hashed by key
+    table: HashTable<(Queue, usize)>,
+    // A1in from the newest entry at the front to the oldest at the back
+    recent: List<Entry<K, V>>,
+    // Am from the most-recently used entry at the front
+    // to the least-recently used entry at the back
+    main: List<Entry<K, V>>,
+    // indices of the hashes in A1out
+    ghosts: HashTable<usize>,
+    // A1out from the most recently evicted hash at the front
+    out: List<u64>,
+    stats: Stats,
+}
+
+impl<K, V> TwoQueueCache<K, V>
+    where K: Eq + Hash
+{
+    // Panics if the capacity is zero.
+    pub fn new(capacity: usize) -> TwoQueueCache<K, V> {
+        TwoQueueCache::with_ratios(capacity, DEFAULT_IN_RATIO, DEFAULT_OUT_RATIO)
+    }
+
+    // A1in holds in_ratio of the capacity and A1out remembers
+    // out_ratio times the capacity of keys, both rounded.
+    // Panics if the capacity is zero or a ratio is negative.
+    pub fn with_ratios(capacity: usize, in_ratio: f64, out_ratio: f64) -> TwoQueueCache<K, V> {
+        TwoQueueCache::with_hasher(capacity, in_ratio, out_ratio, DefaultHashBuilder::default())
+    }
+
+    pub fn try_new(capacity: usize) -> Result<TwoQueueCache<K, V>, CapacityError> {
+        TwoQueueCache::try_with_hasher(capacity,
+                                       DEFAULT_IN_RATIO,
+                                       DEFAULT_OUT_RATIO,
+                                       DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> TwoQueueCache<K, V, S>
+    where K: Eq + Hash,
+          S: BuildHasher
+{
+    // Panics if the capacity is zero or a ratio is negative.
+    pub fn with_hasher(capacity: usize,
+                       in_ratio: f64,
+                       out_ratio: f64,
+                       hash_builder: S)
+                       -> TwoQueueCache<K, V, S> {
+        match TwoQueueCache::try_with_hasher(capacity, in_ratio, out_ratio, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if a ratio is negative.
+    pub fn try_with_hasher(capacity: usize,
+                           in_ratio: f64,
+                           out_ratio: f64,
+                           hash_builder: S)
+                           -> Result<TwoQueueCache<K, V, S>, CapacityError> {
+        assert!(in_ratio >= 0.0 && out_ratio >= 0.0, "queue ratios must not be negative");
+        if capacity == 0 {
+            return Err(CapacityError);
+        }
+        let out_capacity = share(capacity, out_ratio);
+        Ok(TwoQueueCache {
+            capacity,
+            in_capacity: share(capacity, in_ratio),
+            out_capacity,
+            hash_builder,
+            table: HashTable::with_capacity(capacity),
+            recent: List::new(),
+            main: List::new(),
+            ghosts: HashTable::with_capacity(out_capacity),
+            out: List::with_capacity(out_capacity),
+            stats: Stats::default(),
+        })
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &e.val)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.lookup(key).map(|e| &mut e.val)
+    }
+
+    // Read the value without promoting the entry.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        self.locate(hash, key).map(|(queue, index)| &self.queue(queue).get(index).val)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        self.peek(key).is_some()
+    }
+
+    // Replacing the value of a key counts as an access.
+    pub fn insert(&mut self, key: K, val: V) -> InsertResult<K, V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(loc) = self.locate(hash, &key) {
+            self.stats.replacements += 1;
+            let e = self.access(loc);
+            return InsertResult {
+                replaced: Some(mem::replace(&mut e.val, val)),
+                evicted: None,
+            };
+        }
+        self.stats.insertions += 1;
+        let queue = if self.take_ghost(hash) { Queue::Main } else { Queue::In };
+        let evicted = self.reclaim();
+        if evicted.is_some() {
+            self.stats.evictions += 1;
+        }
+        let index = self.queue_mut(queue).push_front(Entry { key, val, hash });
+        let (recent, main) = (&self.recent, &self.main);
+        self.table.insert_unique(hash, (queue, index), |&loc| entry_hash(recent, main, loc));
+        InsertResult { replaced: None, evicted }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key)?;
+        Some(self.take_entry(loc).val)
+    }
+
+    // A1out is cleared as well.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.recent.clear();
+        self.main.clear();
+        self.ghosts.clear();
+        self.out.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.main.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // The number of cached entries in A1in and in Am,
+    // and the number of keys remembered by A1out.
+    pub fn queues(&self) -> (usize, usize, usize) {
+        (self.recent.len(), self.main.len(), self.out.len())
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn lookup<Q>(&mut self, key: &Q) -> Option<&mut Entry<K, V>>
+        where K: Borrow<Q>,
+              Q: ?Sized + Hash + Eq
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let loc = self.locate(hash, key);
+        self.stats.record_lookup(loc.is_some());
+        Some(self.access(loc?))
+    }
+
+    // An access promotes an entry of Am. The entries of A1in keep their
+    // position, because the accesses that closely follow an insertion
+    // are usually correlated with it.
+    fn access(&mut self, (queue, index): (Queue, usize)) -> &mut Entry<K, V> {
+        if queue == Queue::Main {
+            self.main.move_to_front(index);
+        }
+        self.queue_mut(queue).get_mut(index)
+    }
+
+    fn locate<Q>(&self, hash: u64, key: &Q) -> Option<(Queue, usize)>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq
+    {
+        self.table
+            .find(hash, |&(queue, i)| self.queue(queue).get(i).key.borrow() == key)
+            .copied()
+    }
+
+    fn queue(&self, queue: Queue) -> &List<Entry<K, V>> {
+        match queue {
+            Queue::In => &self.recent,
+            Queue::Main => &self.main,
+        }
+    }
+
+    fn queue_mut(&mut self, queue: Queue) -> &mut List<Entry<K, V>> {
+        match queue {
+            Queue::In => &mut self.recent,
+            Queue::Main => &mut self.main,
+        }
+    }
+
+    // Evict an entry if the cache is full. The oldest entry of A1in is
+    // evicted while A1in exceeds its share, and is remembered in A1out.
+    fn reclaim(&mut self) -> Option<(K, V)> {
+        if self.len() < self.capacity {
+            return None;
+        }
+        if self.recent.len() > self.in_capacity || self.main.len() == 0 {
+            let index = self.recent.back()?;
+            let e = self.take_entry((Queue::In, index));
+            self.remember(e.hash);
+            Some((e.key, e.val))
+        } else {
+            let index = self.main.back()?;
+            let e = self.take_entry((Queue::Main, index));
+            Some((e.key, e.val))
+        }
+    }
+
+    // Add a hash to A1out and forget the oldest hash if A1out is full.
+    fn remember(&mut self, hash: u64) {
+        if self.out_capacity == 0 {
+            return;
+        }
+        if self.out.len() >= self.out_capacity {
+            if let Some(index) = self.out.back() {
+                let hash = self.out.remove(index);
+                self.ghosts
+                    .find_entry(hash, |&i| i == index)
+                    .expect("ghost table and A1out are out of sync")
+                    .remove();
+            }
+        }
+        let index = self.out.push_front(hash);
+        let out = &self.out;
+        self.ghosts.insert_unique(hash, index, |&i| *out.get(i));
+    }
+
+    // Remove a hash from A1out. Returns true if it was remembered.
+    fn take_ghost(&mut self, hash: u64) -> bool {
+        let out = &self.out;
+        match self.ghosts.find_entry(hash, |&i| *out.get(i) == hash) {
+            Ok(entry) => {
+                let (index, _) = entry.remove();
+                self.out.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    // Remove a cached entry from the table and its queue.
+    fn take_entry(&mut self, loc: (Queue, usize)) -> Entry<K, V> {
+        let hash = self.queue(loc.0).get(loc.1).hash;
+        self.table
+            .find_entry(hash, |&l| l == loc)
+            .expect("table and queues are out of sync")
+            .remove();
+        self.queue_mut(loc.0).remove(loc.1)
+    }
+}
+
+// A ratio of the capacity, rounded to the nearest number of entries.
+fn share(capacity: usize, ratio: f64) -> usize {
+    (capacity as f64 * ratio + 0.5) as usize
+}
+
+fn entry_hash<K, V>(recent: &List<Entry<K, V>>,
+                    main: &List<Entry<K, V>>,
+                    (queue, index): (Queue, usize))
+                    -> u64 {
+    match queue {
+        Queue::In => recent.get(index).hash,
+        Queue::Main => main.get(index).hash,
+    }
+}
+
+#[test]
+fn twoq_queues() {
+    // A1in holds one entry and A1out remembers two keys.
+    let mut cache = TwoQueueCache::with_ratios(3, 0.34, 0.67);
+    for i in 0..3 {
+        cache.insert(i, i);
+    }
+    // A1in is evicted in insertion order, even when it was read.
+    cache.get(&0);
+    assert_eq!(Some((0, 0)), cache.insert(3, 3).evicted);
+    assert_eq!(Some((1, 1)), cache.insert(4, 4).evicted);
+    assert_eq!((3, 0, 2), cache.queues());
+    // A key that is remembered by A1out enters Am.
+    assert_eq!(Some((2, 2)), cache.insert(0, 0).evicted);
+    assert_eq!((2, 1, 2), cache.queues());
+    // A scan evicts only from A1in until A1in is within its share.
+    for i in 10..20 {
+        cache.insert(i, i);
+    }
+    assert_eq!(Some(&0), cache.peek(&0));
+    assert_eq!((2, 1, 2), cache.queues());
+    // Once A1in is within its share, Am is evicted in LRU order.
+    cache.insert(17, 17);
+    assert_eq!((1, 2, 2), cache.queues());
+    assert_eq!(Some((0, 0)), cache.insert(1, 1).evicted);
+}